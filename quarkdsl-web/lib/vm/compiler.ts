@@ -287,6 +287,7 @@ export class Compiler {
       "print_float",
       "print_bool",
       "print_array",
+      "assert",
       "sqrt",
       "sin",
       "cos",
@@ -305,6 +306,8 @@ export class Compiler {
       "max",
       "pow",
       "len",
+      "to_float",
+      "to_int",
       "random",
       "pi",
       "sum",
@@ -344,6 +344,13 @@ export class Interpreter {
         this.stack.push(0);
         break;
 
+      case "assert":
+        if (!args[0]) {
+          throw new Error("Assertion failed");
+        }
+        this.stack.push(0);
+        break;
+
       case "sqrt":
         this.stack.push(Math.sqrt(args[0] as number));
         break;
@@ -396,6 +403,14 @@ export class Interpreter {
         this.stack.push((args[0] as Value[]).length);
         break;
 
+      case "to_float":
+        this.stack.push(args[0] as number);
+        break;
+
+      case "to_int":
+        this.stack.push(Math.trunc(args[0] as number));
+        break;
+
       case "random":
         this.stack.push(Math.random());
         break;
@@ -84,6 +84,9 @@ const KEYWORDS: Record<string, TokenType> = {
   for: TokenType.For,
   in: TokenType.In,
   map: TokenType.Map,
+  and: TokenType.AndAnd,
+  or: TokenType.OrOr,
+  not: TokenType.Bang,
   int: TokenType.Int,
   float: TokenType.Float,
   bool: TokenType.Bool,
@@ -79,6 +79,24 @@ fn main() -> int {
 }`,
     expected: 9,
   },
+  {
+    name: "Passing assert",
+    code: `
+fn main() -> int {
+    assert(1 == 1);
+    return 1;
+}`,
+    expected: 1,
+  },
+  {
+    name: "Failing assert",
+    code: `
+fn main() -> int {
+    assert(false);
+    return 1;
+}`,
+    expectedError: "Assertion failed",
+  },
 ];
 
 console.log("QuarkDSL VM Test Suite");
@@ -91,6 +109,17 @@ for (const test of tests) {
   console.log(`\nTest: ${test.name}`);
   const result = execute(test.code);
 
+  if (test.expectedError !== undefined) {
+    if (!result.success && result.error?.includes(test.expectedError)) {
+      console.log(`  PASSED: failed with ${result.error}`);
+      passed++;
+    } else {
+      console.log(`  FAILED: expected error "${test.expectedError}"`);
+      failed++;
+    }
+    continue;
+  }
+
   if (!result.success) {
     console.log(`  FAILED: ${result.error}`);
     failed++;
@@ -0,0 +1,99 @@
+// Classical Feed-forward shared by the Qiskit circuit builders of the
+// quantum and orchestrator backends: `if m == 1 { ... }` on a measured bit
+// becomes a `with circuit.if_test(...)` block around the arm's gates.
+
+use crate::middle::ir::*;
+use std::collections::HashMap;
+
+// Nest generated statements one level deeper inside a statement indented by
+// `depth` spaces. An arm with no statements (only comments, or nothing)
+// gets a `pass` so the Python stays valid.
+pub(crate) fn indent_python(code: &str, depth: usize) -> String {
+    let mut output: String = code.lines().map(|line| format!("    {}\n", line)).collect();
+    if code.lines().all(|line| line.trim().is_empty() || line.trim_start().starts_with('#')) {
+        output.push_str(&format!("{}    pass\n", " ".repeat(depth)));
+    }
+    output
+}
+
+// A branch on a measured bit: `if m == value` where m = measure(q) wrote cr[bit]
+pub(crate) struct ClassicalCondition {
+    pub bit: usize,
+    pub value: i64,
+    pub then_label: String,
+    pub else_label: Option<String>,
+}
+
+// Branches whose condition compares a measurement result with 0 or 1, keyed
+// by the branching block's label. Only simple if/else shapes qualify: each
+// arm is one block jumping to the merge block.
+pub(crate) fn classical_conditions(func: &IRFunction) -> HashMap<String, ClassicalCondition> {
+    // Measurement result variables (and plain copies of them) -> classical bit
+    let mut bits: HashMap<SSAVar, usize> = HashMap::new();
+    let mut comparisons: HashMap<SSAVar, (BinOp, &Value, &Value)> = HashMap::new();
+    let mut next_bit = 0;
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            match inst {
+                Instruction::Call { function, dest, .. } if function == "measure" => {
+                    if let Some(d) = dest {
+                        bits.insert(*d, next_bit);
+                    }
+                    next_bit += 1;
+                }
+                Instruction::Assign { dest, value: Value::Var(src) } => {
+                    if let Some(&b) = bits.get(src) {
+                        bits.insert(*dest, b);
+                    }
+                }
+                Instruction::BinaryOp { dest, op, left, right } => {
+                    comparisons.insert(*dest, (*op, left, right));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let jump_target = |label: &str| {
+        func.blocks.iter().find(|b| b.label == label).and_then(|b| match &b.terminator {
+            Terminator::Jump(target) => Some(target.clone()),
+            _ => None,
+        })
+    };
+
+    let mut conditions = HashMap::new();
+    for block in &func.blocks {
+        let Terminator::Branch { condition: Value::Var(c), true_label, false_label } = &block.terminator else {
+            continue;
+        };
+        let Some(&(op, left, right)) = comparisons.get(c) else {
+            continue;
+        };
+        let (bit, constant) = match (left, right) {
+            (Value::Var(v), Value::Int(n)) | (Value::Int(n), Value::Var(v)) if bits.contains_key(v) => (bits[v], *n),
+            _ => continue,
+        };
+        if constant != 0 && constant != 1 {
+            continue;
+        }
+        let value = match op {
+            BinOp::Eq => constant,
+            BinOp::Ne => 1 - constant,
+            _ => continue,
+        };
+
+        let Some(merge) = jump_target(true_label) else { continue };
+        let else_label = if merge == *false_label {
+            None
+        } else if jump_target(false_label).as_deref() == Some(merge.as_str()) {
+            Some(false_label.clone())
+        } else {
+            continue;
+        };
+        conditions.insert(
+            block.label.clone(),
+            ClassicalCondition { bit, value, then_label: true_label.clone(), else_label },
+        );
+    }
+    conditions
+}
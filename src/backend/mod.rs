@@ -1,3 +1,44 @@
 pub mod wgsl;
 pub mod quantum;
 pub mod orchestrator;
+mod structure;
+mod common;
+
+use crate::middle::ir::{Instruction, Module, SSAVar, Value};
+use anyhow::{bail, Result};
+
+// GPU Atomics: the buffer an atomic_add/atomic_max call updates
+pub(crate) fn atomic_buffer(inst: &Instruction) -> Option<SSAVar> {
+    match inst {
+        Instruction::Call { function, args, .. } if matches!(function.as_str(), "atomic_add" | "atomic_max") => {
+            match args.first() {
+                Some(Value::Var(buffer)) => Some(*buffer),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Map: `map(f, xs)` is lowered to a call to `map_f`, which no backend
+// defines yet. Report it (an error under --strict) instead of silently
+// emitting a call to a function that doesn't exist.
+pub(crate) fn check_unlowered_maps(module: &Module, strict: bool) -> Result<()> {
+    for func in &module.functions {
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            let Instruction::Call { function, .. } = inst else { continue };
+            let Some(mapped) = function.strip_prefix("map_") else { continue };
+            if module.functions.iter().any(|f| f.name == *function) {
+                continue;
+            }
+            if strict {
+                bail!("map({}, ...) in '{}' is not supported by this backend", mapped, func.name);
+            }
+            warn!(
+                "map({}, ...) in '{}' is not supported by this backend yet (it lowers to a call to undefined function {})",
+                mapped, func.name, function
+            );
+        }
+    }
+    Ok(())
+}
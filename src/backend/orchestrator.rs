@@ -1,19 +1,32 @@
-/// Python Orchestrator Backend - Generates hybrid execution code
-///
-/// This backend generates Python code that orchestrates:
-/// 1. GPU execution (WGSL via WebGPU)
-/// 2. Quantum execution (Qiskit)
-/// 3. Data marshalling between domains
+//! Python Orchestrator Backend - Generates hybrid execution code
+//!
+//! This backend generates Python code that orchestrates:
+//! 1. GPU execution (WGSL via WebGPU)
+//! 2. Quantum execution (Qiskit)
+//! 3. Data marshalling between domains
 
 use super::super::middle::ir::*;
-use anyhow::Result;
+use super::common::{classical_conditions, indent_python};
+use super::structure::Stmt;
+use anyhow::{bail, Result};
 
-pub fn generate_orchestrator(module: &Module) -> Result<String> {
+/// Generate the Python orchestrator for a whole module.
+pub fn generate_orchestrator(module: &Module, seed: Option<u64>, strict: bool) -> Result<String> {
     let mut output = String::new();
+    super::check_unlowered_maps(module, strict)?;
+
+    // Calls between @quantum functions become the callee's gates in place
+    // and qubit indices held in constant variables become literals
+    let mut module = module.clone();
+    crate::middle::inline_quantum_calls(&mut module);
+    for func in module.functions.iter_mut().filter(|f| f.domain == crate::frontend::ast::Domain::Quantum) {
+        crate::middle::resolve_constants(func);
+    }
 
     // Python imports
     output.push_str("#!/usr/bin/env python3\n");
     output.push_str("\"\"\"QuarkDSL Hybrid Orchestrator - Auto-generated\"\"\"\n\n");
+    output.push_str("import copy\n");
     output.push_str("import numpy as np\n");
     output.push_str("try:\n");
     output.push_str("    from qiskit import QuantumCircuit, QuantumRegister, ClassicalRegister\n");
@@ -38,7 +51,12 @@ pub fn generate_orchestrator(module: &Module) -> Result<String> {
     output.push_str("import os\n\n");
     output.push_str("DEBUG_MODE = os.getenv(\"DEBUG_MODE\", \"false\").lower() == \"true\"\n");
     output.push_str("USE_QUANTUM_COMPUTER = os.getenv(\"USE_QUANTUM_COMPUTER\", \"false\").lower() == \"true\"\n");
-    output.push_str("IBM_API_KEY = os.getenv(\"IBM_API_KEY\", \"\")\n\n");
+    output.push_str("IBM_API_KEY = os.getenv(\"IBM_API_KEY\", \"\")\n");
+    // Fixed seed makes simulation and transpilation reproducible
+    match seed {
+        Some(seed) => output.push_str(&format!("SEED = {}\n\n", seed)),
+        None => output.push_str("SEED = None\n\n"),
+    }
     output.push_str("if USE_QUANTUM_COMPUTER and not IBM_API_KEY:\n");
     output.push_str("    raise ValueError(\"IBM_API_KEY environment variable must be set when USE_QUANTUM_COMPUTER=true\")\n\n");
 
@@ -47,13 +65,39 @@ pub fn generate_orchestrator(module: &Module) -> Result<String> {
 
     // Generate function implementations
     for func in &module.functions {
-        output.push_str(&generate_function(func)?);
-        output.push_str("\n");
+        output.push_str(&generate_function(func, strict)?);
+        output.push('\n');
+    }
+
+    // Entry point: run `main` when the script is executed directly
+    if let Some(main_func) = module.functions.iter().find(|f| f.name == "main") {
+        output.push_str(&generate_entry_point(main_func));
     }
 
     Ok(output)
 }
 
+fn generate_entry_point(main_func: &IRFunction) -> String {
+    let mut output = String::from("if __name__ == \"__main__\":\n");
+    if !main_func.params.is_empty() {
+        // No way to know the arguments; show the expected call instead
+        let params: Vec<String> = main_func
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect();
+        let args: Vec<&str> = main_func.params.iter().map(|(name, _)| name.as_str()).collect();
+        output.push_str(&format!("    # main({}) takes arguments:\n", params.join(", ")));
+        output.push_str(&format!("    # print(main({}))\n", args.join(", ")));
+        output.push_str("    pass\n");
+    } else if main_func.return_type == IRType::Void {
+        output.push_str("    main()\n");
+    } else {
+        output.push_str("    print(main())\n");
+    }
+    output
+}
+
 fn generate_helpers() -> String {
     r#"# ============================================================================
 # Helper Functions for Domain Conversions
@@ -65,9 +109,13 @@ def encode_angle(data):
         return float(data)
     return np.array(data, dtype=float)
 
-def encode_amplitude(data):
+def encode_amplitude(data, size=None):
     """Convert classical array to quantum state using amplitude encoding"""
-    data = np.array(data, dtype=float)
+    # Complex amplitudes keep their phase; real data stays real
+    data = np.array(data, dtype=complex if np.iscomplexobj(data) else float)
+    # Zero-pad to the register's 2^n amplitudes
+    if size is not None and len(data) < size:
+        data = np.pad(data, (0, size - len(data)))
     # Normalize to unit vector
     norm = np.linalg.norm(data)
     if norm > 0:
@@ -76,6 +124,9 @@ def encode_amplitude(data):
 
 def extract_measurement(counts):
     """Extract classical value from quantum measurement counts"""
+    # Values read back from a measurement are already classical
+    if isinstance(counts, (int, float, np.ndarray, list)):
+        return counts
     # Get most common measurement result
     if not counts:
         return 0
@@ -83,8 +134,19 @@ def extract_measurement(counts):
     # Convert binary string to int
     return int(most_common, 2)
 
-def run_quantum_circuit(circuit, shots=1024):
+def extract_bits(counts):
+    """Most common measurement result as a list of bits, qubit 0 first"""
+    if not counts:
+        return []
+    most_common = max(counts, key=counts.get).replace(" ", "")
+    # Qiskit bitstrings put qubit 0 rightmost
+    return [int(b) for b in reversed(most_common)]
+
+def run_quantum_circuit(circuit, shots=1024, seed=None, device=None):
     """Execute quantum circuit and return counts"""
+    # A per-function seed (@quantum(seed=N)) overrides the global one
+    if seed is None:
+        seed = SEED
     if not QISKIT_AVAILABLE:
         print("Error: Qiskit is required for quantum circuit execution")
         print("Install with: pip install qiskit qiskit-aer qiskit-ibm-runtime")
@@ -122,13 +184,17 @@ def run_quantum_circuit(circuit, shots=1024):
                     if DEBUG_MODE:
                         print(f"  Debug: {e}")
                     _aer_fallback_shown = True
-                simulator = AerSimulator()
+                simulator = AerSimulator(seed_simulator=seed)
                 job = simulator.run(circuit, shots=shots)
                 result = job.result()
                 counts = result.get_counts()
                 return counts
         else:
-            backend = service.least_busy(operational=True, simulator=False)
+            if device:
+                # A device named by @quantum(backend="...") or --target-device
+                backend = service.backend(device)
+            else:
+                backend = service.least_busy(operational=True, simulator=False)
             if not _aer_fallback_shown:
                 print(f"\\n{'='*60}")
                 print(f"Using real IBM Quantum hardware: {backend.name}")
@@ -137,7 +203,7 @@ def run_quantum_circuit(circuit, shots=1024):
                 _aer_fallback_shown = True
 
         # Transpile circuit for target hardware
-        pm = generate_preset_pass_manager(backend=backend, optimization_level=3)
+        pm = generate_preset_pass_manager(backend=backend, optimization_level=3, seed_transpiler=seed)
         transpiled_circuit = pm.run(circuit)
 
         if DEBUG_MODE:
@@ -145,6 +211,8 @@ def run_quantum_circuit(circuit, shots=1024):
             print(f"Transpiled circuit depth: {transpiled_circuit.depth()}")
 
         sampler = Sampler(backend)
+        if seed is not None and use_cloud_simulator:
+            sampler.options.simulator.seed_simulator = seed
         job = sampler.run([transpiled_circuit], shots=shots)
 
         print(f"\\n{'='*60}")
@@ -199,7 +267,7 @@ def run_quantum_circuit(circuit, shots=1024):
         # Use local simulator
         if DEBUG_MODE:
             print("Using local Qiskit Aer simulator")
-        simulator = AerSimulator()
+        simulator = AerSimulator(seed_simulator=seed)
         job = simulator.run(circuit, shots=shots)
         result = job.result()
         counts = result.get_counts()
@@ -223,7 +291,7 @@ def simulate_gpu_function(func_name, *args):
 "#.to_string()
 }
 
-fn generate_function(func: &IRFunction) -> Result<String> {
+fn generate_function(func: &IRFunction, strict: bool) -> Result<String> {
     let mut output = String::new();
 
     // Function signature
@@ -236,8 +304,35 @@ fn generate_function(func: &IRFunction) -> Result<String> {
     }
     output.push_str("):\n");
 
-    // Docstring with domain info
-    output.push_str(&format!("    \"\"\"Domain: {:?}\"\"\"\n", func.domain));
+    // Docstring: the source `///` doc comment, else the domain
+    match &func.doc {
+        Some(doc) => output.push_str(&python_docstring(doc)),
+        None => output.push_str(&format!("    \"\"\"Domain: {:?}\"\"\"\n", func.domain)),
+    }
+
+    // Explicit-width parameters (int32, float64, ...) become NumPy values
+    for (name, ty) in &func.params {
+        match ty {
+            IRType::Array(elem, _) => {
+                if let Some(dtype) = numpy_dtype(elem) {
+                    output.push_str(&format!("    {0} = np.asarray({0}, dtype={1})\n", name, dtype));
+                }
+            }
+            _ => {
+                if let Some(dtype) = numpy_dtype(ty) {
+                    output.push_str(&format!("    {0} = {1}({0})\n", name, dtype));
+                }
+            }
+        }
+    }
+
+    // Python has no phi: merged values become assignments in each
+    // predecessor, ahead of rebuilding if/else and loops
+    let mut func = func.clone();
+    if func.domain != crate::frontend::ast::Domain::Quantum {
+        crate::middle::eliminate_function_phis(&mut func);
+    }
+    let func = &func;
 
     // Function body based on domain
     match func.domain {
@@ -245,7 +340,7 @@ fn generate_function(func: &IRFunction) -> Result<String> {
             output.push_str(&generate_gpu_function_body(func)?);
         }
         crate::frontend::ast::Domain::Quantum => {
-            output.push_str(&generate_quantum_function_body(func)?);
+            output.push_str(&generate_quantum_function_body(func, strict)?);
         }
         crate::frontend::ast::Domain::Classical => {
             output.push_str(&generate_classical_function_body(func)?);
@@ -268,28 +363,55 @@ fn generate_gpu_function_body(func: &IRFunction) -> Result<String> {
     // Build inline map for single-use variables
     let inline_map = build_inline_map(func);
 
+    // Storage buffers the kernel writes are mutated in place, like the GPU
+    // buffer, and a `void` kernel hands them back for the caller to read
+    let written = written_params(func);
+    let write_back = match written.as_slice() {
+        [] => None,
+        [param] => Some(format!("    return {}\n", func.params[*param].0)),
+        params => {
+            let names: Vec<_> = params.iter().map(|p| func.params[*p].0.as_str()).collect();
+            Some(format!("    return ({})\n", names.join(", ")))
+        }
+    };
+
     // Generate instructions (skip inlined ones)
-    for block in &func.blocks {
-        for inst in &block.instructions {
-            // Skip instructions that define variables to be inlined
-            if let Some(dest) = get_dest_var(inst) {
-                if inline_map.contains_key(&dest.id) {
-                    continue;
-                }
+    let emitter = PythonEmitter { func, var_names: &var_names, inline_map: &inline_map, write_back: write_back.as_deref() };
+    emitter.emit(&super::structure::structure(func)?, 1, &mut Vec::new(), &mut output)?;
+
+    Ok(output)
+}
+
+// Parameters (by position, in order) that some `store` or atomic writes into
+fn written_params(func: &IRFunction) -> Vec<usize> {
+    let mut written = Vec::new();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        let array = match inst {
+            Instruction::Store { array, .. } => Some(*array),
+            _ => super::atomic_buffer(inst),
+        };
+        if let Some(array) = array {
+            if array.id < func.params.len() && !written.contains(&array.id) {
+                written.push(array.id);
             }
-            output.push_str(&generate_python_instruction_with_inline(inst, &var_names, &inline_map)?);
         }
-        output.push_str(&generate_python_terminator_with_inline(&block.terminator, &var_names, &inline_map)?);
     }
-
-    Ok(output)
+    written.sort();
+    written
 }
 
-fn generate_quantum_function_body(func: &IRFunction) -> Result<String> {
+fn generate_quantum_function_body(func: &IRFunction, strict: bool) -> Result<String> {
     let mut output = String::new();
 
     // Estimate qubits needed
     let num_qubits = estimate_qubits(func);
+    // One classical bit per measure() call, else one per qubit
+    let num_measurements = count_measurements(func);
+    let num_classical = if num_measurements > 0 { num_measurements } else { num_qubits };
+    let measure_all = measures_all(func);
+    if measure_all && num_measurements > 0 {
+        bail!("measure_all() cannot be combined with measure() in '{}'", func.name);
+    }
 
     output.push_str(&format!("    # Quantum function - {} qubits\n", num_qubits));
     output.push_str("    if not QISKIT_AVAILABLE:\n");
@@ -297,7 +419,7 @@ fn generate_quantum_function_body(func: &IRFunction) -> Result<String> {
     output.push_str("        print(\"Install with: pip install qiskit qiskit-aer qiskit-ibm-runtime\")\n");
     output.push_str("        return 0\n\n");
     output.push_str(&format!("    qr = QuantumRegister({}, 'q')\n", num_qubits));
-    output.push_str(&format!("    cr = ClassicalRegister({}, 'c')\n", num_qubits));
+    output.push_str(&format!("    cr = ClassicalRegister({}, 'c')\n", num_classical));
     output.push_str("    circuit = QuantumCircuit(qr, cr)\n\n");
 
     // Build variable name mapping
@@ -309,12 +431,13 @@ fn generate_quantum_function_body(func: &IRFunction) -> Result<String> {
     // Build inline map for single-use variables
     let inline_map = build_inline_map(func);
 
-    // Track variables that come from measure() calls (including transitive assigns)
+    // Track variables that come from measure() calls, and everything computed
+    // from them: these are evaluated after the circuit has run
     let mut measure_vars = std::collections::HashSet::new();
     for block in &func.blocks {
         for inst in &block.instructions {
             if let Instruction::Call { function, dest, .. } = inst {
-                if function == "measure" {
+                if function == "measure" || function == "measure_all" {
                     if let Some(d) = dest {
                         measure_vars.insert(d.id);
                     }
@@ -323,18 +446,15 @@ fn generate_quantum_function_body(func: &IRFunction) -> Result<String> {
         }
     }
 
-    // Also track variables assigned from measure vars
     let mut changed = true;
     while changed {
         changed = false;
         for block in &func.blocks {
             for inst in &block.instructions {
-                if let Instruction::Assign { dest, value } = inst {
-                    if let Value::Var(var) = value {
-                        if measure_vars.contains(&var.id) && !measure_vars.contains(&dest.id) {
-                            measure_vars.insert(dest.id);
-                            changed = true;
-                        }
+                if let Some(dest) = get_dest_var(inst) {
+                    if !measure_vars.contains(&dest.id) && uses_any_var(inst, &measure_vars) {
+                        measure_vars.insert(dest.id);
+                        changed = true;
                     }
                 }
             }
@@ -342,37 +462,148 @@ fn generate_quantum_function_body(func: &IRFunction) -> Result<String> {
     }
 
     // Generate quantum operations (skip inlined ones and measure-related instructions)
+    let conditions = classical_conditions(func);
+    let conditioned: std::collections::HashSet<&str> = conditions
+        .values()
+        .flat_map(|c| std::iter::once(c.then_label.as_str()).chain(c.else_label.as_deref()))
+        .collect();
+    let mut bit = 0;
+    for block in &func.blocks {
+        // Conditioned blocks are emitted inside their branch's if_test
+        if conditioned.contains(block.label.as_str()) {
+            continue;
+        }
+        output.push_str(&circuit_block_code(block, &func.name, strict, &measure_vars, &var_names, &inline_map, &mut bit)?);
+
+        // Classical Feed-forward: `if m == 1 { ... }` on a measured bit runs
+        // its gates conditionally inside the circuit
+        if let Some(cond) = conditions.get(&block.label) {
+            let block_named = |label: &str| func.blocks.iter().find(|b| b.label == label);
+            output.push_str(&format!("    with circuit.if_test((cr[{}], {})) as else_:\n", cond.bit, cond.value));
+            let then_code = match block_named(&cond.then_label) {
+                Some(b) => circuit_block_code(b, &func.name, strict, &measure_vars, &var_names, &inline_map, &mut bit)?,
+                None => String::new(),
+            };
+            output.push_str(&indent_python(&then_code, 4));
+            if let Some(else_block) = cond.else_label.as_deref().and_then(block_named) {
+                let else_code = circuit_block_code(else_block, &func.name, strict, &measure_vars, &var_names, &inline_map, &mut bit)?;
+                output.push_str("    with else_:\n");
+                output.push_str(&indent_python(&else_code, 4));
+            }
+        }
+    }
+
+    // Without explicit measurements, measure the whole register at the end
+    if num_measurements == 0 && !measure_all {
+        output.push_str("\n    # Measurements\n");
+        output.push_str("    circuit.measure(qr, cr)\n");
+    }
+    // Circuit Diagram: DEBUG_MODE=true prints the circuit as ASCII art
+    output.push_str("    if DEBUG_MODE:\n");
+    output.push_str("        print(circuit.draw())\n");
+    // @quantum(shots=N, seed=M, backend="...") arguments
+    let mut run_args = String::new();
+    if let Some(shots) = func.annotations.get("shots") {
+        run_args.push_str(&format!(", shots={}", shots));
+    }
+    if let Some(seed) = func.annotations.get("seed") {
+        run_args.push_str(&format!(", seed={}", seed));
+    }
+    if let Some(device) = &func.device {
+        run_args.push_str(&format!(", device={:?}", device));
+    }
+    output.push_str(&format!("    counts = run_quantum_circuit(circuit{})\n", run_args));
+    output.push_str("    result = extract_measurement(counts)\n");
+
+    // Read each measure() result back as its classical bit: cr[k] is bit k
+    // of the most common outcome
+    let mut return_value = None;
+    let mut bit = 0;
     for block in &func.blocks {
         for inst in &block.instructions {
-            // Skip measure() calls - we do global measurement at the end
-            if let Instruction::Call { function, .. } = inst {
-                if function == "measure" {
-                    continue;
+            match inst {
+                Instruction::Call { function, dest, .. } if function == "measure" => {
+                    if let Some(dest) = dest {
+                        output.push_str(&format!("    {} = (result >> {}) & 1\n", var_name(dest.id, &var_names), bit));
+                    }
+                    bit += 1;
                 }
+                // measure_all() reads back one bit per qubit, qubit 0 first
+                Instruction::Call { function, dest: Some(dest), .. } if function == "measure_all" => {
+                    output.push_str(&format!("    {} = extract_bits(counts)\n", var_name(dest.id, &var_names)));
+                }
+                _ => {
+                    if let Some(dest) = get_dest_var(inst) {
+                        if measure_vars.contains(&dest.id) && !inline_map.contains_key(&dest.id) {
+                            output.push_str(&generate_python_instruction_with_inline(inst, func, &var_names, &inline_map)?);
+                        }
+                    }
+                }
+            }
+        }
+        if let Terminator::Return(val) = &block.terminator {
+            if value_uses_any_var(val, &measure_vars) {
+                return_value = Some(python_value_with_inline(val, &var_names, &inline_map));
             }
+        }
+    }
+
+    // Functions that compute their result from measurements return it;
+    // otherwise the whole measured register is the result
+    match return_value {
+        Some(value) => output.push_str(&format!("    return {}\n", value)),
+        None => output.push_str("    return result\n"),
+    }
+
+    Ok(output)
+}
 
-            // Skip all Assign instructions in quantum functions - they're not needed for circuit building
-            if matches!(inst, Instruction::Assign { .. }) {
+// Circuit operations of one block of a quantum function. Measurements happen
+// where they are called (into the next classical bit); their results, and
+// everything computed from them, are read back after the run.
+fn circuit_block_code(
+    block: &BasicBlock,
+    func_name: &str,
+    strict: bool,
+    measure_vars: &std::collections::HashSet<usize>,
+    var_names: &std::collections::HashMap<usize, String>,
+    inline_map: &std::collections::HashMap<usize, String>,
+    bit: &mut usize,
+) -> Result<String> {
+    let mut output = String::new();
+    for inst in &block.instructions {
+        if let Instruction::Call { function, args, .. } = inst {
+            if function == "measure" {
+                let qubit = args
+                    .first()
+                    .map_or("0".to_string(), |q| python_value_with_inline(q, var_names, inline_map));
+                let code = format!("    circuit.measure(qr[{}], cr[{}])\n", qubit, bit);
+                output.push_str(&with_line_comment(inst, code));
+                *bit += 1;
                 continue;
             }
-
-            // Skip instructions that define variables to be inlined
-            if let Some(dest) = get_dest_var(inst) {
-                if inline_map.contains_key(&dest.id) {
-                    continue;
-                }
+            if function == "measure_all" {
+                output.push_str(&with_line_comment(inst, "    circuit.measure(qr, cr)\n".to_string()));
+                continue;
             }
-            output.push_str(&generate_quantum_instruction_with_inline(inst, &var_names, &inline_map)?);
         }
-    }
+        if get_dest_var(inst).is_some_and(|d| measure_vars.contains(&d.id)) {
+            continue;
+        }
 
-    // Add measurements
-    output.push_str("\n    # Measurements\n");
-    output.push_str("    circuit.measure(qr, cr)\n");
-    output.push_str("    counts = run_quantum_circuit(circuit)\n");
-    output.push_str("    result = extract_measurement(counts)\n");
-    output.push_str("    return result\n");
+        // Skip all Assign instructions in quantum functions - they're not needed for circuit building
+        if matches!(inst, Instruction::Assign { .. }) {
+            continue;
+        }
 
+        // Skip instructions that define variables to be inlined
+        if let Some(dest) = get_dest_var(inst) {
+            if inline_map.contains_key(&dest.id) {
+                continue;
+            }
+        }
+        output.push_str(&generate_quantum_instruction_with_inline(inst, func_name, strict, var_names, inline_map)?);
+    }
     Ok(output)
 }
 
@@ -390,30 +621,137 @@ fn generate_classical_function_body(func: &IRFunction) -> Result<String> {
     let inline_map = build_inline_map(func);
 
     // Generate instructions (skip inlined ones)
-    for block in &func.blocks {
-        for inst in &block.instructions {
-            // Skip instructions that define variables to be inlined
+    let emitter = PythonEmitter { func, var_names: &var_names, inline_map: &inline_map, write_back: None };
+    emitter.emit(&super::structure::structure(func)?, 1, &mut Vec::new(), &mut output)?;
+
+    Ok(output)
+}
+
+// Structured Control Flow in Python: a loop is `while True:` opening with
+// the header's instructions and exit test. Python has no `continuing`, so
+// the latch's instructions close the body and also run before each
+// `continue`.
+//
+//   Loop { header, exit when !c,        while True:
+//          body: [Block(body)],   =>        if not (i < n):
+//          latch }                              break
+//                                           ...
+//                                           i = (i + 1)
+struct PythonEmitter<'a> {
+    func: &'a IRFunction,
+    var_names: &'a std::collections::HashMap<usize, String>,
+    inline_map: &'a std::collections::HashMap<usize, String>,
+    write_back: Option<&'a str>, // Stands in for `return None` in a kernel writing buffers
+}
+
+impl PythonEmitter<'_> {
+    // `latches` holds the latch of each enclosing loop, innermost last
+    fn emit(&self, stmts: &[Stmt], depth: usize, latches: &mut Vec<Option<usize>>, out: &mut String) -> Result<()> {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Block(b) => self.emit_instructions(*b, depth, out)?,
+                Stmt::If { condition, negated, then, otherwise } => {
+                    let cond = python_value_with_inline(condition, self.var_names, self.inline_map);
+                    let cond = if *negated { format!("not {}", cond) } else { cond };
+                    out.push_str(&indented(&format!("    if {}:\n", cond), depth));
+                    self.emit_arm(then, depth + 1, latches, out)?;
+                    if !otherwise.is_empty() {
+                        out.push_str(&indented("    else:\n", depth));
+                        self.emit_arm(otherwise, depth + 1, latches, out)?;
+                    }
+                }
+                Stmt::Loop { header, condition, exit_when, body, latch } => {
+                    out.push_str(&indented("    while True:\n", depth));
+                    self.emit_instructions(*header, depth + 1, out)?;
+                    let cond = python_value_with_inline(condition, self.var_names, self.inline_map);
+                    let exit_cond = if *exit_when { cond } else { format!("not {}", cond) };
+                    out.push_str(&indented(&format!("    if {}:\n        break\n", exit_cond), depth + 1));
+                    latches.push(*latch);
+                    self.emit(body, depth + 1, latches, out)?;
+                    latches.pop();
+                    if let Some(latch) = latch {
+                        self.emit_instructions(*latch, depth + 1, out)?;
+                    }
+                }
+                Stmt::Return(val) => {
+                    out.push_str(&indented(&generate_python_return_with_inline(Some(val), self.var_names, self.inline_map), depth));
+                }
+                Stmt::ReturnVoid => {
+                    let code = match self.write_back {
+                        Some(write_back) => write_back.to_string(),
+                        None => generate_python_return_with_inline(None, self.var_names, self.inline_map),
+                    };
+                    out.push_str(&indented(&code, depth));
+                }
+                Stmt::Break => out.push_str(&indented("    break\n", depth)),
+                Stmt::Continue => {
+                    if let Some(Some(latch)) = latches.last() {
+                        self.emit_instructions(*latch, depth, out)?;
+                    }
+                    out.push_str(&indented("    continue\n", depth));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // An `if`/`else` arm whose instructions were all inlined still needs a statement
+    fn emit_arm(&self, stmts: &[Stmt], depth: usize, latches: &mut Vec<Option<usize>>, out: &mut String) -> Result<()> {
+        let start = out.len();
+        self.emit(stmts, depth, latches, out)?;
+        if out.len() == start {
+            out.push_str(&indented("    pass\n", depth));
+        }
+        Ok(())
+    }
+
+    fn emit_instructions(&self, block: usize, depth: usize, out: &mut String) -> Result<()> {
+        for inst in &self.func.blocks[block].instructions {
+            // Skip instructions that define variables to be inlined, and the
+            // `x = x` copies phi elimination leaves for unchanged loop values
             if let Some(dest) = get_dest_var(inst) {
-                if inline_map.contains_key(&dest.id) {
+                if self.inline_map.contains_key(&dest.id) {
                     continue;
                 }
             }
-            output.push_str(&generate_python_instruction_with_inline(inst, &var_names, &inline_map)?);
+            if matches!(inst, Instruction::Assign { dest, value: Value::Var(v) } if v == dest) {
+                continue;
+            }
+            let code = generate_python_instruction_with_inline(inst, self.func, self.var_names, self.inline_map)?;
+            out.push_str(&indented(&code, depth));
         }
-        output.push_str(&generate_python_terminator_with_inline(&block.terminator, &var_names, &inline_map)?);
+        Ok(())
     }
-
-    Ok(output)
 }
 
-fn generate_python_instruction(inst: &Instruction) -> Result<String> {
-    generate_python_instruction_with_names(inst, &std::collections::HashMap::new())
+// Generated lines carry one level of indentation (the function body);
+// nest them `depth` levels deep
+fn indented(code: &str, depth: usize) -> String {
+    let extra = "    ".repeat(depth.saturating_sub(1));
+    code.lines().map(|line| if line.is_empty() { "\n".to_string() } else { format!("{}{}\n", extra, line) }).collect()
 }
 
-fn generate_python_instruction_with_inline(inst: &Instruction, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> Result<String> {
+fn generate_python_instruction_with_inline(inst: &Instruction, func: &IRFunction, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> Result<String> {
     let code = match inst {
+        Instruction::Assign { dest, value: Value::Array(elements) } if elements.iter().any(|e| is_array_var(func, e, inline_map)) => {
+            // Rows that are array variables are copied into each slot, so the
+            // rows of `[row; 2]` don't alias `row` or each other
+            let elems = elements
+                .iter()
+                .map(|e| {
+                    let elem = python_value_with_inline(e, var_names, inline_map);
+                    if is_array_var(func, e, inline_map) { format!("copy.deepcopy({})", elem) } else { elem }
+                })
+                .collect::<Vec<_>>();
+            format!("    {} = [{}]\n", var_name(dest.id, var_names), elems.join(", "))
+        }
         Instruction::Assign { dest, value } => {
-            format!("    {} = {}\n", var_name(dest.id, var_names), python_value_with_inline(value, var_names, inline_map))
+            let value_str = python_value_with_inline(value, var_names, inline_map);
+            if is_array_copy(func, *dest, value, inline_map) {
+                format!("    {} = copy.deepcopy({})\n", var_name(dest.id, var_names), value_str)
+            } else {
+                format!("    {} = {}\n", var_name(dest.id, var_names), value_str)
+            }
         }
         Instruction::BinaryOp { dest, op, left, right } => {
             let op_str = match op {
@@ -430,12 +768,16 @@ fn generate_python_instruction_with_inline(inst: &Instruction, var_names: &std::
                 BinOp::Ge => ">=",
                 BinOp::And => "and",
                 BinOp::Or => "or",
+                BinOp::Shl => "<<",
+                BinOp::Shr => ">>",
             };
-            format!("    {} = {} {} {}\n",
-                var_name(dest.id, var_names),
-                python_value_with_inline(left, var_names, inline_map),
-                op_str,
-                python_value_with_inline(right, var_names, inline_map))
+            let left = elementwise_operand(python_value_with_inline(left, var_names, inline_map), func.var_types.get(dest));
+            let right = python_value_with_inline(right, var_names, inline_map);
+            if is_float_mod(op, func.var_types.get(dest)) {
+                format!("    {} = np.fmod({}, {})\n", var_name(dest.id, var_names), left, right)
+            } else {
+                format!("    {} = {} {} {}\n", var_name(dest.id, var_names), left, op_str, right)
+            }
         }
         Instruction::UnaryOp { dest, op, operand } => {
             let op_str = match op {
@@ -444,19 +786,23 @@ fn generate_python_instruction_with_inline(inst: &Instruction, var_names: &std::
             };
             format!("    {} = {}{}\n", var_name(dest.id, var_names), op_str, python_value_with_inline(operand, var_names, inline_map))
         }
+        Instruction::Cast { dest, value, to } => {
+            let function = if to.is_int() { "int" } else { "float" };
+            format!("    {} = {}({})\n", var_name(dest.id, var_names), function, python_value_with_inline(value, var_names, inline_map))
+        }
         Instruction::Load { dest, array, index } => {
             format!("    {} = {}[{}]\n",
                 var_name(dest.id, var_names),
                 var_name(array.id, var_names),
                 python_value_with_inline(index, var_names, inline_map))
         }
-        Instruction::Store { array, index, value } => {
+        Instruction::Store { array, index, value, .. } => {
             format!("    {}[{}] = {}\n",
                 var_name(array.id, var_names),
                 python_value_with_inline(index, var_names, inline_map),
                 python_value_with_inline(value, var_names, inline_map))
         }
-        Instruction::Call { dest, function, args } => {
+        Instruction::Call { dest, function, args, line } => {
             let args_str = args.iter()
                 .map(|arg| python_value_with_inline(arg, var_names, inline_map))
                 .collect::<Vec<_>>()
@@ -464,170 +810,104 @@ fn generate_python_instruction_with_inline(inst: &Instruction, var_names: &std::
             let mut result = String::new();
 
             // Handle built-in print functions
-            if function == "print" || function == "print_float" || function == "print_array" {
+            if matches!(function.as_str(), "print" | "print_int" | "print_float" | "print_bool" | "print_array") {
                 result.push_str(&format!("    print({})\n", args_str));
+            } else if let (Some(Value::Str(format)), "print_fmt") = (args.first(), function.as_str()) {
+                let values: Vec<String> = args[1..].iter()
+                    .map(|arg| python_value_with_inline(arg, var_names, inline_map))
+                    .collect();
+                result.push_str(&format!("    print({})\n", python_fstring(format, &values)));
+            } else if let (Some(_), [_, index, value]) = (super::atomic_buffer(inst), args.as_slice()) {
+                // GPU Atomics: the simulation runs one invocation at a time,
+                // so a plain read-modify-write (keeping the old value) does
+                let element = format!("{}[{}]", python_value_with_inline(&args[0], var_names, inline_map),
+                    python_value_with_inline(index, var_names, inline_map));
+                let value = python_value_with_inline(value, var_names, inline_map);
+                if let Some(d) = dest {
+                    result.push_str(&format!("    {} = {}\n", var_name(d.id, var_names), element));
+                }
+                if function == "atomic_add" {
+                    result.push_str(&format!("    {} += {}\n", element, value));
+                } else {
+                    result.push_str(&format!("    {0} = max({0}, {1})\n", element, value));
+                }
+            } else if function == "assert" {
+                let location = line.map_or(String::new(), |l| format!(" on line {}", l));
+                result.push_str(&format!("    assert {}, \"assertion failed{}\"\n", args_str, location));
+            } else if let (Some(np_fn), Some(d)) = (numpy_constructor(function), dest) {
+                result.push_str(&format!("    {} = {}({})\n", var_name(d.id, var_names), np_fn, args_str));
             } else if let Some(d) = dest {
                 result.push_str(&format!("    {} = {}({})\n", var_name(d.id, var_names), function, args_str));
+                // The call is shown as literal text: inlined arguments may
+                // contain braces or quotes, which are escaped for the f-string
                 result.push_str(&format!("    if DEBUG_MODE:\n        print(f\"  {}({}) = {{{}}}\")\n",
-                    function, args_str, var_name(d.id, var_names)));
+                    fstring_text(function), fstring_text(&args_str), var_name(d.id, var_names)));
             } else {
                 result.push_str(&format!("    {}({})\n", function, args_str));
-                result.push_str(&format!("    if DEBUG_MODE:\n        print(f\"  {}({})\")\n", function, args_str));
+                result.push_str(&format!("    if DEBUG_MODE:\n        print(f\"  {}({})\")\n",
+                    fstring_text(function), fstring_text(&args_str)));
             }
             result
         }
-        Instruction::DomainConversion { dest, source, from_domain, to_domain, encoding } => {
-            format!("    {} = encode_angle({})\n",
+        Instruction::DomainConversion { dest, source, encoding, .. } => {
+            format!("    {} = {}({})\n",
                 var_name(dest.id, var_names),
+                conversion_function(encoding),
                 python_value_with_inline(source, var_names, inline_map))
         }
         _ => String::new(),
     };
-    Ok(code)
-}
-
-fn generate_python_instruction_with_names(inst: &Instruction, var_names: &std::collections::HashMap<usize, String>) -> Result<String> {
-    let code = match inst {
-        Instruction::Assign { dest, value } => {
-            format!("    {} = {}\n", var_name(dest.id, var_names), python_value_with_names(value, var_names))
-        }
-        Instruction::BinaryOp { dest, op, left, right } => {
-            let op_str = match op {
-                BinOp::Add => "+",
-                BinOp::Sub => "-",
-                BinOp::Mul => "*",
-                BinOp::Div => "/",
-                BinOp::Mod => "%",
-                BinOp::Eq => "==",
-                BinOp::Ne => "!=",
-                BinOp::Lt => "<",
-                BinOp::Le => "<=",
-                BinOp::Gt => ">",
-                BinOp::Ge => ">=",
-                BinOp::And => "and",
-                BinOp::Or => "or",
-            };
-            format!("    {} = {} {} {}\n",
-                var_name(dest.id, var_names),
-                python_value_with_names(left, var_names),
-                op_str,
-                python_value_with_names(right, var_names))
-        }
-        Instruction::UnaryOp { dest, op, operand } => {
-            let op_str = match op {
-                UnOp::Neg => "-",
-                UnOp::Not => "not ",
-            };
-            format!("    {} = {}{}\n", var_name(dest.id, var_names), op_str, python_value_with_names(operand, var_names))
-        }
-        Instruction::Load { dest, array, index } => {
-            format!("    {} = {}[{}]\n",
-                var_name(dest.id, var_names),
-                var_name(array.id, var_names),
-                python_value_with_names(index, var_names))
-        }
-        Instruction::Store { array, index, value } => {
-            format!("    {}[{}] = {}\n",
-                var_name(array.id, var_names),
-                python_value_with_names(index, var_names),
-                python_value_with_names(value, var_names))
-        }
-        Instruction::Call { dest, function, args } => {
-            let args_str = args.iter()
-                .map(|a| python_value_with_names(a, var_names))
-                .collect::<Vec<_>>()
-                .join(", ");
-            if let Some(d) = dest {
-                format!("    {} = {}({})\n", var_name(d.id, var_names), function, args_str)
-            } else {
-                format!("    {}({})\n", function, args_str)
-            }
-        }
-        Instruction::DomainConversion { dest, source, from_domain, to_domain, encoding } => {
-            let conv_fn = match (from_domain, to_domain, encoding) {
-                (_, _, ConversionEncoding::AngleEncoding) => "encode_angle",
-                (_, _, ConversionEncoding::AmplitudeEncoding) => "encode_amplitude",
-                (_, _, ConversionEncoding::MeasurementExtract) => "extract_measurement",
-            };
-            format!("    {} = {}({})\n", var_name(dest.id, var_names), conv_fn, python_value_with_names(source, var_names))
-        }
-        Instruction::Phi { .. } => {
-            "    # phi node\n".to_string()
-        }
-    };
-    Ok(code)
-}
-
-fn generate_python_terminator(term: &Terminator) -> Result<String> {
-    generate_python_terminator_with_names(term, &std::collections::HashMap::new())
+    Ok(with_line_comment(inst, code))
 }
 
-fn generate_python_terminator_with_names(term: &Terminator, var_names: &std::collections::HashMap<usize, String>) -> Result<String> {
-    let code = match term {
-        Terminator::Return(val) => {
-            format!("    return {}\n", python_value_with_names(val, var_names))
-        }
-        Terminator::ReturnVoid => {
-            "    return None\n".to_string()
-        }
-        Terminator::Branch { condition, true_label, false_label } => {
-            format!("    if {}:\n        goto {}\n    else:\n        goto {}\n",
-                python_value_with_names(condition, var_names), true_label, false_label)
-        }
-        Terminator::Jump(label) => {
-            format!("    goto {}\n", label)
-        }
-    };
-    Ok(code)
+// Python helper implementing each conversion encoding
+fn conversion_function(encoding: &ConversionEncoding) -> &'static str {
+    match encoding {
+        ConversionEncoding::AngleEncoding => "encode_angle",
+        ConversionEncoding::AmplitudeEncoding => "encode_amplitude",
+        ConversionEncoding::MeasurementExtract => "extract_measurement",
+    }
 }
 
-fn generate_python_terminator_with_inline(term: &Terminator, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> Result<String> {
-    let code = match term {
-        Terminator::Return(val) => {
-            format!("    return {}\n", python_value_with_inline(val, var_names, inline_map))
-        }
-        Terminator::ReturnVoid => {
-            "    return None\n".to_string()
-        }
-        Terminator::Branch { condition, true_label, false_label } => {
-            format!("    if {}:\n        goto {}\n    else:\n        goto {}\n",
-                python_value_with_inline(condition, var_names, inline_map), true_label, false_label)
-        }
-        Terminator::Jump(label) => {
-            format!("    goto {}\n", label)
-        }
-    };
-    Ok(code)
+// Branches and jumps have no Python of their own: PythonEmitter rebuilds
+// them as if/else and loops, leaving only the returns
+fn generate_python_return_with_inline(val: Option<&Value>, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> String {
+    match val {
+        Some(val) => format!("    return {}\n", python_value_with_inline(val, var_names, inline_map)),
+        None => "    return None\n".to_string(),
+    }
 }
 
 fn var_name(id: usize, var_names: &std::collections::HashMap<usize, String>) -> String {
     var_names.get(&id).cloned().unwrap_or_else(|| format!("v{}", id))
 }
 
-fn python_value(val: &Value) -> String {
-    python_value_with_names(val, &std::collections::HashMap::new())
-}
+// Debug formatting is the shortest round-trip form and keeps the `.0` on
+// whole numbers (3.0, not 3), so floats stay floats in the generated code.
+// The predefined constants PI, TAU and E are written by name, as are the
+// folded angles PI / n (parenthesized, since they may be an operand).
+fn python_float(f: f64) -> String {
+    use std::f64::consts::{E, PI, TAU};
 
-fn python_value_with_names(val: &Value, var_names: &std::collections::HashMap<usize, String>) -> String {
-    match val {
-        Value::Int(n) => format!("{}", n),
-        Value::Float(f) => format!("{}", f),
-        Value::Bool(b) => if *b { "True" } else { "False" }.to_string(),
-        Value::Var(v) => var_name(v.id, var_names),
-        Value::Array(elements) => {
-            let elems = elements.iter()
-                .map(|e| python_value_with_names(e, var_names))
-                .collect::<Vec<_>>()
-                .join(", ");
-            format!("[{}]", elems)
-        }
+    if f == PI {
+        return "np.pi".to_string();
+    }
+    if f == TAU {
+        return "(2 * np.pi)".to_string();
     }
+    if f == E {
+        return "np.e".to_string();
+    }
+    if let Some(n) = (2..=16).find(|&n| f == PI / n as f64) {
+        return format!("(np.pi / {})", n);
+    }
+    format!("{:?}", f)
 }
 
 fn python_value_with_inline(val: &Value, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> String {
     match val {
         Value::Int(n) => format!("{}", n),
-        Value::Float(f) => format!("{}", f),
+        Value::Float(f) => python_float(*f),
         Value::Bool(b) => if *b { "True" } else { "False" }.to_string(),
         Value::Var(v) => {
             // Check if this variable should be inlined
@@ -644,14 +924,22 @@ fn python_value_with_inline(val: &Value, var_names: &std::collections::HashMap<u
                 .join(", ");
             format!("[{}]", elems)
         }
+        Value::Tuple(elements) => {
+            format!("({})", elements.iter().map(|e| python_value_with_inline(e, var_names, inline_map)).collect::<Vec<_>>().join(", "))
+        }
+        Value::Str(s) => format!("{:?}", s),
     }
 }
 
-fn generate_quantum_instruction(inst: &Instruction) -> Result<String> {
-    generate_quantum_instruction_with_names(inst, &std::collections::HashMap::new())
-}
-
-fn generate_quantum_instruction_with_inline(inst: &Instruction, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> Result<String> {
+fn generate_quantum_instruction_with_inline(inst: &Instruction, func_name: &str, strict: bool, var_names: &std::collections::HashMap<usize, String>, inline_map: &std::collections::HashMap<usize, String>) -> Result<String> {
+    // Strict Mode: an instruction the circuit can't express is an error
+    // instead of a comment (or nothing at all)
+    let dropped = |note: &str| -> Result<String> {
+        if strict {
+            bail!("unsupported instruction in @quantum function '{}': {}", func_name, crate::middle::dump_inst(inst));
+        }
+        Ok(note.to_string())
+    };
     let code = match inst {
         Instruction::Load { dest, array, index } => {
             format!("    {} = {}[{}]\n",
@@ -662,184 +950,152 @@ fn generate_quantum_instruction_with_inline(inst: &Instruction, var_names: &std:
         Instruction::Assign { dest, value } => {
             format!("    {} = {}\n", var_name(dest.id, var_names), python_value_with_inline(value, var_names, inline_map))
         }
-        Instruction::Call { function, args, dest } => {
-            // Map quantum gate calls to Qiskit
-            match function.as_str() {
-                "h" | "hadamard" => {
-                    if let Some(Value::Int(qubit)) = args.first() {
-                        format!("    circuit.h(qr[{}])\n", qubit)
-                    } else {
-                        "    # h gate (invalid args)\n".to_string()
-                    }
-                }
-                "x" | "pauli_x" => {
-                    if let Some(Value::Int(qubit)) = args.first() {
-                        format!("    circuit.x(qr[{}])\n", qubit)
-                    } else {
-                        "    # x gate (invalid args)\n".to_string()
-                    }
-                }
-                "ry" => {
-                    if args.len() >= 2 {
-                        if let (Some(qubit_val), Some(angle)) = (args.get(0), args.get(1)) {
-                            if let Value::Int(qubit) = qubit_val {
-                                format!("    circuit.ry({}, qr[{}])\n", python_value_with_inline(angle, var_names, inline_map), qubit)
-                            } else {
-                                "    # ry gate (invalid qubit)\n".to_string()
-                            }
-                        } else {
-                            "    # ry gate (invalid args)\n".to_string()
-                        }
-                    } else {
-                        "    # ry gate (missing args)\n".to_string()
-                    }
-                }
-                "cx" | "cnot" => {
-                    if args.len() >= 2 {
-                        if let (Some(Value::Int(control)), Some(Value::Int(target))) = (args.get(0), args.get(1)) {
-                            format!("    circuit.cx(qr[{}], qr[{}])\n", control, target)
-                        } else {
-                            "    # cx gate (invalid args)\n".to_string()
-                        }
-                    } else {
-                        "    # cx gate (missing args)\n".to_string()
-                    }
-                }
-                _ => String::new(),
-            }
+        Instruction::DomainConversion { source, encoding: ConversionEncoding::AmplitudeEncoding, .. } => {
+            format!("    circuit.initialize(encode_amplitude({}, 2 ** len(qr)), qr)\n",
+                python_value_with_inline(source, var_names, inline_map))
         }
-        _ => String::new(),
-    };
-    Ok(code)
-}
-
-fn generate_quantum_instruction_with_names(inst: &Instruction, var_names: &std::collections::HashMap<usize, String>) -> Result<String> {
-    let code = match inst {
-        Instruction::Call { function, args, dest } => {
+        Instruction::Call { function, args, .. } => {
             // Map quantum gate calls to Qiskit
             match function.as_str() {
                 "h" | "hadamard" => {
                     if let Some(Value::Int(qubit)) = args.first() {
                         format!("    circuit.h(qr[{}])\n", qubit)
                     } else {
-                        "    # h gate (invalid args)\n".to_string()
+                        dropped("    # h gate (invalid args)\n")?
                     }
                 }
                 "x" | "pauli_x" => {
                     if let Some(Value::Int(qubit)) = args.first() {
                         format!("    circuit.x(qr[{}])\n", qubit)
                     } else {
-                        "    # x gate (invalid args)\n".to_string()
+                        dropped("    # x gate (invalid args)\n")?
                     }
                 }
                 "y" | "pauli_y" => {
                     if let Some(Value::Int(qubit)) = args.first() {
                         format!("    circuit.y(qr[{}])\n", qubit)
                     } else {
-                        "    # y gate (invalid args)\n".to_string()
+                        dropped("    # y gate (invalid args)\n")?
                     }
                 }
                 "z" | "pauli_z" => {
                     if let Some(Value::Int(qubit)) = args.first() {
                         format!("    circuit.z(qr[{}])\n", qubit)
                     } else {
-                        "    # z gate (invalid args)\n".to_string()
+                        dropped("    # z gate (invalid args)\n")?
                     }
                 }
-                "rx" => {
-                    if args.len() >= 2 {
-                        if let (Some(qubit_val), Some(angle)) = (args.get(0), args.get(1)) {
-                            if let Value::Int(qubit) = qubit_val {
-                                format!("    circuit.rx({}, qr[{}])\n", python_value_with_names(angle, var_names), qubit)
-                            } else {
-                                "    # rx gate (invalid qubit)\n".to_string()
-                            }
-                        } else {
-                            "    # rx gate (invalid args)\n".to_string()
-                        }
+                "rz" => {
+                    if let (Some(Value::Int(qubit)), Some(angle)) = (args.first(), args.get(1)) {
+                        format!("    circuit.rz({}, qr[{}])\n", python_value_with_inline(angle, var_names, inline_map), qubit)
                     } else {
-                        "    # rx gate (missing args)\n".to_string()
+                        dropped("    # rz gate (invalid args)\n")?
                     }
                 }
                 "ry" => {
                     if args.len() >= 2 {
-                        if let (Some(qubit_val), Some(angle)) = (args.get(0), args.get(1)) {
+                        if let (Some(qubit_val), Some(angle)) = (args.first(), args.get(1)) {
                             if let Value::Int(qubit) = qubit_val {
-                                format!("    circuit.ry({}, qr[{}])\n", python_value_with_names(angle, var_names), qubit)
+                                format!("    circuit.ry({}, qr[{}])\n", python_value_with_inline(angle, var_names, inline_map), qubit)
                             } else {
-                                "    # ry gate (invalid qubit)\n".to_string()
+                                dropped("    # ry gate (invalid qubit)\n")?
                             }
                         } else {
-                            "    # ry gate (invalid args)\n".to_string()
+                            dropped("    # ry gate (invalid args)\n")?
                         }
                     } else {
-                        "    # ry gate (missing args)\n".to_string()
+                        dropped("    # ry gate (missing args)\n")?
                     }
                 }
-                "rz" => {
-                    if args.len() >= 2 {
-                        if let (Some(qubit_val), Some(angle)) = (args.get(0), args.get(1)) {
-                            if let Value::Int(qubit) = qubit_val {
-                                format!("    circuit.rz({}, qr[{}])\n", python_value_with_names(angle, var_names), qubit)
-                            } else {
-                                "    # rz gate (invalid qubit)\n".to_string()
-                            }
-                        } else {
-                            "    # rz gate (invalid args)\n".to_string()
-                        }
+                "u" => {
+                    if let (Some(theta), Some(phi), Some(lam), Some(Value::Int(qubit))) =
+                        (args.first(), args.get(1), args.get(2), args.get(3))
+                    {
+                        format!("    circuit.u({}, {}, {}, qr[{}])\n",
+                            python_value_with_inline(theta, var_names, inline_map),
+                            python_value_with_inline(phi, var_names, inline_map),
+                            python_value_with_inline(lam, var_names, inline_map),
+                            qubit)
                     } else {
-                        "    # rz gate (missing args)\n".to_string()
+                        dropped("    # u gate (invalid args)\n")?
                     }
                 }
-                "cx" | "cnot" => {
-                    if args.len() >= 2 {
-                        if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) = (args.get(0), args.get(1)) {
-                            format!("    circuit.cx(qr[{}], qr[{}])\n", ctrl, target)
-                        } else {
-                            "    # cx gate (invalid args)\n".to_string()
-                        }
+                "p" => {
+                    if let (Some(lam), Some(Value::Int(qubit))) = (args.first(), args.get(1)) {
+                        format!("    circuit.p({}, qr[{}])\n", python_value_with_inline(lam, var_names, inline_map), qubit)
                     } else {
-                        "    # cx gate (missing args)\n".to_string()
+                        dropped("    # p gate (invalid args)\n")?
                     }
                 }
-                "cz" => {
-                    if args.len() >= 2 {
-                        if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) = (args.get(0), args.get(1)) {
-                            format!("    circuit.cz(qr[{}], qr[{}])\n", ctrl, target)
-                        } else {
-                            "    # cz gate (invalid args)\n".to_string()
-                        }
+                "reset" => {
+                    if let Some(Value::Int(qubit)) = args.first() {
+                        format!("    circuit.reset(qr[{}])\n", qubit)
                     } else {
-                        "    # cz gate (missing args)\n".to_string()
+                        dropped("    # reset (invalid args)\n")?
                     }
                 }
-                "measure" => {
-                    // Store result in variable if dest exists
-                    if let Some(d) = dest {
-                        format!("    {} = 0  # measure placeholder\n", var_name(d.id, var_names))
+                "barrier" => "    circuit.barrier()\n".to_string(),
+                "cx" | "cnot" => {
+                    if args.len() >= 2 {
+                        if let (Some(Value::Int(control)), Some(Value::Int(target))) = (args.first(), args.get(1)) {
+                            format!("    circuit.cx(qr[{}], qr[{}])\n", control, target)
+                        } else {
+                            dropped("    # cx gate (invalid args)\n")?
+                        }
                     } else {
-                        "    # measure\n".to_string()
+                        dropped("    # cx gate (missing args)\n")?
                     }
                 }
-                _ => {
-                    format!("    # unknown quantum op: {}\n", function)
-                }
+                _ => dropped("")?,
             }
         }
-        Instruction::Load { dest, array, index } => {
-            format!("    {} = {}[{}]\n",
-                var_name(dest.id, var_names),
-                var_name(array.id, var_names),
-                python_value_with_names(index, var_names))
+        _ => dropped("")?,
+    };
+    Ok(with_line_comment(inst, code))
+}
+
+// Source Mapping: "# line 12" ahead of the code generated for a call or store
+fn with_line_comment(inst: &Instruction, code: String) -> String {
+    let line = match inst {
+        Instruction::Call { line, .. } | Instruction::Store { line, .. } => *line,
+        _ => None,
+    };
+    match line {
+        Some(line) if !code.is_empty() => {
+            let indent = &code[..code.len() - code.trim_start().len()];
+            format!("{}# line {}\n{}", indent, line, code)
         }
-        Instruction::Assign { dest, value } => {
-            format!("    {} = {}\n", var_name(dest.id, var_names), python_value_with_names(value, var_names))
+        _ => code,
+    }
+}
+
+// Does any operand of the instruction read one of the given variables?
+fn uses_any_var(inst: &Instruction, vars: &std::collections::HashSet<usize>) -> bool {
+    match inst {
+        Instruction::Assign { value, .. } => value_uses_any_var(value, vars),
+        Instruction::BinaryOp { left, right, .. } => {
+            value_uses_any_var(left, vars) || value_uses_any_var(right, vars)
         }
-        _ => {
-            format!("    # {:?}\n", inst)
+        Instruction::UnaryOp { operand, .. } => value_uses_any_var(operand, vars),
+        Instruction::Cast { value, .. } => value_uses_any_var(value, vars),
+        Instruction::Load { array, index, .. } => {
+            vars.contains(&array.id) || value_uses_any_var(index, vars)
         }
-    };
-    Ok(code)
+        Instruction::Store { array, index, value, .. } => {
+            vars.contains(&array.id) || value_uses_any_var(index, vars) || value_uses_any_var(value, vars)
+        }
+        Instruction::Call { args, .. } => args.iter().any(|a| value_uses_any_var(a, vars)),
+        Instruction::Phi { incoming, .. } => incoming.iter().any(|(v, _)| value_uses_any_var(v, vars)),
+        Instruction::DomainConversion { source, .. } => value_uses_any_var(source, vars),
+    }
+}
+
+fn value_uses_any_var(val: &Value, vars: &std::collections::HashSet<usize>) -> bool {
+    match val {
+        Value::Var(v) => vars.contains(&v.id),
+        Value::Array(elements) | Value::Tuple(elements) => elements.iter().any(|e| value_uses_any_var(e, vars)),
+        _ => false,
+    }
 }
 
 fn get_dest_var(inst: &Instruction) -> Option<SSAVar> {
@@ -847,6 +1103,7 @@ fn get_dest_var(inst: &Instruction) -> Option<SSAVar> {
         Instruction::Assign { dest, .. } => Some(*dest),
         Instruction::BinaryOp { dest, .. } => Some(*dest),
         Instruction::UnaryOp { dest, .. } => Some(*dest),
+        Instruction::Cast { dest, .. } => Some(*dest),
         Instruction::Load { dest, .. } => Some(*dest),
         Instruction::Call { dest, .. } => *dest,
         Instruction::Phi { dest, .. } => Some(*dest),
@@ -855,62 +1112,170 @@ fn get_dest_var(inst: &Instruction) -> Option<SSAVar> {
     }
 }
 
-fn collect_used_var_ids(inst: &Instruction, used: &mut std::collections::HashSet<usize>) {
-    match inst {
-        Instruction::Assign { value, .. } => collect_value_vars(value, used),
-        Instruction::BinaryOp { left, right, .. } => {
-            collect_value_vars(left, used);
-            collect_value_vars(right, used);
-        }
-        Instruction::UnaryOp { operand, .. } => collect_value_vars(operand, used),
-        Instruction::Load { array, index, .. } => {
-            used.insert(array.id);
-            collect_value_vars(index, used);
+// NumPy equivalents of the zeros/ones/range array constructors and matmul
+// Elementwise Arithmetic: array literals are Python lists, where `+` would
+// concatenate, so an array-typed operation starts from a numpy array
+// Float Modulo: Python's % takes the sign of the divisor (-3.5 % 2.0 == 0.5)
+// while WGSL's % and constant folding truncate like C's fmod (-1.5), so
+// float (and float array) remainders use np.fmod
+fn is_float_mod(op: &BinOp, dest_type: Option<&IRType>) -> bool {
+    let is_float = |ty: &IRType| matches!(ty, IRType::Float | IRType::Float32 | IRType::Float64);
+    *op == BinOp::Mod
+        && match dest_type {
+            Some(IRType::Array(elem, _)) => is_float(elem),
+            Some(ty) => is_float(ty),
+            None => false,
         }
-        Instruction::Store { array, index, value } => {
-            used.insert(array.id);
-            collect_value_vars(index, used);
-            collect_value_vars(value, used);
-        }
-        Instruction::Call { args, .. } => {
-            for arg in args {
-                collect_value_vars(arg, used);
-            }
+}
+
+// Value Semantics: arrays are copied on binding when either side is later
+// written through a store, so `let mut b = a; b[0] = 9;` leaves `a` alone
+// (Python lists and NumPy arrays would otherwise alias; deepcopy also
+// separates the rows of a matrix). An inlined source is a fresh value
+fn is_array_copy(func: &IRFunction, dest: SSAVar, value: &Value, inline_map: &std::collections::HashMap<usize, String>) -> bool {
+    let Value::Var(source) = value else {
+        return false;
+    };
+    if inline_map.contains_key(&source.id) || !matches!(func.var_types.get(&dest), Some(IRType::Array(..))) {
+        return false;
+    }
+    func.blocks.iter().flat_map(|b| &b.instructions).any(|inst| {
+        matches!(inst, Instruction::Store { array, .. } if *array == dest || array == source)
+    })
+}
+
+// A non-inlined variable holding an array (a row of a nested literal)
+fn is_array_var(func: &IRFunction, value: &Value, inline_map: &std::collections::HashMap<usize, String>) -> bool {
+    matches!(value, Value::Var(v) if !inline_map.contains_key(&v.id) && matches!(func.var_types.get(v), Some(IRType::Array(..))))
+}
+
+fn elementwise_operand(left: String, dest_type: Option<&IRType>) -> String {
+    match dest_type {
+        Some(IRType::Array(..)) => format!("np.asarray({})", left),
+        _ => left,
+    }
+}
+
+// print_fmt("x={}", x) -> f"x={x}": the literal text has its braces doubled
+// and quotes escaped, each `{}` takes the next value
+fn python_fstring(format: &str, values: &[String]) -> String {
+    let mut out = String::from("f\"");
+    for (i, piece) in format.split("{}").enumerate() {
+        if i > 0 {
+            out.push_str(&format!("{{{}}}", values[i - 1]));
         }
-        Instruction::DomainConversion { source, .. } => collect_value_vars(source, used),
-        _ => {}
+        out.push_str(&fstring_text(piece));
     }
+    out.push('"');
+    out
 }
 
-fn collect_value_vars(value: &Value, used: &mut std::collections::HashSet<usize>) {
-    match value {
-        Value::Var(v) => { used.insert(v.id); }
-        Value::Array(elements) => {
-            for elem in elements {
-                collect_value_vars(elem, used);
-            }
+// Literal text inside f"...": braces doubled, quotes and backslashes escaped
+fn fstring_text(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '{' => out.push_str("{{"),
+            '}' => out.push_str("}}"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
         }
-        _ => {}
     }
+    out
+}
+
+fn numpy_constructor(function: &str) -> Option<&'static str> {
+    match function {
+        "zeros" => Some("np.zeros"),
+        "ones" => Some("np.ones"),
+        "range" => Some("np.arange"),
+        "matmul" => Some("np.matmul"),
+        "sum" => Some("np.sum"),
+        "product" => Some("np.prod"),
+        "dot" => Some("np.dot"),
+        _ => None,
+    }
+}
+
+// Indented Python docstring; backslashes and triple quotes are escaped so
+// the doc text can't end the string early
+fn python_docstring(doc: &str) -> String {
+    let escaped = doc.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"");
+    if escaped.contains('\n') || escaped.ends_with('"') {
+        let body = escaped.lines().map(|l| format!("    {}", l).trim_end().to_string()).collect::<Vec<_>>();
+        format!("    \"\"\"\n{}\n    \"\"\"\n", body.join("\n"))
+    } else {
+        format!("    \"\"\"{}\"\"\"\n", escaped)
+    }
+}
+
+// NumPy type for an explicit-width scalar; plain int/float stay Python values
+fn numpy_dtype(ty: &IRType) -> Option<&'static str> {
+    match ty {
+        IRType::Int32 => Some("np.int32"),
+        IRType::Int64 => Some("np.int64"),
+        IRType::Float32 => Some("np.float32"),
+        IRType::Float64 => Some("np.float64"),
+        _ => None,
+    }
+}
+
+// Mid-circuit Measurement: each `measure(q)` call gets its own classical
+// bit, in program order (call k writes cr[k]). Zero when the function never
+// measures explicitly, in which case every qubit is measured at the end.
+fn count_measurements(func: &IRFunction) -> usize {
+    func.blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter())
+        .filter(|inst| matches!(inst, Instruction::Call { function, .. } if function == "measure"))
+        .count()
+}
+
+fn measures_all(func: &IRFunction) -> bool {
+    func.blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter())
+        .any(|inst| matches!(inst, Instruction::Call { function, .. } if function == "measure_all"))
 }
 
 fn estimate_qubits(func: &IRFunction) -> usize {
     // Simple heuristic: count unique qubit indices
     let mut max_qubit = 0;
+    // Amplitude encoding of n values needs ceil(log2(n)) qubits
+    let mut amplitude_qubits = 0;
     for block in &func.blocks {
         for inst in &block.instructions {
-            if let Instruction::Call { args, .. } = inst {
-                for arg in args {
-                    if let Value::Int(n) = arg {
-                        if *n >= 0 {
-                            max_qubit = max_qubit.max(*n as usize);
+            match inst {
+                Instruction::Call { args, .. } => {
+                    for arg in args {
+                        if let Value::Int(n) = arg {
+                            if *n >= 0 {
+                                max_qubit = max_qubit.max(*n as usize);
+                            }
                         }
                     }
                 }
+                Instruction::DomainConversion { source, encoding: ConversionEncoding::AmplitudeEncoding, .. } => {
+                    let len = match source {
+                        Value::Array(elements) => Some(elements.len()),
+                        Value::Var(v) => match func.var_types.get(v) {
+                            Some(IRType::Array(_, Some(n))) => Some(*n),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let Some(len) = len {
+                        amplitude_qubits = amplitude_qubits.max(len.next_power_of_two().trailing_zeros() as usize);
+                    }
+                }
+                _ => {}
             }
         }
     }
-    (max_qubit + 1).max(2)
+    (max_qubit + 1).max(amplitude_qubits).max(2)
 }
 
 // Build inline map: variables that are used only once and can be inlined
@@ -922,51 +1287,80 @@ fn build_inline_map(func: &IRFunction) -> std::collections::HashMap<usize, Strin
     // Track variables used as arrays in Store/Load (can't be inlined)
     let mut no_inline_vars: std::collections::HashSet<usize> = std::collections::HashSet::new();
 
-    for block in &func.blocks {
-        for inst in &block.instructions {
+    // Where each variable is read: (block, instruction index), the
+    // terminator counting as the index past the last instruction
+    let mut use_site: HashMap<usize, (usize, usize)> = HashMap::new();
+
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            let mut reads = HashMap::new();
             // Count uses in operands
             match inst {
-                Instruction::Assign { value, .. } => count_value_uses(value, &mut use_count),
+                Instruction::Assign { value, .. } => count_value_uses(value, &mut reads),
                 Instruction::BinaryOp { left, right, .. } => {
-                    count_value_uses(left, &mut use_count);
-                    count_value_uses(right, &mut use_count);
+                    count_value_uses(left, &mut reads);
+                    count_value_uses(right, &mut reads);
                 }
-                Instruction::UnaryOp { operand, .. } => count_value_uses(operand, &mut use_count),
+                Instruction::UnaryOp { operand, .. } => count_value_uses(operand, &mut reads),
+                Instruction::Cast { value, .. } => count_value_uses(value, &mut reads),
                 Instruction::Load { array, index, .. } => {
                     // Arrays in Load can't be inlined
                     no_inline_vars.insert(array.id);
-                    count_value_uses(index, &mut use_count);
+                    count_value_uses(index, &mut reads);
                 }
                 Instruction::Store { array, index, value, .. } => {
                     // Arrays in Store can't be inlined
                     no_inline_vars.insert(array.id);
-                    count_value_uses(index, &mut use_count);
-                    count_value_uses(value, &mut use_count);
+                    count_value_uses(index, &mut reads);
+                    count_value_uses(value, &mut reads);
                 }
                 Instruction::Call { args, .. } => {
                     for arg in args {
-                        count_value_uses(arg, &mut use_count);
+                        count_value_uses(arg, &mut reads);
                     }
                 }
-                Instruction::DomainConversion { source, .. } => count_value_uses(source, &mut use_count),
+                Instruction::DomainConversion { source, .. } => count_value_uses(source, &mut reads),
                 _ => {}
             }
+            for (var, n) in reads {
+                *use_count.entry(var).or_insert(0) += n;
+                use_site.insert(var, (b, i));
+            }
         }
         // Count uses in terminator
+        let mut reads = HashMap::new();
         match &block.terminator {
-            Terminator::Return(val) => count_value_uses(val, &mut use_count),
-            Terminator::Branch { condition, .. } => count_value_uses(condition, &mut use_count),
+            Terminator::Return(val) => count_value_uses(val, &mut reads),
+            Terminator::Branch { condition, .. } => count_value_uses(condition, &mut reads),
             _ => {}
         }
+        for (var, n) in reads {
+            *use_count.entry(var).or_insert(0) += n;
+            use_site.insert(var, (b, block.instructions.len()));
+        }
+    }
+
+    // After phi elimination a merged variable is assigned in several blocks.
+    // It is never inlined, and an expression reading it (directly or through
+    // another inlined expression) only moves to a use later in its own
+    // block with no reassignment in between
+    let mut defs: HashMap<usize, usize> = HashMap::new();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        if let Some(dest) = get_dest_var(inst) {
+            *defs.entry(dest.id).or_insert(0) += 1;
+        }
     }
+    let reassigned: std::collections::HashSet<usize> =
+        defs.into_iter().filter(|&(_, n)| n > 1).map(|(var, _)| var).collect();
+    let mut reads_reassigned = reassigned.clone();
 
     // Build inline map for single-use variables
-    // Do this in multiple passes to handle dependencies
+    // One pass in program order, so an operand is already inlined (or known
+    // not to be) by the time the instruction using it is visited
     let mut inline_map = HashMap::new();
 
-    // Pass 1: Inline Loads and simple Assigns
-    for block in &func.blocks {
-        for inst in &block.instructions {
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
             if let Some(dest) = get_dest_var(inst) {
                 // Skip parameters
                 if dest.id < func.params.len() {
@@ -974,10 +1368,23 @@ fn build_inline_map(func: &IRFunction) -> std::collections::HashMap<usize, Strin
                 }
 
                 // Skip variables that can't be inlined (used as arrays)
-                if no_inline_vars.contains(&dest.id) {
+                if no_inline_vars.contains(&dest.id) || reassigned.contains(&dest.id) {
                     continue;
                 }
 
+                let reads_merged = uses_any_var(inst, &reads_reassigned);
+                if reads_merged {
+                    let stays_valid = match use_site.get(&dest.id) {
+                        Some(&(use_block, use_index)) if use_block == b && use_index > i => block.instructions[i + 1..use_index]
+                            .iter()
+                            .all(|other| get_dest_var(other).is_none_or(|d| !reassigned.contains(&d.id))),
+                        _ => false,
+                    };
+                    if !stays_valid {
+                        continue;
+                    }
+                }
+
                 // Only inline if used exactly once
                 if use_count.get(&dest.id).copied().unwrap_or(0) == 1 {
                     match inst {
@@ -987,38 +1394,15 @@ fn build_inline_map(func: &IRFunction) -> std::collections::HashMap<usize, Strin
                             let index_str = value_to_inline_string(index, func, &inline_map);
                             inline_map.insert(dest.id, format!("{}[{}]", array_name, index_str));
                         }
-                        Instruction::Assign { value, .. } => {
+                        Instruction::Assign { value, .. } if !is_array_copy(func, dest, value, &inline_map) => {
                             // Inline simple assigns
                             let value_str = value_to_inline_string(value, func, &inline_map);
                             inline_map.insert(dest.id, value_str);
                         }
-                        _ => {}
-                    }
-                }
-            }
-        }
-    }
-
-    // Pass 2: Inline BinaryOps using the inline_map from Pass 1
-    for block in &func.blocks {
-        for inst in &block.instructions {
-            if let Some(dest) = get_dest_var(inst) {
-                // Skip parameters
-                if dest.id < func.params.len() {
-                    continue;
-                }
-
-                // Skip variables that can't be inlined (used as arrays)
-                if no_inline_vars.contains(&dest.id) {
-                    continue;
-                }
-
-                // Only inline if used exactly once and not already inlined
-                if use_count.get(&dest.id).copied().unwrap_or(0) == 1 && !inline_map.contains_key(&dest.id) {
-                    match inst {
                         Instruction::BinaryOp { op, left, right, .. } => {
-                            // Inline BinaryOp as (left op right)
-                            let left_str = value_to_inline_string(left, func, &inline_map);
+                            // Inline BinaryOp as (left op right); parenthesized so
+                            // nesting keeps the IR's evaluation order
+                            let left_str = elementwise_operand(value_to_inline_string(left, func, &inline_map), func.var_types.get(&dest));
                             let right_str = value_to_inline_string(right, func, &inline_map);
                             let op_str = match op {
                                 BinOp::Add => "+",
@@ -1034,11 +1418,20 @@ fn build_inline_map(func: &IRFunction) -> std::collections::HashMap<usize, Strin
                                 BinOp::Ge => ">=",
                                 BinOp::And => "and",
                                 BinOp::Or => "or",
+                                BinOp::Shl => "<<",
+                                BinOp::Shr => ">>",
                             };
-                            inline_map.insert(dest.id, format!("{} {} {}", left_str, op_str, right_str));
+                            if is_float_mod(op, func.var_types.get(&dest)) {
+                                inline_map.insert(dest.id, format!("np.fmod({}, {})", left_str, right_str));
+                            } else {
+                                inline_map.insert(dest.id, format!("({} {} {})", left_str, op_str, right_str));
+                            }
                         }
                         _ => {}
                     }
+                    if reads_merged && inline_map.contains_key(&dest.id) {
+                        reads_reassigned.insert(dest.id);
+                    }
                 }
             }
         }
@@ -1048,8 +1441,14 @@ fn build_inline_map(func: &IRFunction) -> std::collections::HashMap<usize, Strin
 }
 
 fn count_value_uses(value: &Value, use_count: &mut std::collections::HashMap<usize, usize>) {
-    if let Value::Var(v) = value {
-        *use_count.entry(v.id).or_insert(0) += 1;
+    match value {
+        Value::Var(v) => *use_count.entry(v.id).or_insert(0) += 1,
+        Value::Array(elements) | Value::Tuple(elements) => {
+            for elem in elements {
+                count_value_uses(elem, use_count);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -1072,14 +1471,18 @@ fn value_to_inline_string(value: &Value, func: &IRFunction, inline_map: &std::co
             }
         }
         Value::Int(n) => n.to_string(),
-        Value::Float(f) => f.to_string(),
-        Value::Bool(b) => b.to_string(),
+        Value::Float(f) => python_float(*f),
+        Value::Bool(b) => if *b { "True" } else { "False" }.to_string(),
         Value::Array(elements) => {
             let elem_strs: Vec<String> = elements.iter()
                 .map(|e| value_to_inline_string(e, func, inline_map))
                 .collect();
             format!("[{}]", elem_strs.join(", "))
         }
+        Value::Tuple(elements) => {
+            format!("({})", elements.iter().map(|e| value_to_inline_string(e, func, inline_map)).collect::<Vec<_>>().join(", "))
+        }
+        Value::Str(s) => format!("{:?}", s),
     }
 }
 
@@ -1,14 +1,34 @@
 use crate::middle::ir::*;
-use anyhow::Result;
+use super::common::{classical_conditions, indent_python};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
 
-pub fn codegen(module: &Module) -> Result<String> {
+/// Generate a Qiskit script with one circuit builder per `@quantum` function.
+///
+/// Float parameters used as rotation angles become Qiskit `Parameter`s, so a
+/// variational circuit is built once and bound to new angles on each run
+/// (`python circuit.py ansatz 0.5`).
+pub fn codegen(module: &Module, seed: Option<u64>, strict: bool) -> Result<String> {
     let mut output = String::new();
+    super::check_unlowered_maps(module, strict)?;
+
+    // Calls between @quantum functions become the callee's gates in place
+    // and qubit indices held in constant variables become literals
+    let mut module = module.clone();
+    crate::middle::inline_quantum_calls(&mut module);
+    for func in module.functions.iter_mut().filter(|f| f.domain == crate::frontend::ast::Domain::Quantum) {
+        crate::middle::resolve_constants(func);
+    }
 
     // Qiskit imports
     output.push_str("# Generated Qiskit code\n");
     output.push_str("from qiskit import QuantumCircuit, QuantumRegister, ClassicalRegister\n");
     output.push_str("from qiskit_ibm_runtime import QiskitRuntimeService, SamplerV2 as Sampler\n");
     output.push_str("from qiskit_aer import AerSimulator\n");
+    let parametric = module.functions.iter().any(|f| !angle_parameters(f).is_empty());
+    if parametric {
+        output.push_str("from qiskit.circuit import Parameter\n");
+    }
     output.push_str("import sys\n\n");
 
     // Configuration
@@ -16,32 +36,110 @@ pub fn codegen(module: &Module) -> Result<String> {
     output.push_str("# Configuration\n");
     output.push_str("# ============================================================================\n\n");
     output.push_str("USE_QUANTUM_COMPUTER = False  # Set to True to use IBM Quantum hardware\n");
-    output.push_str("IBM_API_KEY = \"krPjNWz0BsR_PSI0UVVG_VxIFSA27a5SaEgpLlI22-F-\"  # IBM Quantum API key\n\n");
-
-    // Generate circuit from main function
-    if let Some(main_func) = module.functions.iter().find(|f| f.name == "main") {
-        output.push_str(&codegen_quantum_circuit(main_func)?);
-    } else {
-        // Generate from first function
-        if let Some(func) = module.functions.first() {
-            output.push_str(&codegen_quantum_circuit(func)?);
+    output.push_str("IBM_API_KEY = \"krPjNWz0BsR_PSI0UVVG_VxIFSA27a5SaEgpLlI22-F-\"  # IBM Quantum API key\n");
+
+    match seed {
+        Some(seed) => output.push_str(&format!("SEED = {}  # Fixed simulator seed for reproducible results\n", seed)),
+        None => output.push_str("SEED = None  # Set to an integer for reproducible results\n"),
+    }
+    output.push_str("SHOTS = 1024\n\n");
+
+    // One circuit builder per @quantum function; a module without any builds
+    // its circuit from main, else from the first function
+    let mut circuit_funcs: Vec<&IRFunction> = module
+        .functions
+        .iter()
+        .filter(|f| f.domain == crate::frontend::ast::Domain::Quantum)
+        .collect();
+    if circuit_funcs.is_empty() {
+        circuit_funcs.extend(
+            module
+                .functions
+                .iter()
+                .find(|f| f.name == "main")
+                .or_else(|| module.functions.first()),
+        );
+    }
+
+    for func in &circuit_funcs {
+        output.push_str(&format!("def build_{}():\n", func.name));
+        output.push_str(&format!("    \"\"\"Build the circuit for function '{}'\"\"\"\n", func.name));
+        for line in codegen_quantum_circuit(func, strict)?.lines() {
+            if line.is_empty() {
+                output.push('\n');
+            } else {
+                output.push_str(&format!("    {}\n", line));
+            }
         }
+        output.push_str("    return circuit\n\n");
     }
 
+    // Circuit name -> (builder, shots, seed, device); @quantum(shots=N,
+    // seed=M) overrides the defaults for that circuit, and
+    // @quantum(backend="...") names the IBM device it runs on
+    output.push_str("CIRCUITS = {\n");
+    for func in &circuit_funcs {
+        let shots = func.annotations.get("shots").map_or("SHOTS".to_string(), |s| s.to_string());
+        let seed = func.annotations.get("seed").map_or("SEED".to_string(), |s| s.to_string());
+        let device = func.device.as_ref().map_or("None".to_string(), |d| format!("{:?}", d));
+        output.push_str(&format!("    \"{0}\": (build_{0}, {1}, {2}, {3}),\n", func.name, shots, seed, device));
+    }
+    output.push_str("}\n");
+
+    // Circuit name -> its Parameter names, in declaration order
+    if parametric {
+        output.push_str("\nPARAMETERS = {\n");
+        for func in &circuit_funcs {
+            let names: Vec<String> = angle_parameters(func).iter().map(|(_, name)| format!("\"{}\",", name)).collect();
+            if !names.is_empty() {
+                output.push_str(&format!("    \"{}\": ({}),\n", func.name, names.join(" ")));
+            }
+        }
+        output.push_str("}\n");
+    }
+
+    // Run main's circuit by default, else the first one
+    let default_circuit = circuit_funcs
+        .iter()
+        .find(|f| f.name == "main")
+        .or(circuit_funcs.first())
+        .map_or("", |f| f.name.as_str());
+
     // Runtime execution code
     output.push_str("\n# ============================================================================\n");
     output.push_str("# Execution\n");
     output.push_str("# ============================================================================\n\n");
     output.push_str("if __name__ == '__main__':\n");
+    output.push_str("    # Usage: python circuit.py [circuit_name]\n");
+    output.push_str(&format!("    name = sys.argv[1] if len(sys.argv) > 1 else \"{}\"\n", default_circuit));
+    output.push_str("    if name not in CIRCUITS:\n");
+    output.push_str("        print(f\"Unknown circuit '{name}'. Available: {', '.join(CIRCUITS)}\")\n");
+    output.push_str("        sys.exit(1)\n");
+    output.push_str("    build, shots, seed, device = CIRCUITS[name]\n");
+    output.push_str("    circuit = build()\n");
+    if parametric {
+        output.push_str("    params = PARAMETERS.get(name, ())\n");
+        output.push_str("    if params:\n");
+        output.push_str("        # Bind the angles given after the circuit name\n");
+        output.push_str("        values = sys.argv[2:]\n");
+        output.push_str("        if len(values) != len(params):\n");
+        output.push_str("            print(f\"Usage: python circuit.py {name} {' '.join(params)}\")\n");
+        output.push_str("            sys.exit(1)\n");
+        output.push_str("        circuit = circuit.assign_parameters({p: float(v) for p, v in zip(params, values)})\n");
+    }
+    output.push_str("    \n");
     output.push_str("    if USE_QUANTUM_COMPUTER:\n");
     output.push_str("        # Use IBM Quantum hardware\n");
     output.push_str("        print(\"Connecting to IBM Quantum...\")\n");
     output.push_str("        service = QiskitRuntimeService(channel=\"ibm_quantum\", token=IBM_API_KEY)\n");
-    output.push_str("        backend = service.least_busy(operational=True, simulator=False)\n");
+    output.push_str("        if device:\n");
+    output.push_str("            backend = service.backend(device)\n");
+    output.push_str("        else:\n");
+    output.push_str("            backend = service.least_busy(operational=True, simulator=False)\n");
     output.push_str("        print(f\"Using IBM Quantum backend: {backend.name}\")\n");
     output.push_str("        \n");
     output.push_str("        sampler = Sampler(backend)\n");
-    output.push_str("        job = sampler.run([circuit], shots=1024)\n");
+    output.push_str("        job = sampler.run([circuit], shots=shots)\n");
     output.push_str("        print(f\"Job ID: {job.job_id()}\")\n");
     output.push_str("        print(\"Waiting for results...\")\n");
     output.push_str("        result = job.result()\n");
@@ -53,47 +151,212 @@ pub fn codegen(module: &Module) -> Result<String> {
     output.push_str("    else:\n");
     output.push_str("        # Use local simulator\n");
     output.push_str("        print(\"Using local Qiskit Aer simulator\")\n");
-    output.push_str("        backend = AerSimulator()\n");
-    output.push_str("        result = backend.run(circuit, shots=1024).result()\n");
+    output.push_str("        backend = AerSimulator(seed_simulator=seed)\n");
+    output.push_str("        result = backend.run(circuit, shots=shots).result()\n");
     output.push_str("        counts = result.get_counts()\n");
     output.push_str("        print(f\"Counts: {counts}\")\n");
 
     Ok(output)
 }
 
-fn codegen_quantum_circuit(func: &IRFunction) -> Result<String> {
+fn codegen_quantum_circuit(func: &IRFunction, strict: bool) -> Result<String> {
     let mut output = String::new();
 
     // Estimate number of qubits needed
     let num_qubits = estimate_qubits(func);
-    let num_classical = num_qubits; // Same number of classical bits for measurement
+    // One classical bit per measure() call, else one per qubit
+    let num_measurements = count_measurements(func);
+    let num_classical = if num_measurements > 0 { num_measurements } else { num_qubits };
+    let measure_all = measures_all(func);
+    if measure_all && num_measurements > 0 {
+        bail!("measure_all() cannot be combined with measure() in '{}'", func.name);
+    }
 
-    output.push_str(&format!("# Function: {}\n", func.name));
     output.push_str(&format!("qr = QuantumRegister({}, 'q')\n", num_qubits));
     output.push_str(&format!("cr = ClassicalRegister({}, 'c')\n", num_classical));
-    output.push_str("circuit = QuantumCircuit(qr, cr)\n\n");
+    output.push_str("circuit = QuantumCircuit(qr, cr)\n");
+    for (id, name) in angle_parameters(func) {
+        output.push_str(&format!("v{} = Parameter('{}')\n", id, name));
+    }
+    output.push('\n');
 
     // Process instructions
+    let conditions = classical_conditions(func);
+    let block_named = |label: &str| func.blocks.iter().find(|b| b.label == label);
+    let mut bit = 0;
     for block in &func.blocks {
+        // Conditioned blocks are emitted inside their branch's if_test
+        let conditioned = conditions
+            .values()
+            .any(|c| c.then_label == block.label || c.else_label.as_ref() == Some(&block.label));
+        if conditioned {
+            continue;
+        }
         output.push_str(&format!("# Block: {}\n", block.label));
+        output.push_str(&circuit_block_code(block, &func.name, strict, &mut bit)?);
 
-        for inst in &block.instructions {
-            if let Some(quantum_op) = try_codegen_quantum_instruction(inst) {
-                output.push_str(&format!("{}\n", quantum_op));
-            } else {
-                // Classical instruction - add as comment
-                output.push_str(&format!("# Classical: {:?}\n", inst));
+        // Classical Feed-forward: gates under `if m == 1` run conditioned on
+        // the measured bit
+        if let Some(cond) = conditions.get(&block.label) {
+            output.push_str(&format!("with circuit.if_test((cr[{}], {})) as else_:\n", cond.bit, cond.value));
+            let then_code = match block_named(&cond.then_label) {
+                Some(b) => circuit_block_code(b, &func.name, strict, &mut bit)?,
+                None => String::new(),
+            };
+            output.push_str(&indent_python(&then_code, 0));
+            if let Some(else_block) = cond.else_label.as_deref().and_then(block_named) {
+                output.push_str("with else_:\n");
+                output.push_str(&indent_python(&circuit_block_code(else_block, &func.name, strict, &mut bit)?, 0));
             }
         }
     }
 
-    // Add measurements at the end
-    output.push_str("\n# Measurements\n");
-    output.push_str(&format!("circuit.measure(qr, cr)\n"));
+    // Without explicit measurements, measure every qubit at the end
+    if num_measurements == 0 && !measure_all {
+        output.push_str("\n# Measurements\n");
+        output.push_str("circuit.measure(qr, cr)\n");
+    }
 
     Ok(output)
 }
 
+fn circuit_block_code(block: &BasicBlock, func_name: &str, strict: bool, bit: &mut usize) -> Result<String> {
+    let mut output = String::new();
+    for inst in &block.instructions {
+        // Source Mapping: point calls back at their DSL line
+        if let Instruction::Call { line: Some(line), .. } = inst {
+            output.push_str(&format!("# line {}\n", line));
+        }
+        // Measure in place, into the next classical bit
+        if let Instruction::Call { function, args, .. } = inst {
+            if function == "measure" {
+                let qubit = args.first().map_or("0".to_string(), codegen_value);
+                output.push_str(&format!("circuit.measure(qr[{}], cr[{}])\n", qubit, bit));
+                *bit += 1;
+                continue;
+            }
+            // Every qubit into its own bit
+            if function == "measure_all" {
+                output.push_str("circuit.measure(qr, cr)\n");
+                continue;
+            }
+        }
+        if let Some(quantum_op) = try_codegen_quantum_instruction(inst) {
+            output.push_str(&format!("{}\n", quantum_op));
+        } else if strict && matches!(inst, Instruction::Call { function, .. } if is_quantum_op(function)) {
+            // Strict Mode: a gate call that produced no gate is an error
+            // rather than a comment
+            bail!("unsupported instruction in circuit '{}': {}", func_name, crate::middle::dump_inst(inst));
+        } else {
+            // Classical instruction - add as comment
+            output.push_str(&format!("# Classical: {:?}\n", inst));
+        }
+    }
+    Ok(output)
+}
+
+// Resource Estimation: qubits, gate counts and circuit depth per @quantum
+// function, read straight from the IR before any hardware submission
+pub fn estimate_resources(module: &Module) -> String {
+    let mut output = String::new();
+
+    for func in module.functions.iter().filter(|f| f.domain == crate::frontend::ast::Domain::Quantum) {
+        let num_qubits = estimate_qubits(func);
+        let mut gate_counts: BTreeMap<&str, usize> = BTreeMap::new();
+
+        // Depth: each gate starts one layer after the latest gate on any of
+        // its qubits; two-qubit depth only counts layers of two-qubit gates
+        let mut depth = vec![0usize; num_qubits];
+        let mut two_qubit_depth = vec![0usize; num_qubits];
+
+        for block in &func.blocks {
+            for inst in &block.instructions {
+                let (function, args) = match inst {
+                    Instruction::Call { function, args, .. } if is_quantum_op(function) => (function, args),
+                    _ => continue,
+                };
+                *gate_counts.entry(function.as_str()).or_insert(0) += 1;
+
+                let qubits: Vec<usize> = if function == "barrier" {
+                    (0..num_qubits).collect()
+                } else {
+                    args.iter()
+                        .filter_map(|arg| match arg {
+                            Value::Int(n) if *n >= 0 && (*n as usize) < num_qubits => Some(*n as usize),
+                            _ => None,
+                        })
+                        .collect()
+                };
+                if qubits.is_empty() {
+                    continue;
+                }
+
+                let layer = qubits.iter().map(|&q| depth[q]).max().unwrap_or(0);
+                let two_layer = qubits.iter().map(|&q| two_qubit_depth[q]).max().unwrap_or(0);
+                let is_two_qubit = qubits.len() == 2 && function != "barrier";
+                for &q in &qubits {
+                    // A barrier aligns qubits without adding a layer
+                    depth[q] = if function == "barrier" { layer } else { layer + 1 };
+                    two_qubit_depth[q] = if is_two_qubit { two_layer + 1 } else { two_layer };
+                }
+            }
+        }
+
+        output.push_str(&format!("Function: {}\n", func.name));
+        output.push_str(&format!("  {:<16} {}\n", "Qubits:", num_qubits));
+        output.push_str(&format!("  {:<16} {}\n", "Depth:", depth.iter().max().unwrap_or(&0)));
+        output.push_str(&format!(
+            "  {:<16} {}\n",
+            "2-qubit depth:",
+            two_qubit_depth.iter().max().unwrap_or(&0)
+        ));
+        output.push_str(&format!(
+            "  {:<16} {}\n",
+            "Total gates:",
+            gate_counts.values().sum::<usize>()
+        ));
+        for (gate, count) in &gate_counts {
+            output.push_str(&format!("    {:<14} {}\n", gate, count));
+        }
+        output.push('\n');
+    }
+
+    if output.is_empty() {
+        output.push_str("No @quantum functions\n");
+    }
+    output
+}
+
+/// Fail if a @quantum function needs more qubits than the target device
+/// has (`--max-qubits`), counting as the generated circuit does: after
+/// inlining quantum helpers and resolving constant qubit indices.
+pub fn check_max_qubits(module: &Module, max_qubits: usize) -> Result<()> {
+    let mut module = module.clone();
+    crate::middle::inline_quantum_calls(&mut module);
+    for func in module.functions.iter_mut().filter(|f| f.domain == crate::frontend::ast::Domain::Quantum) {
+        crate::middle::resolve_constants(func);
+        let num_qubits = estimate_qubits(func);
+        if num_qubits > max_qubits {
+            bail!(
+                "@quantum function '{}' needs {} qubits, but --max-qubits is {}",
+                func.name,
+                num_qubits,
+                max_qubits
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_quantum_op(function: &str) -> bool {
+    matches!(
+        function,
+        "h" | "hadamard" | "x" | "pauli_x" | "y" | "pauli_y" | "z" | "pauli_z"
+            | "rx" | "ry" | "rz" | "u" | "p" | "cx" | "cnot" | "cz"
+            | "measure" | "reset" | "barrier"
+    )
+}
+
 fn try_codegen_quantum_instruction(inst: &Instruction) -> Option<String> {
     match inst {
         Instruction::DomainConversion { dest, source, encoding, .. } => {
@@ -146,47 +409,62 @@ fn try_codegen_quantum_instruction(inst: &Instruction) -> Option<String> {
                     }
                 }
                 "cx" | "cnot" => {
-                    if args.len() >= 2 {
-                        if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) =
-                            (args.get(0), args.get(1))
-                        {
-                            return Some(format!("circuit.cx({}, {})", ctrl, target));
-                        }
+                    if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) =
+                        (args.first(), args.get(1))
+                    {
+                        return Some(format!("circuit.cx({}, {})", ctrl, target));
                     }
                 }
                 "cz" => {
-                    if args.len() >= 2 {
-                        if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) =
-                            (args.get(0), args.get(1))
-                        {
-                            return Some(format!("circuit.cz({}, {})", ctrl, target));
-                        }
+                    if let (Some(Value::Int(ctrl)), Some(Value::Int(target))) =
+                        (args.first(), args.get(1))
+                    {
+                        return Some(format!("circuit.cz({}, {})", ctrl, target));
                     }
                 }
+                // Rotations are rx/ry/rz(qubit, angle) in the DSL, angle first in Qiskit
                 "rx" => {
-                    if args.len() >= 2 {
-                        if let (Some(angle), Some(Value::Int(qubit))) = (args.get(0), args.get(1))
-                        {
-                            return Some(format!("circuit.rx({}, {})", codegen_value(angle), qubit));
-                        }
+                    if let (Some(Value::Int(qubit)), Some(angle)) = (args.first(), args.get(1))
+                    {
+                        return Some(format!("circuit.rx({}, {})", codegen_value(angle), qubit));
                     }
                 }
                 "ry" => {
-                    if args.len() >= 2 {
-                        if let (Some(angle), Some(Value::Int(qubit))) = (args.get(0), args.get(1))
-                        {
-                            return Some(format!("circuit.ry({}, {})", codegen_value(angle), qubit));
-                        }
+                    if let (Some(Value::Int(qubit)), Some(angle)) = (args.first(), args.get(1))
+                    {
+                        return Some(format!("circuit.ry({}, {})", codegen_value(angle), qubit));
                     }
                 }
                 "rz" => {
-                    if args.len() >= 2 {
-                        if let (Some(angle), Some(Value::Int(qubit))) = (args.get(0), args.get(1))
-                        {
-                            return Some(format!("circuit.rz({}, {})", codegen_value(angle), qubit));
-                        }
+                    if let (Some(Value::Int(qubit)), Some(angle)) = (args.first(), args.get(1))
+                    {
+                        return Some(format!("circuit.rz({}, {})", codegen_value(angle), qubit));
+                    }
+                }
+                "u" => {
+                    if let (Some(theta), Some(phi), Some(lam), Some(Value::Int(qubit))) =
+                        (args.first(), args.get(1), args.get(2), args.get(3))
+                    {
+                        return Some(format!(
+                            "circuit.u({}, {}, {}, qr[{}])",
+                            codegen_value(theta),
+                            codegen_value(phi),
+                            codegen_value(lam),
+                            qubit
+                        ));
+                    }
+                }
+                "p" => {
+                    if let (Some(lam), Some(Value::Int(qubit))) = (args.first(), args.get(1)) {
+                        return Some(format!("circuit.p({}, qr[{}])", codegen_value(lam), qubit));
+                    }
+                }
+                "reset" => {
+                    if let Some(Value::Int(qubit)) = args.first() {
+                        return Some(format!("circuit.reset(qr[{}])", qubit));
                     }
                 }
+                "barrier" => return Some("circuit.barrier()".to_string()),
                 _ => {}
             }
             None
@@ -195,16 +473,65 @@ fn try_codegen_quantum_instruction(inst: &Instruction) -> Option<String> {
     }
 }
 
+// Parametric Circuit: float parameters passed straight to a rotation (rx,
+// ry, rz, u, p) as its angle, as (SSA id, name); parameter i is SSA var i
+fn angle_parameters(func: &IRFunction) -> Vec<(usize, &str)> {
+    if func.domain != crate::frontend::ast::Domain::Quantum {
+        return Vec::new();
+    }
+    let mut angles = std::collections::HashSet::new();
+    for inst in func.blocks.iter().flat_map(|b| b.instructions.iter()) {
+        if let Instruction::Call { function, args, .. } = inst {
+            let angle_args = match function.as_str() {
+                "rx" | "ry" | "rz" => args.get(1..2),
+                "u" => args.get(..3),
+                "p" => args.get(..1),
+                _ => None,
+            };
+            angles.extend(angle_args.unwrap_or_default().iter().filter_map(|arg| match arg {
+                Value::Var(v) => Some(v.id),
+                _ => None,
+            }));
+        }
+    }
+    func.params
+        .iter()
+        .enumerate()
+        .filter(|&(i, (_, ty))| *ty == IRType::Float && angles.contains(&i))
+        .map(|(i, (name, _))| (i, name.as_str()))
+        .collect()
+}
+
 fn codegen_value(val: &Value) -> String {
     match val {
         Value::Int(n) => format!("{}", n),
-        Value::Float(f) => format!("{}", f),
+        Value::Float(f) => format!("{:?}", f),
         Value::Bool(b) => format!("{}", b),
         Value::Var(v) => format!("v{}", v.id),
         Value::Array(_) => "[]".to_string(),
+        Value::Str(s) => format!("{:?}", s),
+        Value::Tuple(elements) => format!("({})", elements.iter().map(codegen_value).collect::<Vec<_>>().join(", ")),
     }
 }
 
+// Mid-circuit Measurement: each `measure(q)` call gets its own classical
+// bit, in program order (call k writes cr[k]). Zero when the function never
+// measures explicitly, in which case every qubit is measured at the end.
+fn count_measurements(func: &IRFunction) -> usize {
+    func.blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter())
+        .filter(|inst| matches!(inst, Instruction::Call { function, .. } if function == "measure"))
+        .count()
+}
+
+fn measures_all(func: &IRFunction) -> bool {
+    func.blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter())
+        .any(|inst| matches!(inst, Instruction::Call { function, .. } if function == "measure_all"))
+}
+
 fn estimate_qubits(func: &IRFunction) -> usize {
     // Simple heuristic: count unique qubit indices in quantum operations
     let mut max_qubit = 0;
@@ -0,0 +1,276 @@
+// Structured Control Flow: neither WGSL nor Python has goto, so the CFG is
+// turned back into nested statements. Loops are found from DFS back edges
+// (header, latch and the natural loop body); a branch's arms run until their
+// merge, the first block (in reverse postorder) reachable from both arms
+// without taking a back edge. Inside a loop, jumps to the latch become
+// `continue` and jumps to the exit become `break`.
+//
+//   header: c = lt i n; br c, body, exit      Loop { header, exit when !c,
+//   body:   ...; jump latch               =>         body: [Block(body)],
+//   latch:  i = i + 1; jump header                   latch }
+//
+// Backends render the statement tree in their own syntax. Phis must be
+// eliminated first: the tree only says which blocks run, not how values merge.
+
+use crate::middle::ir::*;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+
+pub(crate) enum Stmt {
+    // The instructions of a block (its terminator is the surrounding statement)
+    Block(usize),
+    If {
+        condition: Value,
+        negated: bool,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>, // Empty when there is no else arm
+    },
+    Loop {
+        header: usize,        // Its instructions run first on every iteration
+        condition: Value,     // The header's branch condition
+        exit_when: bool,      // Leave the loop when the condition has this value
+        body: Vec<Stmt>,
+        latch: Option<usize>, // Runs after the body and before each continue
+    },
+    Return(Value),
+    ReturnVoid,
+    Break,
+    Continue,
+}
+
+/// Rebuild a function's CFG as nested statements, starting at the entry block.
+pub(crate) fn structure(func: &IRFunction) -> Result<Vec<Stmt>> {
+    Structurer::new(func).region(0, None, &mut Vec::new())
+}
+
+struct Structurer<'a> {
+    func: &'a IRFunction,
+    succs: Vec<Vec<usize>>,
+    rpo_index: Vec<usize>,             // Position in reverse postorder (usize::MAX if unreachable)
+    back_edges: HashSet<(usize, usize)>, // (latch, header)
+    loops: HashMap<usize, LoopInfo>,   // Keyed by header
+}
+
+struct LoopInfo {
+    latch: usize,
+    exit: Option<usize>,
+    body: HashSet<usize>,
+}
+
+impl<'a> Structurer<'a> {
+    fn new(func: &'a IRFunction) -> Self {
+        let index: HashMap<&str, usize> =
+            func.blocks.iter().enumerate().map(|(i, b)| (b.label.as_str(), i)).collect();
+        let succs: Vec<Vec<usize>> = func
+            .blocks
+            .iter()
+            .map(|b| match &b.terminator {
+                Terminator::Jump(t) => index.get(t.as_str()).copied().into_iter().collect(),
+                Terminator::Branch { true_label, false_label, .. } => [true_label, false_label]
+                    .iter()
+                    .filter_map(|l| index.get(l.as_str()).copied())
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        // DFS from entry: postorder and back edges (edges to a block on the stack)
+        let n = func.blocks.len();
+        let mut postorder = Vec::new();
+        let mut back_edges = HashSet::new();
+        let mut state = vec![0u8; n]; // 0 = unvisited, 1 = on stack, 2 = done
+        let mut stack = vec![(0usize, 0usize)];
+        if n > 0 {
+            state[0] = 1;
+        }
+        while let Some((b, i)) = stack.pop() {
+            if i < succs[b].len() {
+                stack.push((b, i + 1));
+                let s = succs[b][i];
+                match state[s] {
+                    0 => {
+                        state[s] = 1;
+                        stack.push((s, 0));
+                    }
+                    1 => {
+                        back_edges.insert((b, s));
+                    }
+                    _ => {}
+                }
+            } else {
+                state[b] = 2;
+                postorder.push(b);
+            }
+        }
+        let mut rpo_index = vec![usize::MAX; n];
+        for (i, &b) in postorder.iter().rev().enumerate() {
+            rpo_index[b] = i;
+        }
+
+        // Natural loop of each back edge: blocks reaching the latch without
+        // passing through the header
+        let mut preds = vec![Vec::new(); n];
+        for (b, ss) in succs.iter().enumerate() {
+            for &s in ss {
+                preds[s].push(b);
+            }
+        }
+        let mut loops = HashMap::new();
+        for &(latch, header) in &back_edges {
+            let mut body = HashSet::from([header]);
+            let mut work = vec![latch];
+            while let Some(b) = work.pop() {
+                if body.insert(b) {
+                    work.extend(preds[b].iter().copied());
+                }
+            }
+            let exit = succs[header].iter().copied().find(|s| !body.contains(s));
+            loops.insert(header, LoopInfo { latch, exit, body });
+        }
+
+        Self { func, succs, rpo_index, back_edges, loops }
+    }
+
+    // Statements for the blocks from `start` until reaching `stop` (or a
+    // return/break/continue)
+    fn region(&self, start: usize, stop: Option<usize>, open_loops: &mut Vec<usize>) -> Result<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        let mut current = start;
+        // Each block is emitted at most once per region; more means the CFG
+        // isn't one this reconstruction understands
+        for _ in 0..=self.func.blocks.len() {
+            if Some(current) == stop {
+                return Ok(stmts);
+            }
+
+            if let Some(info) = self.loops.get(&current).filter(|_| open_loops.last() != Some(&current)) {
+                let block = &self.func.blocks[current];
+                let Terminator::Branch { condition, .. } = &block.terminator else {
+                    bail!("{}: loop header '{}' does not end in a branch", self.func.name, block.label);
+                };
+                let (body, exit_when) = if info.body.contains(&self.succs[current][0]) {
+                    (self.succs[current][0], false)
+                } else {
+                    (self.succs[current][1], true)
+                };
+                open_loops.push(current);
+                let body = self.region(body, Some(info.latch), open_loops)?;
+                open_loops.pop();
+                stmts.push(Stmt::Loop {
+                    header: current,
+                    condition: condition.clone(),
+                    exit_when,
+                    body,
+                    latch: (info.latch != current).then_some(info.latch),
+                });
+                match info.exit {
+                    Some(exit) => {
+                        current = exit;
+                        continue;
+                    }
+                    None => return Ok(stmts),
+                }
+            }
+
+            let block = &self.func.blocks[current];
+            stmts.push(Stmt::Block(current));
+            match &block.terminator {
+                Terminator::Return(val) => {
+                    stmts.push(Stmt::Return(val.clone()));
+                    return Ok(stmts);
+                }
+                Terminator::ReturnVoid => {
+                    stmts.push(Stmt::ReturnVoid);
+                    return Ok(stmts);
+                }
+                Terminator::Jump(_) => {
+                    let target = self.succs[current][0];
+                    if Some(target) == stop {
+                        return Ok(stmts);
+                    }
+                    if let Some(header) = open_loops.last() {
+                        let info = &self.loops[header];
+                        if target == info.latch || target == *header {
+                            stmts.push(Stmt::Continue);
+                            return Ok(stmts);
+                        }
+                        if Some(target) == info.exit {
+                            stmts.push(Stmt::Break);
+                            return Ok(stmts);
+                        }
+                    }
+                    current = target;
+                }
+                Terminator::Branch { condition, .. } => {
+                    let (t, f) = (self.succs[current][0], self.succs[current][1]);
+                    let in_loop = open_loops.last().map(|h| &self.loops[h]);
+                    let branch = |negated, then, otherwise| Stmt::If { condition: condition.clone(), negated, then, otherwise };
+                    match self.merge_point(t, f, in_loop) {
+                        Some(merge) => {
+                            if f == merge {
+                                stmts.push(branch(false, self.region(t, Some(merge), open_loops)?, Vec::new()));
+                            } else if t == merge {
+                                stmts.push(branch(true, self.region(f, Some(merge), open_loops)?, Vec::new()));
+                            } else {
+                                let then = self.region(t, Some(merge), open_loops)?;
+                                let otherwise = self.region(f, Some(merge), open_loops)?;
+                                stmts.push(branch(false, then, otherwise));
+                            }
+                            current = merge;
+                        }
+                        // No merge: an arm that always returns/breaks/continues
+                        // becomes a guard and the other arm carries on inline
+                        None if self.never_falls_through(t, stop, in_loop) => {
+                            stmts.push(branch(false, self.region(t, None, open_loops)?, Vec::new()));
+                            current = f;
+                        }
+                        None if self.never_falls_through(f, stop, in_loop) => {
+                            stmts.push(branch(true, self.region(f, None, open_loops)?, Vec::new()));
+                            current = t;
+                        }
+                        None => {
+                            let then = self.region(t, stop, open_loops)?;
+                            let otherwise = self.region(f, stop, open_loops)?;
+                            stmts.push(branch(false, then, otherwise));
+                            return Ok(stmts);
+                        }
+                    }
+                }
+            }
+        }
+        bail!("{}: control flow could not be structured", self.func.name)
+    }
+
+    // First block in reverse postorder reachable from both arms, without
+    // taking back edges or leaving the innermost loop body (its latch counts
+    // as outside: reaching it is a `continue`)
+    fn merge_point(&self, t: usize, f: usize, in_loop: Option<&LoopInfo>) -> Option<usize> {
+        let from_t = self.forward_reachable(t, in_loop);
+        let from_f = self.forward_reachable(f, in_loop);
+        from_t
+            .intersection(&from_f)
+            .copied()
+            .min_by_key(|&b| self.rpo_index[b])
+    }
+
+    // An arm that can't reach the region's stop ends in return/break/continue
+    fn never_falls_through(&self, arm: usize, stop: Option<usize>, in_loop: Option<&LoopInfo>) -> bool {
+        stop.is_none_or(|s| !self.forward_reachable(arm, in_loop).contains(&s))
+    }
+
+    fn forward_reachable(&self, start: usize, in_loop: Option<&LoopInfo>) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut work = vec![start];
+        while let Some(b) = work.pop() {
+            if in_loop.is_some_and(|l| !l.body.contains(&b) || b == l.latch) || !seen.insert(b) {
+                continue;
+            }
+            for &s in &self.succs[b] {
+                if !self.back_edges.contains(&(b, s)) {
+                    work.push(s);
+                }
+            }
+        }
+        seen
+    }
+}
@@ -1,39 +1,249 @@
+use super::structure::Stmt;
 use crate::middle::ir::*;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
 
-pub fn codegen(module: &Module) -> Result<String> {
+// Complex numbers are vec2<f32> (re, im); addition, subtraction and
+// negation are component-wise, multiplication and division are not
+const COMPLEX_HELPERS: &str = "fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+  return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+fn complex_div(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+  let d = dot(b, b);
+  return vec2<f32>(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / d;
+}
+
+";
+
+/// Generate WGSL for every function of the module.
+///
+/// `atomic_add`/`atomic_max` on an `[int]` buffer make it an array of
+/// `atomic<i32>`, so its other loads and stores become atomic as well.
+pub fn codegen(module: &Module, strict: bool) -> Result<String> {
     let mut output = String::new();
+    super::check_unlowered_maps(module, strict)?;
+    if strict {
+        check_host_calls(module)?;
+    }
+
+    // WGSL has no phi: merged values become copies in each predecessor
+    let mut module = module.clone();
+    crate::middle::eliminate_phis(&mut module);
 
     output.push_str("// Generated WGSL code\n\n");
 
+    if uses_complex(&module) {
+        output.push_str(COMPLEX_HELPERS);
+    }
+
+    let mut bindings = Bindings::default();
     for func in &module.functions {
-        output.push_str(&codegen_function(func)?);
+        let (decls, code) = codegen_function(func, &mut bindings)?;
+        if !decls.is_empty() {
+            output.push_str(&decls);
+            output.push('\n');
+        }
+        output.push_str(&code);
         output.push('\n');
     }
 
     Ok(output)
 }
 
-fn codegen_function(func: &IRFunction) -> Result<String> {
+/// Generate WGSL as a shared preamble plus one section per function.
+///
+/// The preamble (`preamble.wgsl`) holds the helper functions and every
+/// storage buffer binding; each function follows as `<name>.wgsl`. Bindings
+/// are numbered across the whole module, so the sections concatenate into
+/// one valid shader.
+pub fn codegen_split(module: &Module, strict: bool) -> Result<Vec<(String, String)>> {
+    super::check_unlowered_maps(module, strict)?;
+    if strict {
+        check_host_calls(module)?;
+    }
+    let mut module = module.clone();
+    crate::middle::eliminate_phis(&mut module);
+
+    let mut preamble = String::from("// Generated WGSL code: shared preamble\n\n");
+    if uses_complex(&module) {
+        preamble.push_str(COMPLEX_HELPERS);
+    }
+
+    let mut bindings = Bindings::default();
+    let mut files = Vec::new();
+    for func in &module.functions {
+        let (decls, code) = codegen_function(func, &mut bindings)?;
+        preamble.push_str(&decls);
+        files.push((format!("{}.wgsl", func.name), format!("// Generated WGSL code: {}\n\n{}", func.name, code)));
+    }
+
+    files.insert(0, ("preamble.wgsl".to_string(), preamble));
+    Ok(files)
+}
+
+// Builtins that only exist on the host: printing and asserts (Python) and
+// quantum operations (the circuit). WGSL has no equivalent, so by default
+// they are emitted as calls for the reader to replace
+const HOST_BUILTINS: &[&str] = &[
+    "print", "print_int", "print_float", "print_bool", "print_array", "print_fmt", "assert",
+    "h", "x", "y", "z", "rx", "ry", "rz", "u", "p", "cx", "cnot", "cz", "measure", "measure_all", "reset", "barrier",
+];
+
+// Strict Mode: a call to a host builtin is an error naming the instruction
+// and its function (a function of the module with the same name is fine)
+fn check_host_calls(module: &Module) -> Result<()> {
+    for func in &module.functions {
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            let Instruction::Call { function, .. } = inst else { continue };
+            if HOST_BUILTINS.contains(&function.as_str()) && !module.functions.iter().any(|f| f.name == *function) {
+                bail!("unsupported instruction in WGSL function '{}': {}", func.name, crate::middle::dump_inst(inst));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Parameters (by position) that some `store` or atomic writes into
+fn written_params(func: &IRFunction) -> HashSet<usize> {
+    func.blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .filter_map(|inst| match inst {
+            Instruction::Store { array, .. } if array.id < func.params.len() => Some(array.id),
+            _ => super::atomic_buffer(inst).map(|buffer| buffer.id).filter(|&id| id < func.params.len()),
+        })
+        .collect()
+}
+
+// Buffers (parameters by position) updated by atomic_add/atomic_max: they are
+// declared array<atomic<i32>>, so every load and store of them is atomic too
+fn atomic_params(func: &IRFunction) -> HashSet<usize> {
+    func.blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .filter_map(super::atomic_buffer)
+        .map(|buffer| buffer.id)
+        .filter(|&id| id < func.params.len())
+        .collect()
+}
+
+fn uses_complex(module: &Module) -> bool {
+    module
+        .functions
+        .iter()
+        .any(|f| f.var_types.values().any(|ty| *ty == IRType::Complex))
+}
+
+// Storage Buffers: a runtime-sized array (`[float]`) can't be a function
+// parameter in WGSL, so unsized array parameters become module-scope
+// `var<storage>` bindings, numbered across the whole module
+#[derive(Default)]
+struct Bindings {
+    next: u32,
+    names: HashSet<String>,
+}
+
+// The function's storage buffer declarations and its code, separately
+fn codegen_function(func: &IRFunction, bindings: &mut Bindings) -> Result<(String, String)> {
+    let mut decls = String::new();
     let mut output = String::new();
 
-    // Function signature
-    output.push_str(&format!("fn {}(", func.name));
+    // Parameters are SSA vars 0..n; storage buffers may be renamed
+    // (f_xs) when another function already declared one called xs
+    let mut names = HashMap::new();
+    let written = written_params(func);
+    let atomics = atomic_params(func);
     for (i, (name, ty)) in func.params.iter().enumerate() {
-        if i > 0 {
-            output.push_str(", ");
+        let mut name = name.clone();
+        if let IRType::Array(elem, None) = ty {
+            if !bindings.names.insert(name.clone()) {
+                name = format!("{}_{}", func.name, name);
+                bindings.names.insert(name.clone());
+            }
+            // A buffer the function stores into is written back to the host
+            let access = if written.contains(&i) { "read_write" } else { "read" };
+            let elem = if atomics.contains(&i) { "atomic<i32>".to_string() } else { wgsl_type(elem) };
+            decls.push_str(&format!(
+                "@group(0) @binding({}) var<storage, {}> {}: array<{}>;\n",
+                bindings.next, access, name, elem
+            ));
+            bindings.next += 1;
         }
-        output.push_str(&format!("{}: {}", name, wgsl_type(ty)));
+        names.insert(i, name);
+    }
+
+    // `///` doc comment carried over from the source
+    if let Some(doc) = &func.doc {
+        for line in doc.lines() {
+            output.push_str(format!("// {}", line).trim_end());
+            output.push('\n');
+        }
+    }
+
+    // @gpu(workgroup=N): expose the requested workgroup size to dispatch code
+    if let Some(size) = func.annotations.get("workgroup") {
+        output.push_str(&format!("const {}_WORKGROUP_SIZE: u32 = {}u;\n\n", func.name.to_uppercase(), size));
+    }
+
+    // Tuples exist for the Python backends (several measurement results)
+    let is_tuple = |ty: &IRType| matches!(ty, IRType::Tuple(_));
+    if func.params.iter().map(|(_, ty)| ty).chain(func.var_types.values()).any(is_tuple)
+        || is_tuple(&func.return_type)
+    {
+        bail!("tuples are not supported in WGSL (function '{}')", func.name);
+    }
+
+    let wide = func.params.iter().map(|(_, ty)| ty).chain(func.var_types.values()).any(IRType::is_wide)
+        || func.return_type.is_wide();
+    if wide {
+        warn!(
+            "WGSL has no 64-bit types; int64/float64 in '{}' are narrowed to i32/f32",
+            func.name
+        );
+    }
+
+    // Function signature
+    let params = func
+        .params
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, ty))| !matches!(ty, IRType::Array(_, None)))
+        .map(|(i, (_, ty))| format!("{}: {}", names[&i], wgsl_type(ty)))
+        .collect::<Vec<_>>();
+    output.push_str(&format!("fn {}({}", func.name, params.join(", ")));
+    // WGSL has no `void`: a function returning nothing omits the arrow
+    if func.return_type == IRType::Void {
+        output.push_str(") {\n");
+    } else {
+        output.push_str(&format!(") -> {} {{\n", wgsl_type(&func.return_type)));
     }
-    output.push_str(&format!(") -> {} {{\n", wgsl_type(&func.return_type)));
 
     // Variable declarations (collect all SSA vars)
     let mut declared_vars = std::collections::HashSet::new();
     for block in &func.blocks {
         for inst in &block.instructions {
             if let Some(dest) = get_dest_var(inst) {
+                // Results of void calls are never stored
+                if func.var_types.get(&dest) == Some(&IRType::Void) {
+                    continue;
+                }
                 if declared_vars.insert(dest.id) {
-                    output.push_str(&format!("  var {}: {};\n", var_name(dest), infer_var_type(inst)));
+                    let ty = match func.var_types.get(&dest) {
+                        Some(
+                            ty @ (IRType::Bool
+                            | IRType::Int
+                            | IRType::Float
+                            | IRType::Complex
+                            | IRType::Int32
+                            | IRType::Int64
+                            | IRType::Float32
+                            | IRType::Float64
+                            | IRType::Array(_, Some(_))),
+                        ) => wgsl_type(ty),
+                        _ => infer_var_type(inst),
+                    };
+                    output.push_str(&format!("  var {}: {};\n", var_name(dest, &names), ty));
                 }
             }
         }
@@ -41,27 +251,139 @@ fn codegen_function(func: &IRFunction) -> Result<String> {
 
     output.push('\n');
 
-    // Blocks (WGSL doesn't have explicit blocks, so we flatten)
-    for block in &func.blocks {
-        if block.label != "entry" {
-            output.push_str(&format!("  // {}\n", block.label));
+    // Blocks: the CFG is rebuilt into structured if/else and loop statements
+    let body = super::structure::structure(func)?;
+    let emitter = Emitter { func, names, atomics };
+    emitter.emit(&body, 1, &mut output)?;
+
+    output.push_str("}\n");
+    Ok((decls, output))
+}
+
+// Structured Control Flow in WGSL syntax: the loop header's instructions
+// open the `loop` and the latch's make up its `continuing` block.
+//
+//   Loop { header, exit when !c,        loop {
+//          body: [Block(body)],   =>      c = i < n; if (!(c)) { break; }
+//          latch }                         ...
+//                                          continuing { i = i + 1; }
+//                                        }
+struct Emitter<'a> {
+    func: &'a IRFunction,
+    names: HashMap<usize, String>, // Parameter names by SSA id
+    atomics: HashSet<usize>,       // Buffers of atomic<i32>, by SSA id
+}
+
+impl Emitter<'_> {
+    fn emit(&self, stmts: &[Stmt], depth: usize, out: &mut String) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        for stmt in stmts {
+            match stmt {
+                Stmt::Block(b) => self.emit_instructions(&self.func.blocks[*b], depth, out)?,
+                Stmt::If { condition, negated, then, otherwise } => {
+                    let cond = codegen_value(condition, &self.names);
+                    if *negated {
+                        out.push_str(&format!("{}if (!({})) {{\n", indent, cond));
+                    } else {
+                        out.push_str(&format!("{}if ({}) {{\n", indent, cond));
+                    }
+                    self.emit(then, depth + 1, out)?;
+                    if !otherwise.is_empty() {
+                        out.push_str(&format!("{}}} else {{\n", indent));
+                        self.emit(otherwise, depth + 1, out)?;
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::Loop { header, condition, exit_when, body, latch } => {
+                    out.push_str(&format!("{}loop {{\n", indent));
+                    self.emit_instructions(&self.func.blocks[*header], depth + 1, out)?;
+                    let cond = codegen_value(condition, &self.names);
+                    let exit_cond = if *exit_when { cond } else { format!("!({})", cond) };
+                    out.push_str(&format!("{}  if ({}) {{ break; }}\n", indent, exit_cond));
+                    self.emit(body, depth + 1, out)?;
+                    if let Some(latch) = latch {
+                        out.push_str(&format!("{}  continuing {{\n", indent));
+                        self.emit_instructions(&self.func.blocks[*latch], depth + 2, out)?;
+                        out.push_str(&format!("{}  }}\n", indent));
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::Return(val) => out.push_str(&format!("{}return {};\n", indent, codegen_value(val, &self.names))),
+                Stmt::ReturnVoid => out.push_str(&format!("{}return;\n", indent)),
+                Stmt::Break => out.push_str(&format!("{}break;\n", indent)),
+                Stmt::Continue => out.push_str(&format!("{}continue;\n", indent)),
+            }
         }
+        Ok(())
+    }
 
+    fn emit_instructions(&self, block: &BasicBlock, depth: usize, out: &mut String) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        if block.label != "entry" {
+            out.push_str(&format!("{}// {}\n", indent, block.label));
+        }
         for inst in &block.instructions {
-            output.push_str(&format!("  {}\n", codegen_instruction(inst)?));
+            // Source Mapping: point calls and stores back at their DSL line
+            if let Instruction::Call { line: Some(line), .. } | Instruction::Store { line: Some(line), .. } = inst {
+                out.push_str(&format!("{}// line {}\n", indent, line));
+            }
+            for line in codegen_instruction(inst, &self.func.var_types, &self.names, &self.atomics)?.lines() {
+                out.push_str(&format!("{}{}\n", indent, line));
+            }
         }
-
-        output.push_str(&format!("  {}\n", codegen_terminator(&block.terminator)?));
+        Ok(())
     }
-
-    output.push_str("}\n");
-    Ok(output)
 }
 
-fn codegen_instruction(inst: &Instruction) -> Result<String> {
+fn codegen_instruction(
+    inst: &Instruction,
+    types: &HashMap<SSAVar, IRType>,
+    names: &HashMap<usize, String>,
+    atomics: &HashSet<usize>,
+) -> Result<String> {
     match inst {
+        // Computed Array Literals: `[x, y, z]` fills the declared array one
+        // element at a time; only all-constant literals use `array(...)`
+        Instruction::Assign { dest, value: Value::Array(elements) } if !elements.iter().all(is_constant) => {
+            Ok(element_stores(&var_name(*dest, names), elements, names).join("\n"))
+        }
         Instruction::Assign { dest, value } => {
-            Ok(format!("{} = {};", var_name(*dest), codegen_value(value)))
+            Ok(format!("{} = {};", var_name(*dest, names), codegen_value(value, names)))
+        }
+        Instruction::BinaryOp {
+            dest,
+            op,
+            left,
+            right,
+        } if is_complex(left, types) => {
+            let (l, r) = (codegen_value(left, names), codegen_value(right, names));
+            let expr = match op {
+                BinOp::Mul => format!("complex_mul({}, {})", l, r),
+                BinOp::Div => format!("complex_div({}, {})", l, r),
+                BinOp::Eq => format!("all({} == {})", l, r),
+                BinOp::Ne => format!("any({} != {})", l, r),
+                _ => format!("{} {} {}", l, wgsl_binop(*op), r),
+            };
+            Ok(format!("{} = {};", var_name(*dest, names), expr))
+        }
+        // Elementwise array arithmetic: one loop over the shared length
+        Instruction::BinaryOp {
+            dest,
+            op,
+            left,
+            right,
+        } if matches!(types.get(dest), Some(IRType::Array(..))) => {
+            let Some(IRType::Array(_, Some(n))) = types.get(dest) else {
+                bail!("elementwise array arithmetic in WGSL needs array sizes known at compile time");
+            };
+            Ok(format!(
+                "for (var i: i32 = 0; i < {}; i++) {{ {}[i] = {}[i] {} {}[i]; }}",
+                n,
+                var_name(*dest, names),
+                codegen_value(left, names),
+                wgsl_binop(*op),
+                codegen_value(right, names)
+            ))
         }
         Instruction::BinaryOp {
             dest,
@@ -70,60 +392,150 @@ fn codegen_instruction(inst: &Instruction) -> Result<String> {
             right,
         } => Ok(format!(
             "{} = {} {} {};",
-            var_name(*dest),
-            codegen_value(left),
+            var_name(*dest, names),
+            codegen_value(left, names),
             wgsl_binop(*op),
-            codegen_value(right)
+            codegen_value(right, names)
         )),
         Instruction::UnaryOp { dest, op, operand } => Ok(format!(
             "{} = {}({});",
-            var_name(*dest),
+            var_name(*dest, names),
             wgsl_unop(*op),
-            codegen_value(operand)
+            codegen_value(operand, names)
+        )),
+        Instruction::Cast { dest, value, to } => Ok(format!(
+            "{} = {}({});",
+            var_name(*dest, names),
+            wgsl_type(to),
+            codegen_value(value, names)
+        )),
+        Instruction::Load { dest, array, index } if atomics.contains(&array.id) => Ok(format!(
+            "{} = atomicLoad(&{}[{}]);",
+            var_name(*dest, names),
+            var_name(*array, names),
+            codegen_value(index, names)
         )),
         Instruction::Load { dest, array, index } => Ok(format!(
             "{} = {}[{}];",
-            var_name(*dest),
-            var_name(*array),
-            codegen_value(index)
+            var_name(*dest, names),
+            var_name(*array, names),
+            codegen_value(index, names)
+        )),
+        Instruction::Store { array, index, value, .. } if atomics.contains(&array.id) => Ok(format!(
+            "atomicStore(&{}[{}], {});",
+            var_name(*array, names),
+            codegen_value(index, names),
+            codegen_value(value, names)
         )),
         Instruction::Store {
             array,
             index,
             value,
+            ..
         } => Ok(format!(
             "{}[{}] = {};",
-            var_name(*array),
-            codegen_value(index),
-            codegen_value(value)
+            var_name(*array, names),
+            codegen_value(index, names),
+            codegen_value(value, names)
         )),
         Instruction::Call {
             dest,
             function,
             args,
+            ..
         } => {
             let args_str = args
                 .iter()
-                .map(codegen_value)
+                .map(|v| codegen_value(v, names))
                 .collect::<Vec<_>>()
                 .join(", ");
-            if let Some(d) = dest {
-                Ok(format!("{} = {}({});", var_name(*d), function, args_str))
+            // zeros/ones/range: fill the declared buffer element by element
+            if let (Some(d), "zeros" | "ones" | "range", Some(Value::Int(n))) =
+                (dest, function.as_str(), args.first())
+            {
+                let elem = match function.as_str() {
+                    "zeros" => "0.0",
+                    "ones" => "1.0",
+                    _ => "i",
+                };
+                return Ok(format!(
+                    "for (var i: i32 = 0; i < {}; i++) {{ {}[i] = {}; }}",
+                    n,
+                    var_name(*d, names),
+                    elem
+                ));
+            }
+            // matmul(a, b): loop nest over the (R x K) * (K x C) sizes
+            if let (Some(d), "matmul", [a, b]) = (dest, function.as_str(), args.as_slice()) {
+                let (rows, cols, elem) = match types.get(d) {
+                    Some(IRType::Array(row, Some(rows))) => match &**row {
+                        IRType::Array(elem, Some(cols)) => (*rows, *cols, wgsl_type(elem)),
+                        _ => bail!("matmul in WGSL needs matrix sizes known at compile time"),
+                    },
+                    _ => bail!("matmul in WGSL needs matrix sizes known at compile time"),
+                };
+                let inner = match a {
+                    Value::Var(v) => match types.get(v) {
+                        Some(IRType::Array(row, _)) => match &**row {
+                            IRType::Array(_, Some(k)) => *k,
+                            _ => bail!("matmul in WGSL needs matrix sizes known at compile time"),
+                        },
+                        _ => bail!("matmul in WGSL needs matrix sizes known at compile time"),
+                    },
+                    _ => bail!("matmul in WGSL needs matrix sizes known at compile time"),
+                };
+                let (a, b, d) = (codegen_value(a, names), codegen_value(b, names), var_name(*d, names));
+                return Ok(format!(
+                    "for (var i: i32 = 0; i < {rows}; i++) {{\n  \
+                     for (var j: i32 = 0; j < {cols}; j++) {{\n    \
+                     var acc: {elem} = {elem}(0);\n    \
+                     for (var k: i32 = 0; k < {inner}; k++) {{ acc += {a}[i][k] * {b}[k][j]; }}\n    \
+                     {d}[i][j] = acc;\n  \
+                     }}\n\
+                     }}"
+                ));
+            }
+            // atomic_add(buf, i, v) -> atomicAdd(&buf[i], v), the old value
+            if let (Some(atomic), [Value::Var(buffer), index, value]) =
+                (atomic_builtin(function), args.as_slice())
+            {
+                let call = format!(
+                    "{}(&{}[{}], {})",
+                    atomic,
+                    var_name(*buffer, names),
+                    codegen_value(index, names),
+                    codegen_value(value, names)
+                );
+                return Ok(match dest {
+                    Some(d) => format!("{} = {};", var_name(*d, names), call),
+                    None => format!("{};", call),
+                });
+            }
+            if let (Some(d), "sum" | "product" | "dot") = (dest, function.as_str()) {
+                return reduction(function, *d, args, types, names);
+            }
+            // len of a runtime-sized array: arrayLength counts in u32
+            if let (Some(d), "len", [Value::Var(a)]) = (dest, function.as_str(), args.as_slice()) {
+                return Ok(format!("{} = i32(arrayLength(&{}));", var_name(*d, names), var_name(*a, names)));
+            }
+            // complex(re, im) constructs the vector directly
+            let function = if function == "complex" { "vec2<f32>" } else { function.as_str() };
+            if let Some(d) = dest.filter(|d| types.get(d) != Some(&IRType::Void)) {
+                Ok(format!("{} = {}({});", var_name(d, names), function, args_str))
             } else {
                 Ok(format!("{}({});", function, args_str))
             }
         }
         Instruction::Phi { .. } => {
-            // Phi nodes should be eliminated before codegen
-            Ok("// phi node".to_string())
+            bail!("phi node reached WGSL codegen (eliminate_phis should have removed it)")
         }
         Instruction::DomainConversion { dest, source, from_domain, to_domain, encoding } => {
             // Domain conversions are handled by orchestrator, not in WGSL
             // Just pass through the value
             Ok(format!(
                 "{} = {}; // conversion {:?} -> {:?} ({:?})",
-                var_name(*dest),
-                codegen_value(source),
+                var_name(*dest, names),
+                codegen_value(source, names),
                 from_domain,
                 to_domain,
                 encoding
@@ -132,46 +544,133 @@ fn codegen_instruction(inst: &Instruction) -> Result<String> {
     }
 }
 
-fn codegen_terminator(term: &Terminator) -> Result<String> {
-    match term {
-        Terminator::Return(val) => Ok(format!("return {};", codegen_value(val))),
-        Terminator::ReturnVoid => Ok("return;".to_string()),
-        Terminator::Branch { .. } | Terminator::Jump(_) => {
-            // Control flow should be handled differently in WGSL
-            Ok("// branch".to_string())
-        }
+fn atomic_builtin(function: &str) -> Option<&'static str> {
+    match function {
+        "atomic_add" => Some("atomicAdd"),
+        "atomic_max" => Some("atomicMax"),
+        _ => None,
+    }
+}
+
+// Reductions: a statically-sized array is copied into a scratch array and
+// summed pairwise (stride 1, 2, 4, ...) into element 0, which also keeps
+// float rounding error low. A runtime-sized storage buffer can't be copied,
+// so it is accumulated in one loop over arrayLength. dot multiplies the
+// elements pairwise first.
+fn reduction(
+    function: &str,
+    dest: SSAVar,
+    args: &[Value],
+    types: &HashMap<SSAVar, IRType>,
+    names: &HashMap<usize, String>,
+) -> Result<String> {
+    let op = if function == "product" { "*" } else { "+" };
+    let elem = types.get(&dest).map_or("f32".to_string(), wgsl_type);
+    let identity = if function == "product" { format!("{}(1)", elem) } else { format!("{}(0)", elem) };
+    let d = var_name(dest, names);
+    let size = match args.first() {
+        Some(Value::Array(elements)) => Some(elements.len()),
+        Some(Value::Var(v)) => match types.get(v) {
+            Some(IRType::Array(_, size)) => *size,
+            _ => None,
+        },
+        _ => None,
+    };
+    let (a, b) = match args {
+        [a] => (codegen_value(a, names), None),
+        [a, b] => (codegen_value(a, names), Some(codegen_value(b, names))),
+        _ => bail!("{} expects an array argument", function),
+    };
+    let element = |i: &str| match &b {
+        Some(b) => format!("{a}[{i}] * {b}[{i}]"),
+        None => format!("{a}[{i}]"),
+    };
+
+    let Some(n) = size else {
+        return Ok(format!(
+            "{d} = {identity};\nfor (var i: i32 = 0; i < i32(arrayLength(&{a})); i++) {{ {d} = {d} {op} {}; }}",
+            element("i")
+        ));
+    };
+    if n == 0 {
+        return Ok(format!("{} = {};", d, identity));
     }
+    let fill = match &b {
+        Some(_) => format!(
+            "  var t: array<{elem}, {n}>;\n  for (var i: i32 = 0; i < {n}; i++) {{ t[i] = {}; }}\n",
+            element("i")
+        ),
+        None => format!("  var t = {a};\n"),
+    };
+    Ok(format!(
+        "{{\n{fill}  for (var s: i32 = 1; s < {n}; s = s * 2) {{\n    \
+         for (var i: i32 = 0; i + s < {n}; i = i + 2 * s) {{ t[i] = t[i] {op} t[i + s]; }}\n  \
+         }}\n  {d} = t[0];\n}}"
+    ))
 }
 
-fn codegen_value(val: &Value) -> String {
+fn codegen_value(val: &Value, names: &HashMap<usize, String>) -> String {
     match val {
-        Value::Var(v) => var_name(*v),
+        Value::Var(v) => var_name(*v, names),
         Value::Int(n) => format!("{}", n),
-        Value::Float(f) => format!("{}", f),
+        // Debug formatting is the shortest round-trip form and keeps the `.0` on
+        // whole numbers (3.0, not 3), so floats stay floats in the generated code
+        Value::Float(f) => format!("{:?}", f),
         Value::Bool(b) => format!("{}", b),
+        Value::Str(s) => format!("{:?}", s),
         Value::Array(elements) => {
-            let elems_str = elements.iter().map(codegen_value).collect::<Vec<_>>().join(", ");
+            let elems_str = elements.iter().map(|v| codegen_value(v, names)).collect::<Vec<_>>().join(", ");
             format!("array({})", elems_str)
         }
+        Value::Tuple(_) => unreachable!("functions using tuples are rejected before codegen"),
+    }
+}
+
+fn is_constant(val: &Value) -> bool {
+    match val {
+        Value::Int(_) | Value::Float(_) | Value::Bool(_) => true,
+        Value::Array(elements) => elements.iter().all(is_constant),
+        _ => false,
+    }
+}
+
+// `target[i] = element;` per element, recursing into nested computed arrays
+fn element_stores(target: &str, elements: &[Value], names: &HashMap<usize, String>) -> Vec<String> {
+    let mut stores = Vec::new();
+    for (i, element) in elements.iter().enumerate() {
+        let slot = format!("{}[{}]", target, i);
+        match element {
+            Value::Array(inner) if !is_constant(element) => stores.extend(element_stores(&slot, inner, names)),
+            _ => stores.push(format!("{} = {};", slot, codegen_value(element, names))),
+        }
     }
+    stores
 }
 
-fn var_name(var: SSAVar) -> String {
-    format!("v{}", var.id)
+// Parameters keep their source names; everything else is v<id>
+fn var_name(var: SSAVar, names: &HashMap<usize, String>) -> String {
+    names.get(&var.id).cloned().unwrap_or_else(|| format!("v{}", var.id))
 }
 
 fn wgsl_type(ty: &IRType) -> String {
     match ty {
-        IRType::Int => "i32".to_string(),
-        IRType::Float => "f32".to_string(),
+        // WGSL has no 64-bit types: int64/float64 narrow to i32/f32
+        IRType::Int | IRType::Int32 | IRType::Int64 => "i32".to_string(),
+        IRType::Float | IRType::Float32 | IRType::Float64 => "f32".to_string(),
         IRType::Bool => "bool".to_string(),
         IRType::Array(elem, Some(size)) => format!("array<{}, {}>", wgsl_type(elem), size),
         IRType::Array(elem, None) => format!("array<{}>", wgsl_type(elem)),
         IRType::Qubit => "u32".to_string(), // Placeholder
         IRType::Void => "void".to_string(),
+        IRType::Complex => "vec2<f32>".to_string(),
+        IRType::Tuple(_) => unreachable!("functions using tuples are rejected before codegen"),
     }
 }
 
+fn is_complex(value: &Value, types: &HashMap<SSAVar, IRType>) -> bool {
+    matches!(value, Value::Var(v) if types.get(v) == Some(&IRType::Complex))
+}
+
 fn wgsl_binop(op: BinOp) -> &'static str {
     match op {
         BinOp::Add => "+",
@@ -187,6 +686,8 @@ fn wgsl_binop(op: BinOp) -> &'static str {
         BinOp::Ge => ">=",
         BinOp::And => "&&",
         BinOp::Or => "||",
+        BinOp::Shl => "<<",
+        BinOp::Shr => ">>",
     }
 }
 
@@ -202,6 +703,7 @@ fn get_dest_var(inst: &Instruction) -> Option<SSAVar> {
         Instruction::Assign { dest, .. }
         | Instruction::BinaryOp { dest, .. }
         | Instruction::UnaryOp { dest, .. }
+        | Instruction::Cast { dest, .. }
         | Instruction::Load { dest, .. }
         | Instruction::Phi { dest, .. } => Some(*dest),
         Instruction::Call { dest, .. } => *dest,
@@ -218,14 +720,16 @@ fn infer_var_type(inst: &Instruction) -> String {
             Value::Bool(_) => "bool".to_string(),
             _ => "i32".to_string(), // Default
         },
-        Instruction::BinaryOp { op, .. } => match op {
-            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::And | BinOp::Or => "bool".to_string(),
-            _ => "i32".to_string(),
-        },
+        Instruction::BinaryOp {
+            op: BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::And | BinOp::Or,
+            ..
+        } => "bool".to_string(),
+        Instruction::BinaryOp { .. } => "i32".to_string(),
         Instruction::UnaryOp { op, .. } => match op {
             UnOp::Not => "bool".to_string(),
             UnOp::Neg => "i32".to_string(),
         },
+        Instruction::Cast { to, .. } => wgsl_type(to),
         _ => "i32".to_string(),
     }
 }
@@ -1,6 +1,8 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::Target;
 
 #[derive(Parser)]
 #[command(name = "quarkdsl")]
@@ -9,13 +11,42 @@ use std::path::PathBuf;
 pub struct Args {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Show INFO messages (optimization progress, cross-domain conversions)
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Suppress warnings
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// How errors and warnings are written to stderr
+    #[arg(long, value_enum, global = true, default_value = "human")]
+    pub message_format: MessageFormat,
+
+    /// Unroll constant `for` loops of at most N iterations; longer ones stay loops
+    #[arg(long, value_name = "N", global = true, default_value_t = crate::middle::DEFAULT_MAX_UNROLL)]
+    pub max_unroll: usize,
+
+    /// With -O: run only these optimization passes, in this order, instead of the full pipeline
+    #[arg(long, value_name = "PASS", global = true, value_delimiter = ',',
+          value_parser = clap::builder::PossibleValuesParser::new(crate::middle::pass_names()))]
+    pub passes: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    /// Source snippets with a caret under the problem
+    Human,
+    /// One JSON object per line (severity, message, line, col, span) for IDEs
+    Json,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     /// Compile DSL source to target backend
     Compile {
-        /// Input DSL file
+        /// Input DSL file (`-` reads from stdin)
         input: PathBuf,
 
         /// Target backend
@@ -33,36 +64,114 @@ pub enum Command {
         /// Enable optimizations
         #[arg(short = 'O', long)]
         optimize: bool,
+
+        /// Verify IR invariants (SSA, dominance, branch targets) after optimization
+        #[arg(long)]
+        verify: bool,
+
+        /// Seed the quantum simulator and transpiler for reproducible results
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Write a requirements.txt for the generated Python next to the output file
+        #[arg(long, requires = "output")]
+        emit_deps: bool,
+
+        /// Fail on instructions a backend cannot express instead of dropping them
+        #[arg(long)]
+        strict: bool,
+
+        /// Print how long each optimization pass and backend took to stderr
+        #[arg(long)]
+        time_passes: bool,
+
+        /// With --target wgsl: write preamble.wgsl (helpers, bindings) and one file per function into the --output directory
+        #[arg(long, requires = "output")]
+        split_functions: bool,
+
+        /// Fail if a @quantum function needs more than N qubits (the target device's size)
+        #[arg(long, value_name = "N")]
+        max_qubits: Option<usize>,
+
+        /// Run circuits on this IBM device instead of the least busy one (unless @quantum(backend="...") names another)
+        #[arg(long, value_name = "NAME", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+        target_device: Option<String>,
     },
 
     /// Parse and dump AST
     Parse {
-        /// Input DSL file
+        /// Input DSL file (`-` reads from stdin)
+        input: PathBuf,
+    },
+
+    /// Type check and print each function's signature and inferred `let` types
+    Types {
+        /// Input DSL file (`-` reads from stdin)
         input: PathBuf,
     },
 
     /// Lower to IR and dump
     Lower {
-        /// Input DSL file
+        /// Input DSL file (`-` reads from stdin)
+        input: PathBuf,
+
+        /// Enable optimizations
+        #[arg(short = 'O', long)]
+        optimize: bool,
+
+        /// Verify IR invariants (SSA, dominance, branch targets) after optimization
+        #[arg(long)]
+        verify: bool,
+
+        /// Annotate each SSA variable with its type
+        #[arg(long)]
+        typed: bool,
+    },
+
+    /// Estimate quantum resources (qubits, gate counts, depth)
+    Estimate {
+        /// Input DSL file (`-` reads from stdin)
         input: PathBuf,
 
         /// Enable optimizations
         #[arg(short = 'O', long)]
         optimize: bool,
     },
-}
 
-#[derive(Clone, Copy, ValueEnum)]
-pub enum Target {
-    /// WebGPU WGSL backend
-    Wgsl,
-    /// Quantum Qiskit backend
-    Quantum,
-    /// Python Orchestrator (Hybrid GPU + Quantum)
-    Orchestrator,
+    /// Lower to IR and emit the control flow graph as Graphviz dot
+    Cfg {
+        /// Input DSL file (`-` reads from stdin)
+        input: PathBuf,
+
+        /// Enable optimizations
+        #[arg(short = 'O', long)]
+        optimize: bool,
+    },
 }
 
 pub fn run(args: Args) -> Result<()> {
+    crate::log::set_verbosity(if args.verbose {
+        crate::log::VERBOSE
+    } else if args.quiet {
+        crate::log::QUIET
+    } else {
+        crate::log::NORMAL
+    });
+
+    let options = crate::middle::Options { max_unroll: args.max_unroll, passes: args.passes };
+
+    // JSON Diagnostics: warnings become objects like errors do
+    if args.message_format == MessageFormat::Json {
+        crate::log::set_sink(Some(Box::new(|line: &str| {
+            let (severity, message) = match line.split_once(": ") {
+                Some(("WARNING", message)) => ("warning", message),
+                Some(("INFO", message)) => ("info", message),
+                _ => ("info", line),
+            };
+            eprintln!("{}", json_diagnostic(severity, message, None));
+        })));
+    }
+
     match args.command {
         Command::Compile {
             input,
@@ -70,25 +179,37 @@ pub fn run(args: Args) -> Result<()> {
             output,
             dump_ir,
             optimize,
+            verify,
+            seed,
+            emit_deps,
+            strict,
+            time_passes,
+            split_functions,
+            max_qubits,
+            target_device,
         } => {
-            let source = std::fs::read_to_string(&input)
-                .with_context(|| format!("Failed to read input file: {:?}", input))?;
+            let source = read_source(&input)?;
+            crate::timing::set_enabled(time_passes);
 
-            // Frontend: Parse
-            let ast = crate::frontend::parse(&source)
-                .with_context(|| "Failed to parse source")?;
+            // Frontend and middle-end: parse, typecheck, lower, optimize
+            let mut ir = source_to_ir(&input, &source, optimize, &options)?;
 
-            // Frontend: Type check
-            crate::frontend::typecheck(&ast)
-                .with_context(|| "Type checking failed")?;
+            // Middle-end: Verify
+            if verify {
+                crate::middle::verify(&ir)
+                    .with_context(|| "IR verification failed")?;
+            }
 
-            // Middle-end: Lower to IR
-            let mut ir = crate::middle::lower_to_ir(&ast)
-                .with_context(|| "Failed to lower to IR")?;
+            // Device size: every circuit must fit before any file is written
+            if let Some(max_qubits) = max_qubits {
+                crate::backend::quantum::check_max_qubits(&ir, max_qubits)?;
+            }
 
-            // Middle-end: Optimize
-            if optimize {
-                crate::middle::optimize(&mut ir);
+            // Device choice: circuits without @quantum(backend="...") use --target-device
+            if let Some(device) = target_device {
+                for func in ir.functions.iter_mut().filter(|f| f.domain == crate::frontend::ast::Domain::Quantum) {
+                    func.device.get_or_insert_with(|| device.clone());
+                }
             }
 
             // Dump IR if requested
@@ -98,18 +219,80 @@ pub fn run(args: Args) -> Result<()> {
                 eprintln!();
             }
 
+            // --target all: one file per backend in the output directory
+            if let Target::All = target {
+                let Some(dir) = output else {
+                    bail!("--target all requires --output <directory>");
+                };
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create output directory: {:?}", dir))?;
+                let files = crate::codegen_all(&ir, seed, strict)?;
+                if time_passes {
+                    eprint!("{}", crate::timing::report());
+                }
+                for (file, code) in files {
+                    let path = dir.join(file);
+                    std::fs::write(&path, code)
+                        .with_context(|| format!("Failed to write output: {:?}", path))?;
+                    println!("✓ Compiled to {:?}", path);
+                }
+                if emit_deps {
+                    if let Some(requirements) = python_requirements(&ir, target) {
+                        let deps_path = dir.join("requirements.txt");
+                        std::fs::write(&deps_path, requirements)
+                            .with_context(|| format!("Failed to write dependencies: {:?}", deps_path))?;
+                        println!("✓ Dependencies written to {:?}", deps_path);
+                    }
+                }
+                return Ok(());
+            }
+
+            // --split-functions: WGSL preamble plus one file per function
+            if split_functions {
+                let (Target::Wgsl, Some(dir)) = (target, output) else {
+                    bail!("--split-functions requires --target wgsl");
+                };
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create output directory: {:?}", dir))?;
+                let files = crate::timing::time("codegen wgsl", || crate::backend::wgsl::codegen_split(&ir, strict))?;
+                if time_passes {
+                    eprint!("{}", crate::timing::report());
+                }
+                for (file, code) in files {
+                    let path = dir.join(file);
+                    std::fs::write(&path, code)
+                        .with_context(|| format!("Failed to write output: {:?}", path))?;
+                    println!("✓ Compiled to {:?}", path);
+                }
+                if emit_deps {
+                    warn!("--emit-deps ignored: WGSL output has no Python dependencies");
+                }
+                return Ok(());
+            }
+
             // Backend: Code generation
-            let code = match target {
-                Target::Wgsl => crate::backend::wgsl::codegen(&ir)?,
-                Target::Quantum => crate::backend::quantum::codegen(&ir)?,
-                Target::Orchestrator => crate::backend::orchestrator::generate_orchestrator(&ir)?,
-            };
+            let code = crate::codegen(&ir, target, seed, strict)?;
+            if time_passes {
+                eprint!("{}", crate::timing::report());
+            }
 
             // Output
             if let Some(output_path) = output {
                 std::fs::write(&output_path, code)
                     .with_context(|| format!("Failed to write output: {:?}", output_path))?;
                 println!("✓ Compiled to {:?}", output_path);
+
+                if emit_deps {
+                    match python_requirements(&ir, target) {
+                        Some(requirements) => {
+                            let deps_path = output_path.with_file_name("requirements.txt");
+                            std::fs::write(&deps_path, requirements)
+                                .with_context(|| format!("Failed to write dependencies: {:?}", deps_path))?;
+                            println!("✓ Dependencies written to {:?}", deps_path);
+                        }
+                        None => warn!("--emit-deps ignored: WGSL output has no Python dependencies"),
+                    }
+                }
             } else {
                 println!("{}", code);
             }
@@ -118,8 +301,7 @@ pub fn run(args: Args) -> Result<()> {
         }
 
         Command::Parse { input } => {
-            let source = std::fs::read_to_string(&input)
-                .with_context(|| format!("Failed to read input file: {:?}", input))?;
+            let source = read_source(&input)?;
 
             let ast = crate::frontend::parse(&source)
                 .with_context(|| "Failed to parse source")?;
@@ -128,26 +310,146 @@ pub fn run(args: Args) -> Result<()> {
             Ok(())
         }
 
-        Command::Lower { input, optimize } => {
-            let source = std::fs::read_to_string(&input)
-                .with_context(|| format!("Failed to read input file: {:?}", input))?;
+        Command::Types { input } => {
+            let source = read_source(&input)?;
 
             let ast = crate::frontend::parse(&source)
                 .with_context(|| "Failed to parse source")?;
-
-            crate::frontend::typecheck(&ast)
+            let types = crate::frontend::infer_types(&ast)
+                .map_err(|e| crate::frontend::locate(&source, e))
                 .with_context(|| "Type checking failed")?;
 
-            let mut ir = crate::middle::lower_to_ir(&ast)
-                .with_context(|| "Failed to lower to IR")?;
+            print!("{}", format_types(&types));
+            Ok(())
+        }
 
-            if optimize {
-                crate::middle::optimize(&mut ir);
+        Command::Lower {
+            input,
+            optimize,
+            verify,
+            typed,
+        } => {
+            let source = read_source(&input)?;
+            let ir = source_to_ir(&input, &source, optimize, &options)?;
+
+            if verify {
+                crate::middle::verify(&ir)
+                    .with_context(|| "IR verification failed")?;
+            }
+
+            if typed {
+                println!("{}", crate::middle::dump_ir_typed(&ir));
+            } else {
+                println!("{}", crate::middle::dump_ir(&ir));
             }
+            Ok(())
+        }
+
+        Command::Estimate { input, optimize } => {
+            let source = read_source(&input)?;
+            let ir = source_to_ir(&input, &source, optimize, &options)?;
+
+            print!("{}", crate::backend::quantum::estimate_resources(&ir));
+            Ok(())
+        }
+
+        Command::Cfg { input, optimize } => {
+            let source = read_source(&input)?;
+            let ir = source_to_ir(&input, &source, optimize, &options)?;
 
-            println!("{}", crate::middle::dump_ir(&ir));
+            print!("{}", crate::middle::dump_cfg_dot(&ir));
             Ok(())
         }
     }
 }
 
+// `types` output: one signature line per function, then its `let` bindings
+fn format_types(types: &[crate::frontend::FunctionTypes]) -> String {
+    use crate::frontend::ast::Domain;
+
+    let mut output = String::new();
+    for func in types {
+        let domain = match func.domain {
+            Domain::Gpu => "@gpu ",
+            Domain::Quantum => "@quantum ",
+            Domain::Classical => "",
+        };
+        let params: Vec<_> = func.params.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect();
+        output.push_str(&format!("{}fn {}({}) -> {}\n", domain, func.name, params.join(", "), func.return_type));
+        for (name, ty) in &func.lets {
+            output.push_str(&format!("    let {}: {}\n", name, ty));
+        }
+    }
+    output
+}
+
+/// An error as a JSON diagnostic. Source errors (from the parser and type
+/// checker) carry their position; anything else has null `line`, `col` and
+/// `span`.
+pub fn json_error(error: &anyhow::Error) -> String {
+    match error.chain().find_map(|e| e.downcast_ref::<crate::frontend::SourceError>()) {
+        Some(source_error) => json_diagnostic("error", &source_error.message, Some(source_error)),
+        None => json_diagnostic("error", &format!("{:#}", error), None),
+    }
+}
+
+fn json_diagnostic(severity: &str, message: &str, at: Option<&crate::frontend::SourceError>) -> String {
+    serde_json::json!({
+        "severity": severity,
+        "message": message,
+        "line": at.map(|e| e.line),
+        "col": at.map(|e| e.column),
+        "span": at.map(|e| serde_json::json!({ "start": e.span.start, "end": e.span.end })),
+    })
+    .to_string()
+}
+
+// Textual IR: a `.ir` input (a `lower --typed` dump) skips the frontend and
+// continues from the middle end
+fn source_to_ir(input: &Path, source: &str, optimize: bool, options: &crate::middle::Options) -> Result<crate::middle::ir::Module> {
+    if input.extension().is_some_and(|ext| ext == "ir") {
+        let mut ir = crate::middle::parse_ir(source).with_context(|| "Failed to parse IR")?;
+        if optimize {
+            crate::middle::optimize(&mut ir, options)?;
+        }
+        Ok(ir)
+    } else {
+        crate::compile_to_ir(source, optimize, options)
+    }
+}
+
+// `-` as the input path reads the program from stdin (editor integration, pipes)
+fn read_source(input: &PathBuf) -> Result<String> {
+    if input.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin()).with_context(|| "Failed to read source from stdin")
+    } else {
+        std::fs::read_to_string(input).with_context(|| format!("Failed to read input file: {:?}", input))
+    }
+}
+
+
+// Pinned Python packages the generated code imports. Qiskit is only needed
+// when the program actually has quantum code.
+fn python_requirements(ir: &crate::middle::ir::Module, target: Target) -> Option<String> {
+    let uses_quantum = ir
+        .functions
+        .iter()
+        .any(|f| f.domain == crate::frontend::ast::Domain::Quantum);
+
+    let mut packages = Vec::new();
+    match target {
+        Target::Wgsl => return None,
+        Target::Quantum => {}
+        Target::Orchestrator | Target::All => packages.push("numpy==2.2.6"),
+    }
+    if uses_quantum || matches!(target, Target::Quantum) {
+        packages.extend(["qiskit==2.1.1", "qiskit-aer==0.17.1", "qiskit-ibm-runtime==0.41.0"]);
+    }
+
+    let mut requirements = String::from("# Generated by quarkdsl\n");
+    for package in packages {
+        requirements.push_str(package);
+        requirements.push('\n');
+    }
+    Some(requirements)
+}
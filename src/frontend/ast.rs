@@ -1,12 +1,50 @@
-/// Abstract Syntax Tree definitions for QuarkDSL
+//! Abstract Syntax Tree definitions for QuarkDSL
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
+    pub constants: Vec<Const>,
+    pub enums: Vec<Enum>,
+    pub externs: Vec<Extern>,
     pub functions: Vec<Function>,
 }
 
+/// Function provided outside the generated code:
+/// `extern fn name(params) -> type;`. Calls are type checked against the
+/// signature and emitted as-is; no definition is generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extern {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Type,
+    pub domain: Domain,
+}
+
+/// Module-level compile-time constant: `const NAME: Type = expr;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Const {
+    pub name: String,
+    pub ty: Type,
+    pub value: Expression,
+}
+
+/// Group of named integer constants: `enum Name { A = 0, B, ... }`,
+/// referenced as `Name::A`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub members: Vec<EnumMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMember {
+    pub name: String,
+    pub value: Expression,  // Implicit values are filled in by the parser
+}
+
 /// Execution domain for functions
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Domain {
@@ -22,6 +60,10 @@ pub struct Function {
     pub return_type: Type,
     pub body: Vec<Statement>,
     pub domain: Domain,  // NEW: execution domain
+    pub annotations: HashMap<String, i64>, // Domain annotation arguments, e.g. @gpu(workgroup=64)
+    pub device: Option<String>, // IBM device from @quantum(backend="ibm_brisbane")
+    pub doc: Option<String>,  // `///` doc comment lines preceding the function
+    pub span: Range<usize>,   // Byte range of the function's name (for diagnostics)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,20 +81,43 @@ pub enum Type {
     Qubit,
     Void,
     Tensor(Box<Type>),  // NEW: GPU tensor type, e.g., tensor<float>
+    Matrix(Box<Type>, Option<(usize, usize)>), // matrix<T> or matrix<T, rows, cols>; a nested array
     QState,             // NEW: Quantum state type
+    Complex,            // Complex number (quantum amplitudes)
+    Int32,              // Explicit-width int (plain `int` is i32 on GPU, i64 in Python)
+    Int64,
+    Float32,            // Explicit-width float (plain `float` is f32 on GPU, f64 in Python)
+    Float64,
+    Tuple(Vec<Type>),   // (int, int): several results from one function
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Range<usize>, // Byte range in the source (for diagnostics)
+}
+
+// Statements compare by structure: the same code parsed from differently
+// spelled source (`a and b`, `a && b`) is equal
+impl PartialEq for Statement {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Statement {
+pub enum StatementKind {
     Let {
         name: String,
+        mutable: bool,  // `let mut`: only mutable bindings can be reassigned
         ty: Option<Type>,
         value: Expression,
     },
     Assign {
         target: String,
-        index: Option<Box<Expression>>,
+        indices: Vec<Expression>, // m[i][j] = v: [i, j]
         value: Expression,
+        line: usize, // Source line
     },
     Return(Expression),
     Expression(Expression),
@@ -67,6 +132,8 @@ pub enum Statement {
         then_body: Vec<Statement>,
         else_body: Option<Vec<Statement>>,
     },
+    Break,
+    Continue,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -74,8 +141,18 @@ pub enum Expression {
     IntLiteral(i64),
     FloatLiteral(f64),
     BoolLiteral(bool),
+    /// `"x={}"`: only the format of print_fmt. Escapes are resolved by the
+    /// lexer (`\n \t \r \0 \\ \"`, `\x41`, `\u{e9}`); a raw string `r"..."`
+    /// keeps its backslashes as written
+    StringLiteral(String),
     Variable(String),
     ArrayLiteral(Vec<Expression>),
+    /// `[value; count]`: `count` copies of `value`, with a constant count
+    ArrayRepeat {
+        value: Box<Expression>,
+        count: usize,
+    },
+    TupleLiteral(Vec<Expression>), // (a, b); elements are read back with t[0], t[1]
     Index {
         array: Box<Expression>,
         index: Box<Expression>,
@@ -92,11 +169,21 @@ pub enum Expression {
     Call {
         function: String,
         args: Vec<Expression>,
+        line: usize, // Source line
     },
     Map {
         function: String,
         array: Box<Expression>,
     },
+    // if c { ...; a } else { ...; b }: each arm runs its statements, then its
+    // tail expression (no semicolon) is the value
+    If {
+        condition: Box<Expression>,
+        then_body: Vec<Statement>,
+        then_value: Box<Expression>,
+        else_body: Vec<Statement>,
+        else_value: Box<Expression>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -133,7 +220,18 @@ impl std::fmt::Display for Type {
             Type::Qubit => write!(f, "qubit"),
             Type::Void => write!(f, "void"),
             Type::Tensor(elem) => write!(f, "tensor<{}>", elem),
+            Type::Matrix(elem, Some((rows, cols))) => write!(f, "matrix<{}, {}, {}>", elem, rows, cols),
+            Type::Matrix(elem, None) => write!(f, "matrix<{}>", elem),
             Type::QState => write!(f, "qstate"),
+            Type::Complex => write!(f, "complex"),
+            Type::Int32 => write!(f, "int32"),
+            Type::Int64 => write!(f, "int64"),
+            Type::Float32 => write!(f, "float32"),
+            Type::Tuple(elems) => {
+                let elems: Vec<String> = elems.iter().map(|e| e.to_string()).collect();
+                write!(f, "({})", elems.join(", "))
+            }
+            Type::Float64 => write!(f, "float64"),
         }
     }
 }
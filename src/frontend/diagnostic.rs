@@ -0,0 +1,98 @@
+// Error Reporting: show the offending source line with a caret under the
+// token, in red when stderr is a terminal and NO_COLOR is unset
+use std::io::IsTerminal;
+use std::ops::Range;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+// A source error that knows where it happened: Display is the rendered
+// snippet, `--message-format json` reads the fields
+#[derive(Debug, thiserror::Error)]
+#[error("{rendered}")]
+pub struct SourceError {
+    pub message: String,
+    pub span: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+    rendered: String,
+}
+
+pub fn error(source: &str, span: Range<usize>, message: &str) -> SourceError {
+    let (line, column) = location(source, span.start);
+    SourceError {
+        message: message.to_string(),
+        rendered: render(source, span.clone(), message),
+        span,
+        line,
+        column,
+    }
+}
+
+// An error the type checker has placed in the source. It works on the AST
+// without the source text, so `locate` renders it once the text is at hand
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct Located {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+// Attach a span to an error, unless it already has a more precise one
+pub fn located(error: anyhow::Error, span: Range<usize>) -> anyhow::Error {
+    if error.downcast_ref::<Located>().is_some() {
+        return error;
+    }
+    anyhow::Error::new(Located { message: format!("{:#}", error), span })
+}
+
+// Turn a located error into a SourceError with a snippet and line/column
+pub fn locate(source: &str, error: anyhow::Error) -> anyhow::Error {
+    match error.downcast::<Located>() {
+        Ok(located) => anyhow::Error::new(self::error(source, located.span, &located.message)),
+        Err(error) => error,
+    }
+}
+
+pub fn render(source: &str, span: Range<usize>, message: &str) -> String {
+    render_with_color(source, span, message, use_color())
+}
+
+// 1-based line and column (in characters) of a byte offset
+fn location(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..offset].matches('\n').count() + 1;
+    (line, source[line_start..offset].chars().count() + 1)
+}
+
+fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && std::io::stderr().is_terminal()
+}
+
+fn render_with_color(source: &str, span: Range<usize>, message: &str, color: bool) -> String {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line_text = &source[line_start..line_end];
+    let (line, column) = location(source, start);
+
+    // Keep tabs in the padding so the caret lines up with the source line
+    let padding: String = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = source[start..span.end.clamp(start, line_end)].chars().count().max(1);
+    let carets = "^".repeat(width);
+
+    let gutter = " ".repeat(line.to_string().len());
+    let (red, blue, reset) = if color { (RED, BLUE, RESET) } else { ("", "", "") };
+    format!(
+        "{red}{message}{reset}\n\
+         {gutter}{blue}-->{reset} line {line}, column {column}\n\
+         {gutter} {blue}|{reset}\n\
+         {blue}{line} |{reset} {line_text}\n\
+         {gutter} {blue}|{reset} {padding}{red}{carets}{reset}"
+    )
+}
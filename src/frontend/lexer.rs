@@ -5,7 +5,18 @@ use logos::Logos;
 // Thompson's Construction: Regex → ε-NFA → NFA → DFA (done internally by Logos)
 // DFA Minimization: Logos optimizes the generated DFA
 
+// A lexing error; characters no token matches are skipped by the parser,
+// but a malformed string literal is reported
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LexError {
+    #[default]
+    UnexpectedChar,
+    InvalidEscape(String),
+    UnterminatedString, // No closing quote before the end of the line
+}
+
 #[derive(Logos, Debug, Clone, PartialEq)]
+#[logos(error = LexError)]
 #[logos(skip r"[ \t\n\f]+")]      // Skip whitespace (regex pattern)
 #[logos(skip r"//[^\n]*")]        // Skip single-line comments (regex pattern)
 pub enum Token {
@@ -14,6 +25,8 @@ pub enum Token {
     Fn,
     #[token("let")]
     Let,
+    #[token("mut")]
+    Mut,
     #[token("return")]
     Return,
     #[token("if")]
@@ -26,12 +39,31 @@ pub enum Token {
     In,
     #[token("map")]
     Map,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("const")]
+    Const,
+    #[token("enum")]
+    Enum,
+    #[token("extern")]
+    Extern,
+    // Word forms of `&&`, `||` and `!` (reserved, so never identifiers)
+    #[token("and")]
+    And,
+    #[token("or")]
+    Or,
+    #[token("not")]
+    Not,
 
     // Annotations (domain-specific keywords)
     #[token("@gpu")]
     GpuAnnotation,
     #[token("@quantum")]
     QuantumAnnotation,
+    #[token("@classical")]
+    ClassicalAnnotation,
 
     // Types (keywords for type system)
     #[token("int")]
@@ -46,8 +78,20 @@ pub enum Token {
     Void,
     #[token("tensor")]
     Tensor,
+    #[token("matrix")]
+    Matrix,
     #[token("qstate")]
     QState,
+    #[token("complex")]
+    Complex,
+    #[token("int32")]
+    Int32,
+    #[token("int64")]
+    Int64,
+    #[token("float32")]
+    Float32,
+    #[token("float64")]
+    Float64,
 
     // Literals (constant values)
     #[token("true")]
@@ -59,15 +103,29 @@ pub enum Token {
     #[regex(r"[0-9]+", |lex| lex.slice().parse().ok())]
     IntLiteral(i64),
 
-    // Regex to DFA: Float literal pattern (digits.digits)
-    #[regex(r"[0-9]+\.[0-9]+", |lex| lex.slice().parse().ok())]
+    // Regex to DFA: Float literal pattern (digits.digits, optional exponent: 1.5e-3, 1e-7)
+    #[regex(r"[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?", |lex| lex.slice().parse().ok())]
+    #[regex(r"[0-9]+[eE][+-]?[0-9]+", |lex| lex.slice().parse().ok())]
     FloatLiteral(f64),
 
+    // Regex to DFA: String literal with escapes (one line), or a raw string
+    // r"..." whose backslashes are kept as written. Without the closing
+    // quote, the longest match is the rest of the line: an error
+    #[regex(r#""([^"\\\n]|\\.)*""#, |lex| unescape(&lex.slice()[1..lex.slice().len() - 1]))]
+    #[regex(r#"r"[^"\n]*""#, |lex| lex.slice()[2..lex.slice().len() - 1].to_string())]
+    #[regex(r#""([^"\\\n]|\\.)*"#, |_| Err(LexError::UnterminatedString))]
+    #[regex(r#"r"[^"\n]*"#, |_| Err(LexError::UnterminatedString))]
+    StringLiteral(String),
+
     // Regex to DFA: Identifier pattern (letter/underscore followed by alphanumeric)
     // Maximal Munch: Longest match principle
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
     Identifier(String),
 
+    // Doc comment: `/// text` (outranks the plain `//` comment skip rule)
+    #[regex(r"///[^\n]*", |lex| lex.slice()[3..].trim().to_string(), priority = 10)]
+    DocComment(String),
+
     // Operators
     #[token("+")]
     Plus,
@@ -123,6 +181,8 @@ pub enum Token {
     Semicolon,
     #[token(":")]
     Colon,
+    #[token("::")]
+    ColonColon,
     #[token("->")]
     Arrow,
     #[token("..")]
@@ -134,26 +194,44 @@ impl std::fmt::Display for Token {
         match self {
             Token::Fn => write!(f, "fn"),
             Token::Let => write!(f, "let"),
+            Token::Mut => write!(f, "mut"),
             Token::Return => write!(f, "return"),
             Token::If => write!(f, "if"),
             Token::Else => write!(f, "else"),
             Token::For => write!(f, "for"),
             Token::In => write!(f, "in"),
             Token::Map => write!(f, "map"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::Const => write!(f, "const"),
+            Token::Enum => write!(f, "enum"),
+            Token::Extern => write!(f, "extern"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
             Token::GpuAnnotation => write!(f, "@gpu"),
             Token::QuantumAnnotation => write!(f, "@quantum"),
+            Token::ClassicalAnnotation => write!(f, "@classical"),
             Token::Int => write!(f, "int"),
             Token::Float => write!(f, "float"),
             Token::Bool => write!(f, "bool"),
             Token::Qubit => write!(f, "qubit"),
             Token::Void => write!(f, "void"),
             Token::Tensor => write!(f, "tensor"),
+            Token::Matrix => write!(f, "matrix"),
             Token::QState => write!(f, "qstate"),
+            Token::Complex => write!(f, "complex"),
+            Token::Int32 => write!(f, "int32"),
+            Token::Int64 => write!(f, "int64"),
+            Token::Float32 => write!(f, "float32"),
+            Token::Float64 => write!(f, "float64"),
             Token::True => write!(f, "true"),
             Token::False => write!(f, "false"),
             Token::IntLiteral(n) => write!(f, "{}", n),
             Token::FloatLiteral(n) => write!(f, "{}", n),
             Token::Identifier(s) => write!(f, "{}", s),
+            Token::DocComment(s) => write!(f, "/// {}", s),
+            Token::StringLiteral(s) => write!(f, "{:?}", s),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Star => write!(f, "*"),
@@ -178,9 +256,55 @@ impl std::fmt::Display for Token {
             Token::Comma => write!(f, ","),
             Token::Semicolon => write!(f, ";"),
             Token::Colon => write!(f, ":"),
+            Token::ColonColon => write!(f, "::"),
             Token::Arrow => write!(f, "->"),
             Token::DotDot => write!(f, ".."),
         }
     }
 }
 
+// Escape sequences in a string literal's body: \n \t \r \0 \\ \" plus
+// \xNN (ASCII, up to \x7F) and \u{N..} (any Unicode scalar value)
+fn unescape(body: &str) -> Result<String, LexError> {
+    let invalid = |escape: &str| LexError::InvalidEscape(format!("Invalid escape sequence '\\{}' in string literal", escape));
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            Some('"') => out.push('"'),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 && byte <= 0x7F => out.push(byte as char),
+                    _ => return Err(invalid(&format!("x{}", digits))),
+                }
+            }
+            Some('u') => {
+                let rest = chars.as_str();
+                let code = rest.strip_prefix('{').and_then(|r| r.split_once('}')).map(|(hex, _)| hex);
+                let Some(hex) = code else {
+                    return Err(invalid("u"));
+                };
+                let c = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() <= 6)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| invalid(&format!("u{{{}}}", hex)))?;
+                out.push(c);
+                chars = rest[hex.len() + 2..].chars();
+            }
+            Some(other) => return Err(invalid(&other.to_string())),
+            None => return Err(invalid("")),
+        }
+    }
+    Ok(out)
+}
@@ -1,8 +1,12 @@
 pub mod ast;
+mod diagnostic;
 mod lexer;
 mod parser;
 mod typecheck;
 
+pub use diagnostic::SourceError;
+pub(crate) use diagnostic::locate;
 pub use parser::parse;
-pub use typecheck::typecheck;
+pub use typecheck::{infer_types, typecheck, FunctionTypes};
+pub(crate) use typecheck::MAX_QUBITS;
 
@@ -1,21 +1,41 @@
 use super::ast::*;
-use super::lexer::Token;
+use super::diagnostic;
+use super::lexer::{LexError, Token};
+use crate::middle::ir::Value;
+use crate::middle::{fold_binary, fold_unary, FoldError};
 use anyhow::{anyhow, bail, Result};
 use logos::Logos;
+use std::collections::HashMap;
+use std::ops::Range;
 
 // Syntax Analysis: Recursive Descent Parser (RDP)
 // Top-Down Parsing: Start from root (Program) and expand to leaves
 // LL(1) Grammar: Left-to-right scan, Leftmost derivation, 1 lookahead token
 // Each grammar production rule is implemented as a recursive function
 
+// [value; count] is expanded element by element during lowering; larger
+// arrays are better built with zeros/ones, which stay a single call
+const MAX_REPEAT: usize = 65536;
+
 pub struct Parser {
     tokens: Vec<Token>,  // Token stream from lexer
+    lines: Vec<usize>,   // Source line of each token (1-based)
+    spans: Vec<Range<usize>>, // Byte range of each token (for error carets)
+    docs: HashMap<usize, String>, // `///` doc text keyed by the token it precedes
     pos: usize,          // Current position (lookahead pointer)
+    int_constants: HashMap<String, i64>, // Integer consts seen so far (for array sizes)
 }
 
 impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    fn new(tokens: Vec<Token>, lines: Vec<usize>, spans: Vec<Range<usize>>, docs: HashMap<usize, String>) -> Self {
+        Self {
+            tokens,
+            lines,
+            spans,
+            docs,
+            pos: 0,
+            int_constants: HashMap::new(),
+        }
     }
 
     // Lookahead: Peek at current token without consuming
@@ -23,6 +43,11 @@ impl Parser {
         self.tokens.get(self.pos)
     }
 
+    // Source line of the current token (the last line at EOF)
+    fn line(&self) -> usize {
+        self.lines.get(self.pos).or(self.lines.last()).copied().unwrap_or(1)
+    }
+
     // Consume current token and advance position
     fn advance(&mut self) -> Option<Token> {
         let token = self.tokens.get(self.pos).cloned();
@@ -30,6 +55,12 @@ impl Parser {
         token
     }
 
+    // Step back onto the token just consumed, so an error reported after
+    // `match self.advance()` points at that token
+    fn retreat(&mut self) {
+        self.pos -= 1;
+    }
+
     // Predictive Parsing: Expect specific token based on grammar
     fn expect(&mut self, expected: Token) -> Result<()> {
         let current = self.current().ok_or_else(|| anyhow!("Unexpected EOF"))?;
@@ -40,21 +71,171 @@ impl Parser {
         Ok(())
     }
 
-    // Grammar Rule: Program → Function*
+    // Grammar Rule: Program → (Const | Enum | Extern | Function)*
     // Top-Down Parsing: Start from root production
     fn parse_program(&mut self) -> Result<Program> {
+        let mut constants = Vec::new();
+        let mut enums = Vec::new();
+        let mut externs = Vec::new();
         let mut functions = Vec::new();
         while self.current().is_some() {
-            functions.push(self.parse_function()?);  // Recursive call
+            // Two tokens of lookahead: `@gpu extern fn` vs `@gpu fn`
+            let is_extern = match self.current() {
+                Some(Token::Extern) => true,
+                Some(Token::GpuAnnotation | Token::QuantumAnnotation | Token::ClassicalAnnotation) => {
+                    matches!(self.tokens.get(self.pos + 1), Some(Token::Extern))
+                }
+                _ => false,
+            };
+            match self.current() {
+                Some(Token::Const) => constants.push(self.parse_const()?),
+                Some(Token::Enum) => enums.push(self.parse_enum()?),
+                _ if is_extern => externs.push(self.parse_extern()?),
+                _ => functions.push(self.parse_function()?),  // Recursive call
+            }
+        }
+        Ok(Program { constants, enums, externs, functions })
+    }
+
+    // Grammar Rule: Extern → Domain? "extern" "fn" Identifier "(" Parameters ")" "->" Type ";"
+    fn parse_extern(&mut self) -> Result<Extern> {
+        let domain = match self.current() {
+            Some(Token::GpuAnnotation) => {
+                self.advance();
+                Domain::Gpu
+            }
+            Some(Token::ClassicalAnnotation) => {
+                self.advance();
+                Domain::Classical
+            }
+            // Circuits are built from function bodies; there is none to build from
+            Some(Token::QuantumAnnotation) => bail!("extern functions cannot be @quantum"),
+            _ => Domain::Classical,
+        };
+        self.expect(Token::Extern)?;
+        self.expect(Token::Fn)?;
+        let name = self.try_parse_identifier()?;
+
+        self.expect(Token::LParen)?;
+        let params = self.parse_params()?;
+        self.expect(Token::RParen)?;
+
+        self.expect(Token::Arrow)?;
+        let return_type = self.parse_type()?;
+        self.expect(Token::Semicolon)?;
+
+        Ok(Extern { name, params, return_type, domain })
+    }
+
+    // Grammar Rule: Enum → "enum" Identifier "{" (Member ("," Member)* ","?)? "}"
+    //               Member → Identifier ("=" Expression)?
+    fn parse_enum(&mut self) -> Result<Enum> {
+        self.expect(Token::Enum)?;
+        let name = self.try_parse_identifier()?;
+        self.expect(Token::LBrace)?;
+
+        let mut members = Vec::new();
+        // None once the previous member was i64::MAX: there is no next value
+        let mut next = Some(0);
+        while !matches!(self.current(), Some(Token::RBrace) | None) {
+            let at_member = self.pos;
+            let member = self.try_parse_identifier()?;
+            // Members without `= value` count up from the previous one
+            let value = if matches!(self.current(), Some(Token::Eq)) {
+                self.advance();
+                self.parse_expression()?
+            } else if let Some(n) = next {
+                Expression::IntLiteral(n)
+            } else {
+                // Point the caret at the member that has no value to take
+                self.pos = at_member;
+                bail!("Enum member {}::{} overflows: the previous member is {}", name, member, i64::MAX);
+            };
+            if let Some(n) = self.const_int(&value)? {
+                self.int_constants.insert(format!("{}::{}", name, member), n);
+                next = n.checked_add(1);
+            }
+            members.push(EnumMember { name: member, value });
+
+            if !matches!(self.current(), Some(Token::Comma)) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(Token::RBrace)?;
+
+        Ok(Enum { name, members })
+    }
+
+    // Enum member reference: `Name::Member` (plain names pass through)
+    fn qualified_name(&mut self, name: String) -> Result<String> {
+        if !matches!(self.current(), Some(Token::ColonColon)) {
+            return Ok(name);
+        }
+        self.advance();
+        let member = self.try_parse_identifier()?;
+        Ok(format!("{}::{}", name, member))
+    }
+
+    // Grammar Rule: Const → "const" Identifier ":" Type "=" Expression ";"
+    fn parse_const(&mut self) -> Result<Const> {
+        self.expect(Token::Const)?;
+        let name = self.try_parse_identifier()?;
+        self.expect(Token::Colon)?;
+        let ty = self.parse_type()?;
+        self.expect(Token::Eq)?;
+        let at_value = self.pos;
+        let value = self.parse_expression()?;
+
+        // Remember integer constants so later array types can use them as sizes
+        match self.const_int(&value) {
+            Ok(Some(n)) => {
+                self.int_constants.insert(name.clone(), n);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // Point the caret at the initializer that has no value
+                self.pos = at_value;
+                return Err(e);
+            }
+        }
+        self.expect(Token::Semicolon)?;
+
+        Ok(Const { name, ty, value })
+    }
+
+    // Constant Folding (parse time): evaluate an integer expression built from
+    // literals and previously declared constants. None when it isn't one; an
+    // error when it is but has no value (overflow, division by zero)
+    fn const_int(&self, expr: &Expression) -> Result<Option<i64>> {
+        let folded = match expr {
+            Expression::IntLiteral(n) => return Ok(Some(*n)),
+            Expression::Variable(name) => return Ok(self.int_constants.get(name).copied()),
+            Expression::Unary { op, operand } => match self.const_int(operand)? {
+                Some(n) => fold_unary((*op).into(), &Value::Int(n)),
+                None => return Ok(None),
+            },
+            Expression::Binary { op, left, right } => match (self.const_int(left)?, self.const_int(right)?) {
+                (Some(l), Some(r)) => fold_binary((*op).into(), &Value::Int(l), &Value::Int(r)),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        match folded {
+            Ok(Value::Int(n)) => Ok(Some(n)),
+            Ok(_) | Err(FoldError::NotConstant) => Ok(None),
+            Err(e) => bail!("{}", e),
         }
-        Ok(Program { functions })
     }
 
     // Grammar Rule: Function → Domain? "fn" Identifier "(" Parameters ")" "->" Type Block
     // Recursive Descent: Each grammar rule is a function
     fn parse_function(&mut self) -> Result<Function> {
+        // Doc comment directly before the annotation / `fn`
+        let doc = self.docs.remove(&self.pos);
+
         // Parse optional domain annotation (Domain?)
-        // First Set: {@gpu, @quantum, fn}
+        // First Set: {@gpu, @quantum, @classical, fn}
         let domain = match self.current() {
             Some(Token::GpuAnnotation) => {
                 self.advance();
@@ -64,14 +245,23 @@ impl Parser {
                 self.advance();
                 Domain::Quantum
             }
+            Some(Token::ClassicalAnnotation) => {
+                self.advance();
+                Domain::Classical
+            }
             _ => Domain::Classical,
         };
+        let (annotations, device) = self.parse_annotation_args(&domain)?;
 
         self.expect(Token::Fn)?;
 
+        let span = self.spans.get(self.pos).cloned().unwrap_or_default();
         let name = match self.advance() {
             Some(Token::Identifier(s)) => s,
-            _ => bail!("Expected function name"),
+            _ => {
+                self.retreat();
+                bail!("Expected function name")
+            }
         };
 
         self.expect(Token::LParen)?;
@@ -91,9 +281,80 @@ impl Parser {
             return_type,
             body,
             domain,  // NEW: include domain
+            annotations,
+            device,
+            doc,
+            span,
         })
     }
 
+    // Grammar Rule: AnnotationArgs → ( "(" AnnotArg ("," AnnotArg)* ")" )?
+    //               AnnotArg → IDENT "=" INT | "backend" "=" STRING
+    // The integer arguments, and the device named by @quantum(backend="...")
+    fn parse_annotation_args(&mut self, domain: &Domain) -> Result<(HashMap<String, i64>, Option<String>)> {
+        let mut args = HashMap::new();
+        let mut device = None;
+        if !matches!(self.current(), Some(Token::LParen)) {
+            return Ok((args, device));
+        }
+        self.advance();
+
+        // Keys each domain's backend understands
+        let (annotation, allowed): (&str, &[&str]) = match domain {
+            Domain::Gpu => ("@gpu", &["workgroup"]),
+            Domain::Quantum => ("@quantum", &["shots", "seed", "backend"]),
+            Domain::Classical => ("@classical", &[]),
+        };
+
+        loop {
+            let key = self.try_parse_identifier()?;
+            if allowed.is_empty() {
+                bail!("Unknown argument '{}' for {}; it takes no arguments", key, annotation);
+            }
+            if !allowed.contains(&key.as_str()) {
+                bail!("Unknown argument '{}' for {}; allowed: {}", key, annotation, allowed.join(", "));
+            }
+            self.expect(Token::Eq)?;
+            if key == "backend" {
+                let name = match self.advance() {
+                    Some(Token::StringLiteral(name)) => name,
+                    _ => {
+                        self.retreat();
+                        bail!("Expected a string value for annotation argument 'backend', e.g. backend=\"ibm_brisbane\"")
+                    }
+                };
+                if name.trim().is_empty() {
+                    bail!("Annotation argument 'backend' must name a device");
+                }
+                if device.replace(name).is_some() {
+                    bail!("Duplicate annotation argument 'backend'");
+                }
+            } else {
+                let value = match self.advance() {
+                    Some(Token::IntLiteral(n)) => n,
+                    _ => {
+                        self.retreat();
+                        bail!("Expected integer value for annotation argument '{}'", key)
+                    }
+                };
+                if value <= 0 && key != "seed" {
+                    bail!("Annotation argument '{}' must be positive", key);
+                }
+                if args.insert(key.clone(), value).is_some() {
+                    bail!("Duplicate annotation argument '{}'", key);
+                }
+            }
+
+            if !matches!(self.current(), Some(Token::Comma)) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(Token::RParen)?;
+
+        Ok((args, device))
+    }
+
     fn parse_params(&mut self) -> Result<Vec<Param>> {
         let mut params = Vec::new();
 
@@ -104,7 +365,10 @@ impl Parser {
         loop {
             let name = match self.advance() {
                 Some(Token::Identifier(s)) => s,
-                _ => bail!("Expected parameter name"),
+                _ => {
+                    self.retreat();
+                    bail!("Expected parameter name")
+                }
             };
 
             self.expect(Token::Colon)?;
@@ -129,6 +393,11 @@ impl Parser {
             Some(Token::Qubit) => Ok(Type::Qubit),
             Some(Token::Void) => Ok(Type::Void),
             Some(Token::QState) => Ok(Type::QState),  // NEW: qstate type
+            Some(Token::Complex) => Ok(Type::Complex),
+            Some(Token::Int32) => Ok(Type::Int32),
+            Some(Token::Int64) => Ok(Type::Int64),
+            Some(Token::Float32) => Ok(Type::Float32),
+            Some(Token::Float64) => Ok(Type::Float64),
             Some(Token::Tensor) => {
                 // NEW: tensor<T> type
                 self.expect(Token::Lt)?;
@@ -136,21 +405,50 @@ impl Parser {
                 self.expect(Token::Gt)?;
                 Ok(Type::Tensor(Box::new(elem_type)))
             }
+            Some(Token::Matrix) => {
+                // matrix<T> or matrix<T, rows, cols>
+                self.expect(Token::Lt)?;
+                let elem_type = self.parse_type()?;
+                let dims = if matches!(self.current(), Some(Token::Comma)) {
+                    self.advance();
+                    let rows = self.parse_size()?;
+                    self.expect(Token::Comma)?;
+                    let cols = self.parse_size()?;
+                    Some((rows, cols))
+                } else {
+                    None
+                };
+                self.expect(Token::Gt)?;
+                Ok(Type::Matrix(Box::new(elem_type), dims))
+            }
             Some(Token::LBracket) => {
                 let elem_type = self.parse_type()?;
                 let size = if matches!(self.current(), Some(Token::Semicolon)) {
                     self.advance();
-                    match self.advance() {
-                        Some(Token::IntLiteral(n)) => Some(n as usize),
-                        _ => bail!("Expected array size"),
-                    }
+                    Some(self.parse_size()?)
                 } else {
                     None
                 };
                 self.expect(Token::RBracket)?;
                 Ok(Type::Array(Box::new(elem_type), size))
             }
-            _ => bail!("Expected type"),
+            Some(Token::LParen) => {
+                // Tuple type: (T1, T2, ...), at least two elements
+                let mut elems = vec![self.parse_type()?];
+                while matches!(self.current(), Some(Token::Comma)) {
+                    self.advance();
+                    elems.push(self.parse_type()?);
+                }
+                self.expect(Token::RParen)?;
+                if elems.len() < 2 {
+                    bail!("A tuple type needs at least two elements");
+                }
+                Ok(Type::Tuple(elems))
+            }
+            _ => {
+                self.retreat();
+                bail!("Expected type")
+            }
         }
     }
 
@@ -165,11 +463,28 @@ impl Parser {
     }
 
     fn parse_statement(&mut self) -> Result<Statement> {
+        let start = self.spans.get(self.pos).map_or(0, |s| s.start);
+        let kind = self.parse_statement_kind()?;
+        let end = self.spans.get(self.pos.saturating_sub(1)).map_or(start, |s| s.end);
+        Ok(Statement { kind, span: start..end })
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<StatementKind> {
         match self.current() {
             Some(Token::Let) => self.parse_let(),
             Some(Token::Return) => self.parse_return(),
             Some(Token::For) => self.parse_for(),
             Some(Token::If) => self.parse_if(),
+            Some(Token::Break) => {
+                self.advance();
+                self.expect(Token::Semicolon)?;
+                Ok(StatementKind::Break)
+            }
+            Some(Token::Continue) => {
+                self.advance();
+                self.expect(Token::Semicolon)?;
+                Ok(StatementKind::Continue)
+            }
             Some(Token::Identifier(_)) => {
                 // Could be assignment or expression statement
                 let checkpoint = self.pos;
@@ -181,12 +496,37 @@ impl Parser {
                 self.pos = checkpoint;
                 let expr = self.parse_expression()?;
                 self.expect(Token::Semicolon)?;
-                Ok(Statement::Expression(expr))
+                Ok(StatementKind::Expression(expr))
             }
             _ => {
                 let expr = self.parse_expression()?;
                 self.expect(Token::Semicolon)?;
-                Ok(Statement::Expression(expr))
+                Ok(StatementKind::Expression(expr))
+            }
+        }
+    }
+
+    // Array or matrix dimension: an integer literal or integer constant
+    fn parse_size(&mut self) -> Result<usize> {
+        match self.advance() {
+            Some(Token::IntLiteral(n)) => Ok(n as usize),
+            Some(Token::Identifier(name)) => {
+                let name = self.qualified_name(name)?;
+                match self.int_constants.get(&name) {
+                    Some(&n) if n >= 0 => Ok(n as usize),
+                    Some(_) => {
+                        self.retreat();
+                        bail!("Array size constant {} is negative", name)
+                    }
+                    None => {
+                        self.retreat();
+                        bail!("Array size {} is not an integer constant", name)
+                    }
+                }
+            }
+            _ => {
+                self.retreat();
+                bail!("Expected array size")
             }
         }
     }
@@ -194,12 +534,19 @@ impl Parser {
     fn try_parse_identifier(&mut self) -> Result<String> {
         match self.advance() {
             Some(Token::Identifier(s)) => Ok(s),
-            _ => bail!("Expected identifier"),
+            _ => {
+                self.retreat();
+                bail!("Expected identifier")
+            }
         }
     }
 
-    fn parse_let(&mut self) -> Result<Statement> {
+    fn parse_let(&mut self) -> Result<StatementKind> {
         self.expect(Token::Let)?;
+        let mutable = matches!(self.current(), Some(Token::Mut));
+        if mutable {
+            self.advance();
+        }
         let name = self.try_parse_identifier()?;
 
         let ty = if matches!(self.current(), Some(Token::Colon)) {
@@ -213,46 +560,46 @@ impl Parser {
         let value = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
 
-        Ok(Statement::Let { name, ty, value })
+        Ok(StatementKind::Let { name, mutable, ty, value })
     }
 
-    fn parse_assignment(&mut self, name: String) -> Result<Statement> {
-        let index = if matches!(self.current(), Some(Token::LBracket)) {
+    fn parse_assignment(&mut self, name: String) -> Result<StatementKind> {
+        let line = self.line();
+        let mut indices = Vec::new();
+        while matches!(self.current(), Some(Token::LBracket)) {
             self.advance();
-            let idx = self.parse_expression()?;
+            indices.push(self.parse_expression()?);
             self.expect(Token::RBracket)?;
-            Some(Box::new(idx))
-        } else {
-            None
-        };
+        }
 
         self.expect(Token::Eq)?;
         let value = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
 
-        Ok(Statement::Assign {
+        Ok(StatementKind::Assign {
             target: name,
-            index,
+            indices,
             value,
+            line,
         })
     }
 
-    fn parse_return(&mut self) -> Result<Statement> {
+    fn parse_return(&mut self) -> Result<StatementKind> {
         self.expect(Token::Return)?;
 
         // Check if this is a void return (return;)
         if matches!(self.current(), Some(Token::Semicolon)) {
             self.advance();
             // Return a unit/void value - we'll use IntLiteral(0) as placeholder
-            return Ok(Statement::Return(Expression::IntLiteral(0)));
+            return Ok(StatementKind::Return(Expression::IntLiteral(0)));
         }
 
         let expr = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
-        Ok(Statement::Return(expr))
+        Ok(StatementKind::Return(expr))
     }
 
-    fn parse_for(&mut self) -> Result<Statement> {
+    fn parse_for(&mut self) -> Result<StatementKind> {
         self.expect(Token::For)?;
         let var = self.try_parse_identifier()?;
         self.expect(Token::In)?;
@@ -263,7 +610,7 @@ impl Parser {
         let body = self.parse_statements()?;
         self.expect(Token::RBrace)?;
 
-        Ok(Statement::For {
+        Ok(StatementKind::For {
             var,
             start,
             end,
@@ -271,7 +618,7 @@ impl Parser {
         })
     }
 
-    fn parse_if(&mut self) -> Result<Statement> {
+    fn parse_if(&mut self) -> Result<StatementKind> {
         self.expect(Token::If)?;
         let condition = self.parse_expression()?;
         self.expect(Token::LBrace)?;
@@ -288,20 +635,68 @@ impl Parser {
             None
         };
 
-        Ok(Statement::If {
+        Ok(StatementKind::If {
             condition,
             then_body,
             else_body,
         })
     }
 
+    // Grammar Rule: IfExpr → "if" Expression BlockValue "else" (IfExpr | BlockValue)
+    // Called with `if` already consumed; `else if` chains nest in the else arm
+    fn parse_if_expression(&mut self) -> Result<Expression> {
+        let condition = self.parse_expression()?;
+        let (then_body, then_value) = self.parse_block_value()?;
+        if !matches!(self.current(), Some(Token::Else)) {
+            bail!("`if` used as a value needs an `else` branch");
+        }
+        self.advance();
+        let (else_body, else_value) = if matches!(self.current(), Some(Token::If)) {
+            self.advance();
+            (Vec::new(), self.parse_if_expression()?)
+        } else {
+            self.parse_block_value()?
+        };
+        Ok(Expression::If {
+            condition: Box::new(condition),
+            then_body,
+            then_value: Box::new(then_value),
+            else_body,
+            else_value: Box::new(else_value),
+        })
+    }
+
+    // Grammar Rule: BlockValue → "{" Statement* Expression "}"
+    // The tail is an expression directly followed by `}`; anything else is
+    // parsed again as a statement
+    fn parse_block_value(&mut self) -> Result<(Vec<Statement>, Expression)> {
+        self.expect(Token::LBrace)?;
+        let mut body = Vec::new();
+        loop {
+            if matches!(self.current(), Some(Token::RBrace) | None) {
+                bail!("Each branch of an `if` used as a value must end with an expression");
+            }
+            let checkpoint = self.pos;
+            if !matches!(self.current(), Some(Token::Let | Token::Return | Token::For | Token::Break | Token::Continue)) {
+                if let Ok(value) = self.parse_expression() {
+                    if matches!(self.current(), Some(Token::RBrace)) {
+                        self.advance();
+                        return Ok((body, value));
+                    }
+                }
+                self.pos = checkpoint;
+            }
+            body.push(self.parse_statement()?);
+        }
+    }
+
     // Grammar Rule: Expression → LogicalOr
     // Precedence Climbing: Parse expressions by precedence levels
     fn parse_expression(&mut self) -> Result<Expression> {
         self.parse_or()
     }
 
-    // Grammar Rule: LogicalOr → LogicalAnd ("||" LogicalAnd)*
+    // Grammar Rule: LogicalOr → LogicalAnd (("||" | "or") LogicalAnd)*
     // Left Recursion Elimination: Transformed to iteration
     // Original (left-recursive): LogicalOr → LogicalOr "||" LogicalAnd
     // Transformed: LogicalOr → LogicalAnd ("||" LogicalAnd)*
@@ -309,7 +704,7 @@ impl Parser {
         let mut left = self.parse_and()?;
 
         // Iteration instead of left recursion
-        while matches!(self.current(), Some(Token::OrOr)) {
+        while matches!(self.current(), Some(Token::OrOr | Token::Or)) {
             self.advance();
             let right = self.parse_and()?;
             left = Expression::Binary {
@@ -325,7 +720,7 @@ impl Parser {
     fn parse_and(&mut self) -> Result<Expression> {
         let mut left = self.parse_equality()?;
 
-        while matches!(self.current(), Some(Token::AndAnd)) {
+        while matches!(self.current(), Some(Token::AndAnd | Token::And)) {
             self.advance();
             let right = self.parse_equality()?;
             left = Expression::Binary {
@@ -435,7 +830,7 @@ impl Parser {
                     operand: Box::new(operand),
                 })
             }
-            Some(Token::Bang) => {
+            Some(Token::Bang | Token::Not) => {
                 self.advance();
                 let operand = self.parse_unary()?;
                 Ok(Expression::Unary {
@@ -464,15 +859,24 @@ impl Parser {
                 Some(Token::LParen) => {
                     // Function call
                     if let Expression::Variable(name) = expr {
+                        let line = self.line();
                         self.advance();
-                        let args = self.parse_args()?;
+                        let mut args = self.parse_args()?;
                         self.expect(Token::RParen)?;
+                        // zeros/ones/range/qalloc sizes must be known at compile time
+                        if matches!(name.as_str(), "zeros" | "ones" | "range" | "qalloc") && args.len() == 1 {
+                            if let Some(n) = self.const_int(&args[0])? {
+                                args[0] = Expression::IntLiteral(n);
+                            }
+                        }
                         expr = Expression::Call {
                             function: name,
                             args,
+                            line,
                         };
                     } else {
-                        break;
+                        // Only named functions can be called: no `f()()` or `(a + b)()`
+                        bail!("Callee is not a function name: only `name(args)` calls are supported");
                     }
                 }
                 _ => break,
@@ -488,17 +892,56 @@ impl Parser {
             Some(Token::FloatLiteral(f)) => Ok(Expression::FloatLiteral(f)),
             Some(Token::True) => Ok(Expression::BoolLiteral(true)),
             Some(Token::False) => Ok(Expression::BoolLiteral(false)),
-            Some(Token::Identifier(name)) => Ok(Expression::Variable(name)),
+            Some(Token::StringLiteral(s)) => Ok(Expression::StringLiteral(s)),
+            Some(Token::Identifier(name)) => Ok(Expression::Variable(self.qualified_name(name)?)),
             Some(Token::LBracket) => {
-                let elements = self.parse_array_elements()?;
+                let mut elements = self.parse_array_elements()?;
+                // Array repeat: [value; count], count a constant integer
+                if elements.len() == 1 && matches!(self.current(), Some(Token::Semicolon)) {
+                    self.advance();
+                    let count = self.parse_expression()?;
+                    let count = match self.const_int(&count)? {
+                        Some(n) if n > MAX_REPEAT as i64 => {
+                            bail!("Array repeat count {} is too large (at most {}); use zeros or ones for large arrays", n, MAX_REPEAT)
+                        }
+                        Some(n) if n >= 0 => n as usize,
+                        _ => bail!("Array repeat count must be a non-negative integer constant"),
+                    };
+                    self.expect(Token::RBracket)?;
+                    let value = Box::new(elements.remove(0));
+                    return Ok(Expression::ArrayRepeat { value, count });
+                }
                 self.expect(Token::RBracket)?;
                 Ok(Expression::ArrayLiteral(elements))
             }
             Some(Token::LParen) => {
+                // Parenthesized expression, or a tuple when a comma follows
                 let expr = self.parse_expression()?;
+                if matches!(self.current(), Some(Token::Comma)) {
+                    let mut elements = vec![expr];
+                    while matches!(self.current(), Some(Token::Comma)) {
+                        self.advance();
+                        elements.push(self.parse_expression()?);
+                    }
+                    self.expect(Token::RParen)?;
+                    return Ok(Expression::TupleLiteral(elements));
+                }
                 self.expect(Token::RParen)?;
                 Ok(expr)
             }
+            Some(Token::Complex) => {
+                // complex(re, im): the type keyword doubles as its constructor
+                let line = self.line();
+                self.expect(Token::LParen)?;
+                let args = self.parse_args()?;
+                self.expect(Token::RParen)?;
+                Ok(Expression::Call {
+                    function: "complex".to_string(),
+                    args,
+                    line,
+                })
+            }
+            Some(Token::If) => self.parse_if_expression(),
             Some(Token::Map) => {
                 self.expect(Token::LParen)?;
                 let function = self.try_parse_identifier()?;
@@ -510,7 +953,14 @@ impl Parser {
                     array: Box::new(array),
                 })
             }
-            _ => bail!("Unexpected token in expression"),
+            Some(token) => {
+                self.retreat();
+                bail!("Unexpected token '{}' in expression", token)
+            }
+            None => {
+                self.retreat();
+                bail!("Unexpected end of input in expression")
+            }
         }
     }
 
@@ -551,13 +1001,52 @@ impl Parser {
     }
 }
 
+/// Parse source text into an AST.
+///
+/// `and`, `or` and `not` are the same operators as `&&`, `||` and `!`.
 pub fn parse(source: &str) -> Result<Program> {
-    let tokens: Vec<Token> = Token::lexer(source)
-        .filter_map(|result| result.ok())
-        .collect();
+    // Keep each token's line so later stages can point back at the source
+    let mut tokens = Vec::new();
+    let mut lines = Vec::new();
+    let mut spans = Vec::new();
+    let mut docs: HashMap<usize, String> = HashMap::new();
+    for (result, span) in Token::lexer(source).spanned() {
+        // Doc comments stay out of the token stream: consecutive `///` lines
+        // are joined and attached to the next token (ignored unless a function)
+        if let Ok(Token::DocComment(text)) = result {
+            docs.entry(tokens.len())
+                .and_modify(|doc| {
+                    doc.push('\n');
+                    doc.push_str(&text);
+                })
+                .or_insert(text);
+            continue;
+        }
+        match result {
+            Err(LexError::InvalidEscape(message)) => {
+                return Err(anyhow::Error::new(diagnostic::error(source, span, &message)));
+            }
+            Err(LexError::UnterminatedString) => {
+                return Err(anyhow::Error::new(diagnostic::error(source, span, "unterminated string literal")));
+            }
+            _ => {}
+        }
+        if let Ok(token) = result {
+            tokens.push(token);
+            lines.push(source[..span.start].matches('\n').count() + 1);
+            spans.push(span);
+        }
+    }
 
-    let mut parser = Parser::new(tokens);
-    parser.parse_program()
+    let mut parser = Parser::new(tokens, lines, spans, docs);
+    parser.parse_program().map_err(|e| {
+        // Point at the token the parser stopped on (just past the last token at EOF)
+        let span = parser.spans.get(parser.pos).cloned().unwrap_or_else(|| {
+            let end = parser.spans.last().map_or(0, |s| s.end);
+            end..end
+        });
+        anyhow::Error::new(diagnostic::error(source, span, &format!("{:#}", e)))
+    })
 }
 
 
@@ -1,24 +1,66 @@
 use super::ast::*;
+use super::diagnostic::located;
 use anyhow::{bail, Result};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct TypeChecker {
     variables: HashMap<String, Type>,
+    mutable: HashSet<String>,  // Variables bound with `let mut`
+    buffers: HashSet<String>,  // Unsized array parameters of a @gpu function (storage buffers)
     functions: HashMap<String, (Vec<Type>, Type, Domain)>, // (param_types, return_type, domain)
+    constants: HashMap<String, Type>, // Module-level `const` declarations
     current_domain: Domain, // Track current function's domain
+    current_return: Type,   // Declared return type of the current function
+    loop_depth: usize,      // Nesting depth of enclosing loops (for break/continue)
+    // Inferred `let` types of the current function; shared with the copies
+    // that check `if` expression arms so their bindings are recorded too
+    lets: Rc<RefCell<Vec<(String, Type)>>>,
+    typed: Vec<FunctionTypes>, // Resolved signature and `let` types per checked function
 }
 
+/// A function's resolved types, as reported by [`infer_types`]
+#[derive(Debug, Clone)]
+pub struct FunctionTypes {
+    pub name: String,
+    pub domain: Domain,
+    pub params: Vec<(String, Type)>,
+    pub return_type: Type,
+    pub lets: Vec<(String, Type)>, // In the order they were checked
+}
+
+// Predefined float constants for angles, e.g. ry(0, PI / 2)
+const BUILTIN_CONSTANTS: [&str; 3] = ["PI", "TAU", "E"];
+
+// Register Size Limit: qalloc hands out at most this many qubits per
+// function; far beyond any device or simulator, but keeps `qalloc(1e9)` from
+// exhausting memory while the register is built
+pub(crate) const MAX_QUBITS: i64 = 4096;
+
 impl TypeChecker {
     fn new() -> Self {
         let mut checker = Self {
             variables: HashMap::new(),
+            mutable: HashSet::new(),
+            buffers: HashSet::new(),
             functions: HashMap::new(),
+            constants: HashMap::new(),
             current_domain: Domain::Classical,
+            current_return: Type::Void,
+            loop_depth: 0,
+            lets: Rc::new(RefCell::new(Vec::new())),
+            typed: Vec::new(),
         };
 
         // Register built-in quantum functions
         checker.register_builtin_functions();
 
+        for name in BUILTIN_CONSTANTS {
+            checker.constants.insert(name.to_string(), Type::Float);
+        }
+
         checker
     }
 
@@ -28,86 +70,288 @@ impl TypeChecker {
             "print".to_string(),
             (vec![Type::Int], Type::Void, Domain::Classical),
         );
+        self.functions.insert(
+            "print_int".to_string(),
+            (vec![Type::Int], Type::Void, Domain::Classical),
+        );
         self.functions.insert(
             "print_float".to_string(),
             (vec![Type::Float], Type::Void, Domain::Classical),
         );
+        self.functions.insert(
+            "print_bool".to_string(),
+            (vec![Type::Bool], Type::Void, Domain::Classical),
+        );
         self.functions.insert(
             "print_array".to_string(),
             (vec![Type::Array(Box::new(Type::Float), None)], Type::Void, Domain::Classical),
         );
+        // Runtime check: assert(cond) stops the program when cond is false
+        self.functions.insert(
+            "assert".to_string(),
+            (vec![Type::Bool], Type::Void, Domain::Classical),
+        );
+
+        // Complex numbers: complex(re, im)
+        self.functions.insert(
+            "complex".to_string(),
+            (vec![Type::Float, Type::Float], Type::Complex, Domain::Classical),
+        );
 
         // Quantum gates (single qubit)
         self.functions.insert(
             "h".to_string(),
-            (vec![Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "x".to_string(),
-            (vec![Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "y".to_string(),
-            (vec![Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "z".to_string(),
-            (vec![Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "ry".to_string(),
-            (vec![Type::Int, Type::Float], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit, Type::Float], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "rz".to_string(),
-            (vec![Type::Int, Type::Float], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit, Type::Float], Type::Int, Domain::Quantum),
+        );
+        // Universal gate U(theta, phi, lambda) and phase gate P(lambda):
+        // angles first, target qubit last (Qiskit argument order)
+        self.functions.insert(
+            "u".to_string(),
+            (vec![Type::Float, Type::Float, Type::Float, Type::Qubit], Type::Int, Domain::Quantum),
+        );
+        self.functions.insert(
+            "p".to_string(),
+            (vec![Type::Float, Type::Qubit], Type::Int, Domain::Quantum),
         );
 
         // Quantum gates (two qubit)
         self.functions.insert(
             "cx".to_string(),
-            (vec![Type::Int, Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit, Type::Qubit], Type::Int, Domain::Quantum),
         );
         self.functions.insert(
             "cnot".to_string(),
-            (vec![Type::Int, Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit, Type::Qubit], Type::Int, Domain::Quantum),
         );
 
         // Measurement
         self.functions.insert(
             "measure".to_string(),
-            (vec![Type::Int], Type::Int, Domain::Quantum),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
+        );
+        // Every qubit at once: the most common outcome, one bit per qubit
+        self.functions.insert(
+            "measure_all".to_string(),
+            (vec![], Type::Array(Box::new(Type::Int), None), Domain::Quantum),
+        );
+
+        // State preparation: initialize the register with normalized amplitudes
+        self.functions.insert(
+            "amplitude_encode".to_string(),
+            (vec![Type::Array(Box::new(Type::Float), None)], Type::QState, Domain::Quantum),
+        );
+
+        // Circuit control: reset a qubit to |0>, barrier across all qubits
+        self.functions.insert(
+            "reset".to_string(),
+            (vec![Type::Qubit], Type::Int, Domain::Quantum),
+        );
+        self.functions.insert(
+            "barrier".to_string(),
+            (vec![], Type::Void, Domain::Quantum),
         );
     }
 
     fn check_program(&mut self, program: &Program) -> Result<()> {
-        // First pass: collect function signatures with domains
+        // Enum members are integer constants named `Enum::Member`; they come
+        // first so `const` initializers can use them
+        let mut enum_names = HashSet::new();
+        for e in &program.enums {
+            if !enum_names.insert(&e.name) {
+                bail!("Enum {} is defined more than once", e.name);
+            }
+            self.check_enum(e)?;
+        }
+
+        // Constants are visible in every function, in declaration order
+        for constant in &program.constants {
+            self.check_const(constant)?;
+        }
+
+        // First pass: collect function signatures with domains (extern
+        // functions only have a signature)
+        for func in &program.externs {
+            let param_types = func.params.iter().map(|p| base_type(&p.ty)).collect();
+            self.functions.insert(
+                func.name.clone(),
+                (param_types, base_type(&func.return_type), func.domain.clone()),
+            );
+        }
         for func in &program.functions {
-            let param_types = func.params.iter().map(|p| p.ty.clone()).collect();
+            if program.externs.iter().any(|e| e.name == func.name) {
+                let error = anyhow::anyhow!("Function {} is declared extern and also defined", func.name);
+                return Err(located(error, func.span.clone()));
+            }
+            let param_types = func.params.iter().map(|p| base_type(&p.ty)).collect();
             self.functions.insert(
                 func.name.clone(),
-                (param_types, func.return_type.clone(), func.domain.clone()),
+                (param_types, base_type(&func.return_type), func.domain.clone()),
             );
         }
 
-        // Second pass: type check function bodies
+        // Second pass: reject call-graph cycles
+        self.check_recursion(program)?;
+
+        // Lint: circuits that rely on the implicit final measurement. Only
+        // needs the call graph, so it warns even if a body fails to check
+        warn_unmeasured_circuits(program);
+
+        // Third pass: type check function bodies
         for func in &program.functions {
-            self.check_function(func)?;
+            self.check_function(func).map_err(|e| located(e, func.span.clone()))?;
+            self.typed.push(FunctionTypes {
+                name: func.name.clone(),
+                domain: func.domain.clone(),
+                params: func.params.iter().map(|p| (p.name.clone(), base_type(&p.ty))).collect(),
+                return_type: base_type(&func.return_type),
+                lets: self.lets.take(),
+            });
         }
 
         Ok(())
     }
 
+    // Recursion: Functions are unrolled and inlined with no call stack, so a
+    // cycle in the call graph (f -> g -> f) cannot be compiled
+    fn check_recursion(&self, program: &Program) -> Result<()> {
+        let mut calls: HashMap<&str, Vec<&str>> = HashMap::new();
+        for func in &program.functions {
+            let mut callees = Vec::new();
+            for stmt in &func.body {
+                collect_calls_in_statement(stmt, &mut callees);
+            }
+            // Only user-defined functions take part in the call graph
+            callees.retain(|name| program.functions.iter().any(|f| f.name == *name));
+            calls.insert(func.name.as_str(), callees);
+        }
+
+        // Depth-first search keeping the current call path; reaching a
+        // function already on the path closes a cycle
+        fn visit<'a>(
+            name: &'a str,
+            calls: &HashMap<&'a str, Vec<&'a str>>,
+            path: &mut Vec<&'a str>,
+            done: &mut Vec<&'a str>,
+        ) -> Result<()> {
+            if let Some(start) = path.iter().position(|f| *f == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name);
+                bail!("recursion is not supported: {}", cycle.join(" -> "));
+            }
+            if done.contains(&name) {
+                return Ok(());
+            }
+            path.push(name);
+            for callee in &calls[name] {
+                visit(callee, calls, path, done)?;
+            }
+            path.pop();
+            done.push(name);
+            Ok(())
+        }
+
+        let mut done = Vec::new();
+        for func in &program.functions {
+            visit(func.name.as_str(), &calls, &mut Vec::new(), &mut done).map_err(|e| located(e, func.span.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn check_const(&mut self, constant: &Const) -> Result<()> {
+        if BUILTIN_CONSTANTS.contains(&constant.name.as_str()) {
+            bail!("Constant {} is predefined", constant.name);
+        }
+        if self.constants.contains_key(&constant.name) {
+            bail!("Constant {} is defined more than once", constant.name);
+        }
+        if !self.is_constant_expression(&constant.value) {
+            bail!(
+                "Constant {} must be initialized with a compile-time constant expression",
+                constant.name
+            );
+        }
+        let value_type = self.infer_expression(&constant.value)?;
+        if !self.types_compatible(&constant.ty, &value_type) {
+            bail!(
+                "Type mismatch in constant {}: expected {}, got {}",
+                constant.name,
+                constant.ty,
+                value_type
+            );
+        }
+        self.constants.insert(constant.name.clone(), base_type(&constant.ty));
+        Ok(())
+    }
+
+    fn check_enum(&mut self, e: &Enum) -> Result<()> {
+        for member in &e.members {
+            let name = format!("{}::{}", e.name, member.name);
+            if self.constants.contains_key(&name) {
+                bail!("Enum member {} is defined more than once", name);
+            }
+            if !self.is_constant_expression(&member.value) || self.infer_expression(&member.value)? != Type::Int {
+                bail!("Enum member {} must be an integer constant", name);
+            }
+            self.constants.insert(name, Type::Int);
+        }
+        Ok(())
+    }
+
+    // Literals, earlier constants, and operators over them
+    fn is_constant_expression(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::IntLiteral(_) | Expression::FloatLiteral(_) | Expression::BoolLiteral(_) => true,
+            Expression::Variable(name) => self.constants.contains_key(name),
+            Expression::Unary { operand, .. } => self.is_constant_expression(operand),
+            Expression::Binary { left, right, .. } => {
+                self.is_constant_expression(left) && self.is_constant_expression(right)
+            }
+            _ => false,
+        }
+    }
+
     fn check_function(&mut self, func: &Function) -> Result<()> {
         // Clear variables for new function scope
         self.variables.clear();
+        self.mutable.clear();
+        self.buffers.clear();
 
-        // Set current domain
+        // Set current domain and return type
         self.current_domain = func.domain.clone();
+        self.current_return = base_type(&func.return_type);
 
-        // Add parameters to scope
+        // Add parameters to scope (read-only, except that a @gpu function
+        // may write the elements of its storage buffers, `xs: [float]`)
         for param in &func.params {
-            self.variables.insert(param.name.clone(), param.ty.clone());
+            self.variables.insert(param.name.clone(), base_type(&param.ty));
+            if func.domain == Domain::Gpu && matches!(param.ty, Type::Array(_, None)) {
+                self.buffers.insert(param.name.clone());
+            }
+        }
+
+        // Nothing to fall back on: an empty body can't produce a value
+        if func.body.is_empty() && self.current_return != Type::Void {
+            bail!("Function {} returns {} but its body is empty", func.name, func.return_type);
         }
 
         // Check statements
@@ -119,9 +363,20 @@ impl TypeChecker {
     }
 
     fn check_statement(&mut self, stmt: &Statement) -> Result<()> {
+        self.check_statement_kind(&stmt.kind).map_err(|e| located(e, stmt.span.clone()))
+    }
+
+    fn check_statement_kind(&mut self, stmt: &StatementKind) -> Result<()> {
         match stmt {
-            Statement::Let { name, ty, value } => {
+            StatementKind::Let { name, mutable, ty, value } => {
                 let value_type = self.infer_expression(value)?;
+                // A new binding shadows the old one's mutability too
+                self.buffers.remove(name);
+                if *mutable {
+                    self.mutable.insert(name.clone());
+                } else {
+                    self.mutable.remove(name);
+                }
                 if let Some(declared_ty) = ty {
                     if !self.types_compatible(declared_ty, &value_type) {
                         bail!(
@@ -130,41 +385,54 @@ impl TypeChecker {
                             value_type
                         );
                     }
-                    self.variables.insert(name.clone(), declared_ty.clone());
+                    self.variables.insert(name.clone(), base_type(declared_ty));
                 } else {
                     self.variables.insert(name.clone(), value_type);
                 }
+                self.lets.borrow_mut().push((name.clone(), self.variables[name].clone()));
                 Ok(())
             }
-            Statement::Assign {
+            StatementKind::Assign {
                 target,
-                index,
+                indices,
                 value,
+                ..
             } => {
+                if !self.variables.contains_key(target) && self.constants.contains_key(target) {
+                    bail!("Cannot assign to constant {}", target);
+                }
                 let var_type = self
                     .variables
                     .get(target)
                     .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", target))?
                     .clone();
+                let buffer_element = !indices.is_empty() && self.buffers.contains(target);
+                if !self.mutable.contains(target) && !buffer_element {
+                    bail!("cannot assign to immutable variable {}", target);
+                }
 
                 let value_type = self.infer_expression(value)?;
 
-                if let Some(idx_expr) = index {
-                    // Array assignment
-                    let idx_type = self.infer_expression(idx_expr)?;
-                    if idx_type != Type::Int {
-                        bail!("Array index must be int, got {}", idx_type);
-                    }
-                    if let Type::Array(elem_type, _) = var_type {
-                        if !self.types_compatible(&elem_type, &value_type) {
-                            bail!(
-                                "Type mismatch in array assignment: expected {}, got {}",
-                                elem_type,
-                                value_type
-                            );
+                if !indices.is_empty() {
+                    // Array assignment: each index peels one level (m[i][j]
+                    // on [[int; 2]; 2] is an int)
+                    let mut elem_type = var_type;
+                    for idx_expr in indices {
+                        let idx_type = self.infer_expression(idx_expr)?;
+                        if idx_type != Type::Int {
+                            bail!("Array index must be int, got {}", idx_type);
                         }
-                    } else {
-                        bail!("Cannot index non-array type {}", var_type);
+                        elem_type = match elem_type {
+                            Type::Array(elem, _) => *elem,
+                            other => bail!("Cannot index non-array type {}", other),
+                        };
+                    }
+                    if !self.types_compatible(&elem_type, &value_type) {
+                        bail!(
+                            "Type mismatch in array assignment: expected {}, got {}",
+                            elem_type,
+                            value_type
+                        );
                     }
                 } else {
                     if !self.types_compatible(&var_type, &value_type) {
@@ -177,15 +445,31 @@ impl TypeChecker {
                 }
                 Ok(())
             }
-            Statement::Return(expr) => {
-                self.infer_expression(expr)?;
+            StatementKind::Return(expr) => {
+                let value_type = self.infer_expression(expr)?;
+                // `return;` parses as a placeholder value, so void functions
+                // have nothing to check
+                if self.current_return == Type::Void {
+                    return Ok(());
+                }
+                let sizes_match = match (&self.current_return, &value_type) {
+                    (Type::Array(_, Some(expected)), Type::Array(_, Some(actual))) => expected == actual,
+                    _ => true,
+                };
+                if !self.types_compatible(&self.current_return, &value_type) || !sizes_match {
+                    bail!(
+                        "Return type mismatch: expected {}, got {}",
+                        self.current_return,
+                        value_type
+                    );
+                }
                 Ok(())
             }
-            Statement::Expression(expr) => {
+            StatementKind::Expression(expr) => {
                 self.infer_expression(expr)?;
                 Ok(())
             }
-            Statement::For {
+            StatementKind::For {
                 var,
                 start,
                 end,
@@ -197,12 +481,15 @@ impl TypeChecker {
                     bail!("For loop bounds must be int");
                 }
                 self.variables.insert(var.clone(), Type::Int);
+                self.mutable.remove(var);
+                self.loop_depth += 1;
                 for stmt in body {
                     self.check_statement(stmt)?;
                 }
+                self.loop_depth -= 1;
                 Ok(())
             }
-            Statement::If {
+            StatementKind::If {
                 condition,
                 then_body,
                 else_body,
@@ -221,6 +508,18 @@ impl TypeChecker {
                 }
                 Ok(())
             }
+            StatementKind::Break => {
+                if self.loop_depth == 0 {
+                    bail!("`break` outside of loop");
+                }
+                Ok(())
+            }
+            StatementKind::Continue => {
+                if self.loop_depth == 0 {
+                    bail!("`continue` outside of loop");
+                }
+                Ok(())
+            }
         }
     }
 
@@ -229,9 +528,13 @@ impl TypeChecker {
             Expression::IntLiteral(_) => Ok(Type::Int),
             Expression::FloatLiteral(_) => Ok(Type::Float),
             Expression::BoolLiteral(_) => Ok(Type::Bool),
+            Expression::StringLiteral(_) => {
+                bail!("String literals are only supported as the format of print_fmt")
+            }
             Expression::Variable(name) => self
                 .variables
                 .get(name)
+                .or_else(|| self.constants.get(name))
                 .cloned()
                 .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name)),
             Expression::ArrayLiteral(elements) => {
@@ -247,8 +550,27 @@ impl TypeChecker {
                 }
                 Ok(Type::Array(Box::new(first_type), Some(elements.len())))
             }
+            Expression::ArrayRepeat { value, count } => {
+                Ok(Type::Array(Box::new(self.infer_expression(value)?), Some(*count)))
+            }
+            Expression::TupleLiteral(elements) => {
+                let types: Result<Vec<Type>> =
+                    elements.iter().map(|e| self.infer_expression(e)).collect();
+                Ok(Type::Tuple(types?))
+            }
             Expression::Index { array, index } => {
                 let array_type = self.infer_expression(array)?;
+                // Tuple elements differ in type, so the index must be a literal
+                if let Type::Tuple(elems) = &array_type {
+                    let Expression::IntLiteral(i) = **index else {
+                        bail!("Tuple index must be an integer literal");
+                    };
+                    return usize::try_from(i)
+                        .ok()
+                        .and_then(|i| elems.get(i))
+                        .cloned()
+                        .ok_or_else(|| anyhow::anyhow!("Tuple index {} out of range for {}", i, array_type));
+                }
                 let index_type = self.infer_expression(index)?;
                 if index_type != Type::Int {
                     bail!("Array index must be int");
@@ -265,10 +587,41 @@ impl TypeChecker {
                 use BinaryOp::*;
                 match op {
                     Add | Sub | Mul | Div | Mod => {
+                        if left_type == Type::Qubit || right_type == Type::Qubit {
+                            bail!("Arithmetic is not defined for qubits");
+                        }
                         if left_type == Type::Int && right_type == Type::Int {
                             Ok(Type::Int)
                         } else if left_type == Type::Float && right_type == Type::Float {
                             Ok(Type::Float)
+                        } else if left_type == Type::Complex && right_type == Type::Complex {
+                            if *op == Mod {
+                                bail!("Modulo is not defined for complex numbers");
+                            }
+                            Ok(Type::Complex)
+                        } else if let (Type::Array(l_elem, l_size), Type::Array(r_elem, r_size)) =
+                            (&left_type, &right_type)
+                        {
+                            // Elementwise Arithmetic: a + b on same-length arrays,
+                            // only in GPU kernels so classical `+` keeps scalar meaning
+                            if self.current_domain != Domain::Gpu {
+                                bail!("Elementwise array arithmetic is only allowed in @gpu functions");
+                            }
+                            if l_elem != r_elem || !matches!(**l_elem, Type::Int | Type::Float) {
+                                bail!(
+                                    "Elementwise arithmetic needs int or float arrays of one element type, got {} and {}",
+                                    left_type,
+                                    right_type
+                                );
+                            }
+                            if l_size != r_size {
+                                bail!(
+                                    "Elementwise arithmetic needs arrays of the same length, got {} and {}",
+                                    left_type,
+                                    right_type
+                                );
+                            }
+                            Ok(left_type.clone())
                         } else {
                             bail!("Type mismatch in arithmetic operation");
                         }
@@ -277,6 +630,9 @@ impl TypeChecker {
                         if !self.types_compatible(&left_type, &right_type) {
                             bail!("Type mismatch in comparison");
                         }
+                        if left_type == Type::Complex && !matches!(op, Eq | Ne) {
+                            bail!("Complex numbers are not ordered");
+                        }
                         Ok(Type::Bool)
                     }
                     And | Or => {
@@ -291,7 +647,7 @@ impl TypeChecker {
                 let operand_type = self.infer_expression(operand)?;
                 match op {
                     UnaryOp::Neg => {
-                        if operand_type == Type::Int || operand_type == Type::Float {
+                        if matches!(operand_type, Type::Int | Type::Float | Type::Complex) {
                             Ok(operand_type)
                         } else {
                             bail!("Negation requires numeric type");
@@ -306,7 +662,154 @@ impl TypeChecker {
                     }
                 }
             }
-            Expression::Call { function, args } => {
+            Expression::Call { function, args, .. } if function == "matmul" => {
+                // Matrix product: (R x K) * (K x C) -> (R x C), checked when sizes are known
+                let [a, b] = args.as_slice() else {
+                    bail!("Function matmul expects 2 arguments, got {}", args.len());
+                };
+                let (a_type, b_type) = (self.infer_expression(a)?, self.infer_expression(b)?);
+                let (elem, rows, inner_a) = matrix_shape(&a_type)
+                    .ok_or_else(|| anyhow::anyhow!("matmul expects matrices, got {}", a_type))?;
+                let (b_elem, inner_b, cols) = matrix_shape(&b_type)
+                    .ok_or_else(|| anyhow::anyhow!("matmul expects matrices, got {}", b_type))?;
+                if elem != b_elem || !matches!(elem, Type::Int | Type::Float) {
+                    bail!("matmul needs int or float matrices of one element type, got {} and {}", a_type, b_type);
+                }
+                if let (Some(k1), Some(k2)) = (inner_a, inner_b) {
+                    if k1 != k2 {
+                        bail!(
+                            "matmul dimension mismatch: {}x{} times {}x{}",
+                            dim(rows), k1, k2, dim(cols)
+                        );
+                    }
+                }
+                Ok(Type::Array(Box::new(Type::Array(Box::new(elem), cols)), rows))
+            }
+            Expression::Call { function, args, .. } if matches!(function.as_str(), "sum" | "product") => {
+                // Reductions: the sum or product of an array's elements
+                let [array] = args.as_slice() else {
+                    bail!("Function {} expects 1 arguments, got {}", function, args.len());
+                };
+                match self.infer_expression(array)? {
+                    Type::Array(elem, _) if matches!(*elem, Type::Int | Type::Float) => Ok(*elem),
+                    other => bail!("{} expects an int or float array, got {}", function, other),
+                }
+            }
+            Expression::Call { function, args, .. } if function == "dot" => {
+                let [a, b] = args.as_slice() else {
+                    bail!("Function dot expects 2 arguments, got {}", args.len());
+                };
+                let (a_type, b_type) = (self.infer_expression(a)?, self.infer_expression(b)?);
+                match (&a_type, &b_type) {
+                    (Type::Array(a_elem, a_size), Type::Array(b_elem, b_size))
+                        if **a_elem == Type::Float && **b_elem == Type::Float =>
+                    {
+                        if let (Some(n), Some(m)) = (a_size, b_size) {
+                            if n != m {
+                                bail!("dot needs arrays of the same length, got {} and {}", a_type, b_type);
+                            }
+                        }
+                        Ok(Type::Float)
+                    }
+                    _ => bail!("dot expects two float arrays, got {} and {}", a_type, b_type),
+                }
+            }
+            Expression::Call { function, args, .. }
+                if matches!(function.as_str(), "to_float" | "to_int") =>
+            {
+                // Numeric conversions: to_int truncates toward zero
+                let [value] = args.as_slice() else {
+                    bail!("Function {} expects 1 arguments, got {}", function, args.len());
+                };
+                match base_type(&self.infer_expression(value)?) {
+                    Type::Int | Type::Float => {}
+                    other => bail!("{} expects an int or float, got {}", function, other),
+                }
+                Ok(if function == "to_float" { Type::Float } else { Type::Int })
+            }
+            Expression::Call { function, args, .. } if function == "print_fmt" => {
+                // Formatted printing: one `{}` in the format per remaining argument
+                let Some((Expression::StringLiteral(format), values)) = args.split_first() else {
+                    bail!("print_fmt expects a string literal format as its first argument");
+                };
+                let placeholders = format.matches("{}").count();
+                if placeholders != values.len() {
+                    bail!(
+                        "print_fmt format has {} placeholders but {} values were given",
+                        placeholders,
+                        values.len()
+                    );
+                }
+                for value in values {
+                    self.infer_expression(value)?;
+                }
+                Ok(Type::Void)
+            }
+            Expression::Call { function, args, .. } if function == "len" => {
+                // Array length: folded to the size when it is known statically
+                let [array] = args.as_slice() else {
+                    bail!("Function len expects 1 arguments, got {}", args.len());
+                };
+                match self.infer_expression(array)? {
+                    Type::Array(..) | Type::Matrix(..) | Type::Tensor(_) => Ok(Type::Int),
+                    other => bail!("len expects an array, got {}", other),
+                }
+            }
+            Expression::Call { function, args, .. }
+                if matches!(function.as_str(), "zeros" | "ones" | "range") =>
+            {
+                // Array constructors: the size is folded to a literal by the parser
+                let size = match args.as_slice() {
+                    [Expression::IntLiteral(n)] if *n >= 0 => *n as usize,
+                    [_] => bail!("Size of {} must be a non-negative compile-time integer constant", function),
+                    _ => bail!("Function {} expects 1 arguments, got {}", function, args.len()),
+                };
+                let elem = if function == "range" { Type::Int } else { Type::Float };
+                Ok(Type::Array(Box::new(elem), Some(size)))
+            }
+            Expression::Call { function, args, .. } if function == "qalloc" => {
+                // Qubit Registers: qalloc(n) allocates n fresh qubits of the circuit
+                if self.current_domain != Domain::Quantum {
+                    bail!("qalloc can only be used in @quantum functions");
+                }
+                let size = match args.as_slice() {
+                    [Expression::IntLiteral(n)] if *n > MAX_QUBITS => {
+                        bail!("qalloc({}) is too large: a function can allocate at most {} qubits", n, MAX_QUBITS)
+                    }
+                    [Expression::IntLiteral(n)] if *n >= 0 => *n as usize,
+                    [_] => bail!("Size of qalloc must be a non-negative compile-time integer constant"),
+                    _ => bail!("Function qalloc expects 1 arguments, got {}", args.len()),
+                };
+                Ok(Type::Array(Box::new(Type::Qubit), Some(size)))
+            }
+            Expression::Call { function, args, .. }
+                if matches!(function.as_str(), "atomic_add" | "atomic_max") =>
+            {
+                // GPU Atomics: read-modify-write of one element of an int
+                // storage buffer, returning the element's previous value
+                if self.current_domain != Domain::Gpu {
+                    bail!("{} can only be used in @gpu functions", function);
+                }
+                let [buffer, index, value] = args.as_slice() else {
+                    bail!("Function {} expects 3 arguments, got {}", function, args.len());
+                };
+                let buffer_type = self.infer_expression(buffer)?;
+                match (&buffer_type, buffer) {
+                    (Type::Array(elem, None), Expression::Variable(_)) if **elem == Type::Int => {}
+                    (Type::Array(elem, _), _) if **elem == Type::Int => {
+                        bail!("{} needs a storage buffer (an unsized [int] parameter), got {}", function, buffer_type)
+                    }
+                    _ => bail!("{} expects an int buffer, got {}", function, buffer_type),
+                }
+                for (what, arg) in [("index", index), ("value", value)] {
+                    let ty = self.infer_expression(arg)?;
+                    if ty != Type::Int {
+                        bail!("{} {} must be int, got {}", function, what, ty);
+                    }
+                }
+                Ok(Type::Int)
+            }
+            Expression::Call { function, args, .. } => {
                 let (param_types, return_type, target_domain) = self
                     .functions
                     .get(function)
@@ -318,8 +821,8 @@ impl TypeChecker {
                     // Cross-domain call detected
                     // For now, we allow it (automatic conversion will be inserted later)
                     // In the future, we can add warnings or restrictions here
-                    eprintln!(
-                        "INFO: Cross-domain call from {:?} to {:?} function '{}'",
+                    info!(
+                        "Cross-domain call from {:?} to {:?} function '{}'",
                         self.current_domain, target_domain, function
                     );
                 }
@@ -333,15 +836,64 @@ impl TypeChecker {
                     );
                 }
 
-                for (arg, param_type) in args.iter().zip(param_types.iter()) {
+                for (i, (arg, param_type)) in args.iter().zip(param_types.iter()).enumerate() {
                     let arg_type = self.infer_expression(arg)?;
+                    // Gates act on qubits from a register, never on bare indices
+                    if *param_type == Type::Qubit && arg_type != Type::Qubit {
+                        bail!(
+                            "{} expects a qubit, got {} (argument {}): allocate a register with qalloc and pass an element such as q[0]",
+                            function,
+                            arg_type,
+                            i + 1
+                        );
+                    }
+                    // print_array prints any array, whatever its element type
+                    if function == "print_array" && matches!(arg_type, Type::Array(..)) {
+                        continue;
+                    }
+                    // Overloads: print takes any scalar, not just int
+                    if function == "print" && matches!(base_type(&arg_type), Type::Float | Type::Bool) {
+                        continue;
+                    }
+                    // Amplitudes may be real or complex
+                    if function == "amplitude_encode"
+                        && matches!(&arg_type, Type::Array(elem, _) if **elem == Type::Complex)
+                    {
+                        continue;
+                    }
                     if !self.types_compatible(param_type, &arg_type) {
-                        bail!("Argument type mismatch: expected {}, got {}", param_type, arg_type);
+                        bail!(
+                            "Argument type mismatch in call to {}: argument {} expected {}, got {}",
+                            function,
+                            i + 1,
+                            param_type,
+                            arg_type
+                        );
                     }
                 }
 
                 Ok(return_type)
             }
+            Expression::If { condition, then_body, then_value, else_body, else_value } => {
+                let cond_type = self.infer_expression(condition)?;
+                if cond_type != Type::Bool {
+                    bail!("If condition must be bool, got {}", cond_type);
+                }
+                // Each arm is its own scope, checked on a copy of the checker
+                let arm = |body: &[Statement], value: &Expression| -> Result<Type> {
+                    let mut scope = self.clone();
+                    for stmt in body {
+                        scope.check_statement(stmt)?;
+                    }
+                    scope.infer_expression(value)
+                };
+                let then_type = arm(then_body, then_value)?;
+                let else_type = arm(else_body, else_value)?;
+                if then_type != else_type {
+                    bail!("`if` branches have different types: {} and {}", then_type, else_type);
+                }
+                Ok(then_type)
+            }
             Expression::Map { function, array } => {
                 let array_type = self.infer_expression(array)?;
                 let (param_types, return_type, _domain) = self
@@ -368,20 +920,202 @@ impl TypeChecker {
     }
 
     fn types_compatible(&self, expected: &Type, actual: &Type) -> bool {
-        match (expected, actual) {
+        match (&base_type(expected), &base_type(actual)) {
             (Type::Array(e1, _), Type::Array(e2, _)) => self.types_compatible(e1, e2),
             (Type::Tensor(e1), Type::Tensor(e2)) => self.types_compatible(e1, e2),
             // Allow implicit conversion: Array → Tensor (for hybrid workflows)
             (Type::Tensor(e1), Type::Array(e2, _)) => self.types_compatible(e1, e2),
             (Type::Array(e1, _), Type::Tensor(e2)) => self.types_compatible(e1, e2),
-            _ => expected == actual,
+            (Type::Tuple(t1), Type::Tuple(t2)) => {
+                t1.len() == t2.len() && t1.iter().zip(t2).all(|(a, b)| self.types_compatible(a, b))
+            }
+            (expected, actual) => expected == actual,
+        }
+    }
+}
+
+// Width Annotations: int32/int64 and float32/float64 check as int and float;
+// the width only matters to the backends
+fn base_type(ty: &Type) -> Type {
+    match ty {
+        Type::Int32 | Type::Int64 => Type::Int,
+        Type::Float32 | Type::Float64 => Type::Float,
+        Type::Array(elem, size) => Type::Array(Box::new(base_type(elem)), *size),
+        Type::Tensor(elem) => Type::Tensor(Box::new(base_type(elem))),
+        Type::Tuple(elems) => Type::Tuple(elems.iter().map(base_type).collect()),
+        // matrix<T, R, C> is an array of R rows of C elements
+        Type::Matrix(elem, dims) => {
+            let row = Type::Array(Box::new(base_type(elem)), dims.map(|(_, cols)| cols));
+            Type::Array(Box::new(row), dims.map(|(rows, _)| rows))
+        }
+        _ => ty.clone(),
+    }
+}
+
+// (element, rows, cols) of a matrix-shaped type `[[T; C]; R]`
+fn matrix_shape(ty: &Type) -> Option<(Type, Option<usize>, Option<usize>)> {
+    match base_type(ty) {
+        Type::Array(row, rows) => match *row {
+            Type::Array(elem, cols) => Some((*elem, rows, cols)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn dim(size: Option<usize>) -> String {
+    size.map_or("?".to_string(), |n| n.to_string())
+}
+
+// Qubit Indices: which arguments of a gate builtin name a qubit
+fn qubit_positions(function: &str) -> &'static [usize] {
+    match function {
+        "h" | "x" | "y" | "z" | "rx" | "ry" | "rz" | "measure" | "reset" => &[0],
+        "cx" | "cnot" | "cz" => &[0, 1],
+        "p" => &[1],
+        "u" => &[3],
+        _ => &[],
+    }
+}
+
+// Implicit Measurement: a @quantum function that applies gates but never
+// measures gets every qubit measured at the end by the backends. Warn, so a
+// forgotten measure() doesn't go unnoticed. Helpers called from another
+// @quantum function are part of their caller's circuit and are skipped.
+fn warn_unmeasured_circuits(program: &Program) {
+    let calls: HashMap<&str, Vec<&str>> = program
+        .functions
+        .iter()
+        .map(|func| {
+            let mut callees = Vec::new();
+            for stmt in &func.body {
+                collect_calls_in_statement(stmt, &mut callees);
+            }
+            (func.name.as_str(), callees)
+        })
+        .collect();
+    let is_quantum = |name: &str| program.functions.iter().any(|f| f.name == name && f.domain == Domain::Quantum);
+
+    // Does the function (or a @quantum helper it calls) call `any` of the
+    // given builtins? The call graph is acyclic by now.
+    fn reaches(
+        name: &str,
+        any: &dyn Fn(&str) -> bool,
+        calls: &HashMap<&str, Vec<&str>>,
+        is_quantum: &dyn Fn(&str) -> bool,
+    ) -> bool {
+        calls[name]
+            .iter()
+            .any(|callee| any(callee) || (is_quantum(callee) && reaches(callee, any, calls, is_quantum)))
+    }
+    let is_gate = |name: &str| !qubit_positions(name).is_empty() && name != "measure";
+    let is_measurement = |name: &str| matches!(name, "measure" | "measure_all");
+
+    for func in program.functions.iter().filter(|f| f.domain == Domain::Quantum) {
+        let is_helper = program
+            .functions
+            .iter()
+            .any(|caller| caller.domain == Domain::Quantum && calls[caller.name.as_str()].contains(&func.name.as_str()));
+        if is_helper {
+            continue;
+        }
+        let name = func.name.as_str();
+        if reaches(name, &is_gate, &calls, &is_quantum) && !reaches(name, &is_measurement, &calls, &is_quantum) {
+            warn!(
+                "@quantum function '{}' applies gates but never measures; every qubit is measured at the end (call measure(q) or measure_all() to make this explicit)",
+                name
+            );
+        }
+    }
+}
+
+// Names of all functions called (directly or through map) in source order
+fn collect_calls_in_statement<'a>(stmt: &'a Statement, calls: &mut Vec<&'a str>) {
+    match &stmt.kind {
+        StatementKind::Let { value, .. } => collect_calls_in_expression(value, calls),
+        StatementKind::Assign { indices, value, .. } => {
+            for index in indices {
+                collect_calls_in_expression(index, calls);
+            }
+            collect_calls_in_expression(value, calls);
         }
+        StatementKind::Return(expr) | StatementKind::Expression(expr) => collect_calls_in_expression(expr, calls),
+        StatementKind::For { start, end, body, .. } => {
+            collect_calls_in_expression(start, calls);
+            collect_calls_in_expression(end, calls);
+            for stmt in body {
+                collect_calls_in_statement(stmt, calls);
+            }
+        }
+        StatementKind::If { condition, then_body, else_body } => {
+            collect_calls_in_expression(condition, calls);
+            for stmt in then_body.iter().chain(else_body.iter().flatten()) {
+                collect_calls_in_statement(stmt, calls);
+            }
+        }
+        StatementKind::Break | StatementKind::Continue => {}
     }
 }
 
+fn collect_calls_in_expression<'a>(expr: &'a Expression, calls: &mut Vec<&'a str>) {
+    match expr {
+        Expression::IntLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::BoolLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::Variable(_) => {}
+        Expression::ArrayLiteral(elements) | Expression::TupleLiteral(elements) => {
+            for elem in elements {
+                collect_calls_in_expression(elem, calls);
+            }
+        }
+        Expression::ArrayRepeat { value, .. } => collect_calls_in_expression(value, calls),
+        Expression::Index { array, index } => {
+            collect_calls_in_expression(array, calls);
+            collect_calls_in_expression(index, calls);
+        }
+        Expression::Binary { left, right, .. } => {
+            collect_calls_in_expression(left, calls);
+            collect_calls_in_expression(right, calls);
+        }
+        Expression::Unary { operand, .. } => collect_calls_in_expression(operand, calls),
+        Expression::Call { function, args, .. } => {
+            for arg in args {
+                collect_calls_in_expression(arg, calls);
+            }
+            if !calls.contains(&function.as_str()) {
+                calls.push(function);
+            }
+        }
+        Expression::Map { function, array } => {
+            collect_calls_in_expression(array, calls);
+            if !calls.contains(&function.as_str()) {
+                calls.push(function);
+            }
+        }
+        Expression::If { condition, then_body, then_value, else_body, else_value } => {
+            collect_calls_in_expression(condition, calls);
+            for stmt in then_body.iter().chain(else_body) {
+                collect_calls_in_statement(stmt, calls);
+            }
+            collect_calls_in_expression(then_value, calls);
+            collect_calls_in_expression(else_value, calls);
+        }
+    }
+}
+
+/// Type check a program.
 pub fn typecheck(program: &Program) -> Result<()> {
     let mut checker = TypeChecker::new();
     checker.check_program(program)
 }
 
+/// Type check a program and return each function's resolved parameter,
+/// return and `let` types.
+pub fn infer_types(program: &Program) -> Result<Vec<FunctionTypes>> {
+    let mut checker = TypeChecker::new();
+    checker.check_program(program)?;
+    Ok(checker.typed)
+}
+
 
@@ -0,0 +1,116 @@
+//! QuarkDSL compiler library
+//!
+//! The pipeline the `quarkdsl` binary drives, exposed for embedding
+//! (e.g. a playground server) without spawning a process:
+//! parse -> typecheck -> lower -> optimize -> codegen.
+//!
+//! ```
+//! use quarkdsl::{compile_to_string, Target};
+//!
+//! let source = "fn main() -> int { return 1 + 2; }";
+//! let wgsl = compile_to_string(source, Target::Wgsl, true).unwrap();
+//! assert!(wgsl.contains("fn main() -> i32"));
+//! ```
+
+#[macro_use]
+pub mod log;
+pub mod timing;
+pub mod frontend;
+pub mod middle;
+pub mod backend;
+pub mod cli;
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+
+pub use frontend::{parse, typecheck};
+pub use middle::lower_to_ir as lower;
+pub use middle::optimize;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Target {
+    /// WebGPU WGSL backend
+    Wgsl,
+    /// Quantum Qiskit backend
+    Quantum,
+    /// Python Orchestrator (Hybrid GPU + Quantum)
+    Orchestrator,
+    /// All backends at once: --output names a directory
+    All,
+}
+
+/// Compile DSL source to a single backend's code.
+///
+/// `Target::All` produces several files and is rejected here; use
+/// [`compile_to_ir`] and [`codegen_all`] instead.
+pub fn compile_to_string(source: &str, target: Target, optimize: bool) -> Result<String> {
+    let ir = compile_to_ir(source, optimize, &middle::Options::default())?;
+    codegen(&ir, target, None, false)
+}
+
+/// Run the frontend and middle-end: parse, typecheck, lower, and optionally optimize.
+pub fn compile_to_ir(source: &str, optimize: bool, options: &middle::Options) -> Result<middle::ir::Module> {
+    use timing::time;
+
+    let ast = time("parse", || parse(source)).with_context(|| "Failed to parse source")?;
+    time("typecheck", || typecheck(&ast))
+        .map_err(|e| frontend::locate(source, e))
+        .with_context(|| "Type checking failed")?;
+    let mut ir = time("lower", || lower(&ast, options)).with_context(|| "Failed to lower to IR")?;
+    if optimize {
+        middle::optimize(&mut ir, options)?;
+    }
+    Ok(ir)
+}
+
+/// Generate one backend's code for a lowered module.
+///
+/// With `strict`, an instruction a backend cannot express is an error naming
+/// it and its function, instead of being dropped or left as a comment. That
+/// includes `map(f, xs)`, which no backend implements yet.
+pub fn codegen(ir: &middle::ir::Module, target: Target, seed: Option<u64>, strict: bool) -> Result<String> {
+    if ir.functions.is_empty() {
+        bail!("no functions to compile");
+    }
+    match target {
+        Target::Wgsl => timing::time("codegen wgsl", || backend::wgsl::codegen(ir, strict)),
+        Target::Quantum => timing::time("codegen quantum", || backend::quantum::codegen(ir, seed, strict)),
+        Target::Orchestrator => timing::time("codegen orchestrator", || {
+            backend::orchestrator::generate_orchestrator(ir, seed, strict)
+        }),
+        Target::All => bail!("Target::All writes one file per backend; use codegen_all"),
+    }
+}
+
+/// Route each function to the backend matching its domain: @gpu functions to
+/// out.wgsl, @quantum functions to out_quantum.py, and the whole module to
+/// orchestrator.py. Backends with no functions are skipped.
+///
+/// An array parameter a @gpu kernel stores into is a `read_write` buffer in
+/// the WGSL, and the NumPy simulation mutates it in place and returns it.
+pub fn codegen_all(ir: &middle::ir::Module, seed: Option<u64>, strict: bool) -> Result<Vec<(&'static str, String)>> {
+    use frontend::ast::Domain;
+    use middle::ir::Module;
+
+    if ir.functions.is_empty() {
+        bail!("no functions to compile");
+    }
+
+    let by_domain = |domain: Domain| Module {
+        functions: ir.functions.iter().filter(|f| f.domain == domain).cloned().collect(),
+    };
+
+    let mut files = Vec::new();
+    let gpu = by_domain(Domain::Gpu);
+    if !gpu.functions.is_empty() {
+        files.push(("out.wgsl", timing::time("codegen wgsl", || backend::wgsl::codegen(&gpu, strict))?));
+    }
+    let quantum = by_domain(Domain::Quantum);
+    if !quantum.functions.is_empty() {
+        let code = timing::time("codegen quantum", || backend::quantum::codegen(&quantum, seed, strict))?;
+        files.push(("out_quantum.py", code));
+    }
+    let code = timing::time("codegen orchestrator", || backend::orchestrator::generate_orchestrator(ir, seed, strict))?;
+    files.push(("orchestrator.py", code));
+    Ok(files)
+}
@@ -0,0 +1,55 @@
+// Diagnostics Verbosity: INFO chatter is opt-in (-v), warnings are on by
+// default and silenced by -q
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const QUIET: u8 = 0;
+pub const NORMAL: u8 = 1;
+pub const VERBOSE: u8 = 2;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(NORMAL);
+
+// Log Sink: stderr by default; embedders without a terminal (the browser
+// build) install a callback instead
+pub type Sink = Box<dyn Fn(&str)>;
+
+thread_local! {
+    static SINK: RefCell<Option<Sink>> = RefCell::new(None);
+}
+
+pub fn set_verbosity(level: u8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+pub fn enabled(level: u8) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level
+}
+
+pub fn set_sink(sink: Option<Sink>) {
+    SINK.with(|s| *s.borrow_mut() = sink);
+}
+
+pub fn emit(line: &str) {
+    SINK.with(|s| match &*s.borrow() {
+        Some(sink) => sink(line),
+        None => eprintln!("{}", line),
+    });
+}
+
+// info!("..."): progress and cross-domain notes, shown only with -v
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::VERBOSE) {
+            $crate::log::emit(&format!("INFO: {}", format!($($arg)*)));
+        }
+    };
+}
+
+// warn!("..."): suspicious but accepted input, hidden with -q
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::NORMAL) {
+            $crate::log::emit(&format!("WARNING: {}", format!($($arg)*)));
+        }
+    };
+}
@@ -1,12 +1,15 @@
-mod frontend;
-mod middle;
-mod backend;
-mod cli;
-
 use anyhow::Result;
 use clap::Parser;
+use quarkdsl::cli;
 
 fn main() -> Result<()> {
     let args = cli::Args::parse();
-    cli::run(args)
+    let json = args.message_format == cli::MessageFormat::Json;
+    match cli::run(args) {
+        Err(e) if json => {
+            eprintln!("{}", cli::json_error(&e));
+            std::process::exit(1);
+        }
+        result => result,
+    }
 }
@@ -0,0 +1,99 @@
+// Control Flow Analysis shared by the optimizer and the verifier
+// Blocks are identified by their index in `IRFunction::blocks`; block 0 is
+// the entry
+//
+// Dominance: A block D dominates B if every path from entry to B passes D
+
+use super::ir::*;
+use std::collections::{HashMap, HashSet};
+
+// Labels a terminator can transfer control to
+pub(crate) fn terminator_targets(term: &Terminator) -> Vec<&str> {
+    match term {
+        Terminator::Branch {
+            true_label,
+            false_label,
+            ..
+        } => vec![true_label.as_str(), false_label.as_str()],
+        Terminator::Jump(label) => vec![label.as_str()],
+        Terminator::Return(_) | Terminator::ReturnVoid => vec![],
+    }
+}
+
+// Successors of each block, by index (targets naming no block are skipped)
+pub(crate) fn block_successors(func: &IRFunction) -> Vec<Vec<usize>> {
+    let index: HashMap<&str, usize> = func
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.label.as_str(), i))
+        .collect();
+    func.blocks
+        .iter()
+        .map(|b| {
+            terminator_targets(&b.terminator)
+                .iter()
+                .filter_map(|label| index.get(label).copied())
+                .collect()
+        })
+        .collect()
+}
+
+// Blocks reachable from the entry
+pub(crate) fn reachable_blocks(successors: &[Vec<usize>]) -> HashSet<usize> {
+    let mut reachable = HashSet::new();
+    let mut worklist = if successors.is_empty() { vec![] } else { vec![0] };
+    while let Some(b) = worklist.pop() {
+        if reachable.insert(b) {
+            worklist.extend(successors[b].iter().copied());
+        }
+    }
+    reachable
+}
+
+// Iterative Dataflow Analysis: Dom(entry) = {entry},
+// Dom(b) = {b} ∪ ⋂ Dom(p) over reachable predecessors p, repeated until
+// fixed point. Unreachable blocks are dominated by nothing (an empty set).
+pub(crate) fn compute_dominators(successors: &[Vec<usize>], reachable: &HashSet<usize>) -> Vec<HashSet<usize>> {
+    let n = successors.len();
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (b, succ) in successors.iter().enumerate() {
+        if reachable.contains(&b) {
+            for &s in succ {
+                predecessors[s].push(b);
+            }
+        }
+    }
+
+    let mut dominators: Vec<HashSet<usize>> = (0..n)
+        .map(|b| if reachable.contains(&b) { reachable.clone() } else { HashSet::new() })
+        .collect();
+    if n > 0 {
+        dominators[0] = HashSet::from([0]);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in 1..n {
+            if !reachable.contains(&b) {
+                continue;
+            }
+            let mut new_dom: Option<HashSet<usize>> = None;
+            for &p in &predecessors[b] {
+                new_dom = Some(match new_dom {
+                    None => dominators[p].clone(),
+                    Some(d) => d.intersection(&dominators[p]).copied().collect(),
+                });
+            }
+            let mut new_dom = new_dom.unwrap_or_default();
+            new_dom.insert(b);
+            if new_dom != dominators[b] {
+                dominators[b] = new_dom;
+                changed = true;
+            }
+        }
+    }
+
+    dominators
+}
@@ -1,24 +1,123 @@
 use super::ir::*;
+use std::collections::HashMap;
 
 pub fn dump_ir(module: &Module) -> String {
+    dump_module(module, false)
+}
+
+/// Like `dump_ir`, but annotates every defined variable with its type
+/// (`%3: int = add %1 %2`) and keeps doc comments and source lines
+/// (`call f(%3)  // line 4`), so [`super::parse_ir`] can read it back exactly
+pub fn dump_ir_typed(module: &Module) -> String {
+    dump_module(module, true)
+}
+
+/// A single instruction in dump syntax (`call h(%1)`), for diagnostics
+pub fn dump_inst(inst: &Instruction) -> String {
+    dump_instruction(inst, None)
+}
+
+fn dump_module(module: &Module, typed: bool) -> String {
     let mut output = String::new();
 
     for func in &module.functions {
-        output.push_str(&dump_function(func));
+        output.push_str(&dump_function(func, typed));
         output.push('\n');
     }
 
     output
 }
 
-fn dump_function(func: &IRFunction) -> String {
+/// Render the control flow graph of every function as a Graphviz digraph.
+/// Each function becomes a cluster; nodes are blocks labelled with their
+/// instruction count, edges come from `Branch`/`Jump` terminators.
+pub fn dump_cfg_dot(module: &Module) -> String {
     let mut output = String::new();
 
-    // Domain annotation (if not Classical)
-    match func.domain {
-        crate::frontend::ast::Domain::Gpu => output.push_str("@gpu\n"),
-        crate::frontend::ast::Domain::Quantum => output.push_str("@quantum\n"),
-        crate::frontend::ast::Domain::Classical => {},
+    output.push_str("digraph cfg {\n");
+    output.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+    for func in &module.functions {
+        output.push_str(&format!("  subgraph \"cluster_{}\" {{\n", func.name));
+        output.push_str(&format!("    label=\"{}\";\n", func.name));
+
+        for block in &func.blocks {
+            output.push_str(&format!(
+                "    \"{}.{}\" [label=\"{}\\n{} instrs\"];\n",
+                func.name,
+                block.label,
+                block.label,
+                block.instructions.len()
+            ));
+        }
+
+        for block in &func.blocks {
+            match &block.terminator {
+                Terminator::Branch {
+                    true_label,
+                    false_label,
+                    ..
+                } => {
+                    output.push_str(&format!(
+                        "    \"{0}.{1}\" -> \"{0}.{2}\" [label=\"true\"];\n",
+                        func.name, block.label, true_label
+                    ));
+                    output.push_str(&format!(
+                        "    \"{0}.{1}\" -> \"{0}.{2}\" [label=\"false\"];\n",
+                        func.name, block.label, false_label
+                    ));
+                }
+                Terminator::Jump(target) => {
+                    output.push_str(&format!(
+                        "    \"{0}.{1}\" -> \"{0}.{2}\";\n",
+                        func.name, block.label, target
+                    ));
+                }
+                Terminator::Return(_) | Terminator::ReturnVoid => {}
+            }
+        }
+
+        output.push_str("  }\n");
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+fn dump_function(func: &IRFunction, typed: bool) -> String {
+    let mut output = String::new();
+
+    if let Some(doc) = func.doc.as_ref().filter(|_| typed) {
+        for line in doc.lines() {
+            output.push_str(format!("/// {}", line).trim_end());
+            output.push('\n');
+        }
+    }
+
+    // Domain annotation (Classical is the default; spelled out in typed dumps)
+    let annotation = match func.domain {
+        crate::frontend::ast::Domain::Gpu => Some("@gpu"),
+        crate::frontend::ast::Domain::Quantum => Some("@quantum"),
+        crate::frontend::ast::Domain::Classical => {
+            if typed || !func.annotations.is_empty() || func.device.is_some() {
+                Some("@classical")
+            } else {
+                None
+            }
+        }
+    };
+    if let Some(annotation) = annotation {
+        output.push_str(annotation);
+        let mut args: Vec<(&str, String)> = func.annotations.iter().map(|(k, v)| (k.as_str(), v.to_string())).collect();
+        if let Some(device) = &func.device {
+            args.push(("backend", format!("{:?}", device)));
+        }
+        if !args.is_empty() {
+            args.sort();
+            let args: Vec<String> = args.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            output.push_str(&format!("({})", args.join(", ")));
+        }
+        output.push('\n');
     }
 
     // Function signature
@@ -32,21 +131,29 @@ fn dump_function(func: &IRFunction) -> String {
     output.push_str(&format!(") -> {} {{\n", func.return_type));
 
     // Blocks
+    let types = if typed { Some(&func.var_types) } else { None };
     for block in &func.blocks {
-        output.push_str(&dump_block(block));
+        output.push_str(&dump_block(block, types));
     }
 
     output.push_str("}\n");
     output
 }
 
-fn dump_block(block: &BasicBlock) -> String {
+fn dump_block(block: &BasicBlock, types: Option<&HashMap<SSAVar, IRType>>) -> String {
     let mut output = String::new();
 
     output.push_str(&format!("  {}:\n", block.label));
 
     for inst in &block.instructions {
-        output.push_str(&format!("    {}\n", dump_instruction(inst)));
+        output.push_str(&format!("    {}", dump_instruction(inst, types)));
+        // Typed dumps keep the source line of calls and stores
+        if let (Some(_), Instruction::Call { line: Some(line), .. } | Instruction::Store { line: Some(line), .. }) =
+            (types, inst)
+        {
+            output.push_str(&format!("  // line {}", line));
+        }
+        output.push('\n');
     }
 
     output.push_str(&format!("    {}\n", dump_terminator(&block.terminator)));
@@ -54,10 +161,18 @@ fn dump_block(block: &BasicBlock) -> String {
     output
 }
 
-fn dump_instruction(inst: &Instruction) -> String {
+// Destination variable, with its type when dumping typed IR
+fn dump_dest(dest: &SSAVar, types: Option<&HashMap<SSAVar, IRType>>) -> String {
+    match types.and_then(|t| t.get(dest)) {
+        Some(ty) => format!("{}: {}", dest, ty),
+        None => format!("{}", dest),
+    }
+}
+
+fn dump_instruction(inst: &Instruction, types: Option<&HashMap<SSAVar, IRType>>) -> String {
     match inst {
         Instruction::Assign { dest, value } => {
-            format!("{} = {}", dest, dump_value(value))
+            format!("{} = {}", dump_dest(dest, types), dump_value(value))
         }
         Instruction::BinaryOp {
             dest,
@@ -67,22 +182,26 @@ fn dump_instruction(inst: &Instruction) -> String {
         } => {
             format!(
                 "{} = {} {} {}",
-                dest,
+                dump_dest(dest, types),
                 dump_binop(*op),
                 dump_value(left),
                 dump_value(right)
             )
         }
         Instruction::UnaryOp { dest, op, operand } => {
-            format!("{} = {} {}", dest, dump_unop(*op), dump_value(operand))
+            format!("{} = {} {}", dump_dest(dest, types), dump_unop(*op), dump_value(operand))
+        }
+        Instruction::Cast { dest, value, to } => {
+            format!("{} = cast {} to {}", dump_dest(dest, types), dump_value(value), to)
         }
         Instruction::Load { dest, array, index } => {
-            format!("{} = load {}[{}]", dest, array, dump_value(index))
+            format!("{} = load {}[{}]", dump_dest(dest, types), array, dump_value(index))
         }
         Instruction::Store {
             array,
             index,
             value,
+            ..
         } => {
             format!(
                 "store {}[{}] = {}",
@@ -95,6 +214,7 @@ fn dump_instruction(inst: &Instruction) -> String {
             dest,
             function,
             args,
+            ..
         } => {
             let args_str = args
                 .iter()
@@ -102,7 +222,7 @@ fn dump_instruction(inst: &Instruction) -> String {
                 .collect::<Vec<_>>()
                 .join(", ");
             if let Some(d) = dest {
-                format!("{} = call {}({})", d, function, args_str)
+                format!("{} = call {}({})", dump_dest(d, types), function, args_str)
             } else {
                 format!("call {}({})", function, args_str)
             }
@@ -113,7 +233,7 @@ fn dump_instruction(inst: &Instruction) -> String {
                 .map(|(val, label)| format!("[{}, {}]", dump_value(val), label))
                 .collect::<Vec<_>>()
                 .join(", ");
-            format!("{} = phi {}", dest, incoming_str)
+            format!("{} = phi {}", dump_dest(dest, types), incoming_str)
         }
         Instruction::DomainConversion {
             dest,
@@ -124,7 +244,7 @@ fn dump_instruction(inst: &Instruction) -> String {
         } => {
             format!(
                 "{} = convert_{:?}_to_{:?}({}, {:?})",
-                dest,
+                dump_dest(dest, types),
                 from_domain,
                 to_domain,
                 dump_value(source),
@@ -156,8 +276,9 @@ fn dump_value(val: &Value) -> String {
     match val {
         Value::Var(v) => format!("{}", v),
         Value::Int(n) => format!("{}", n),
-        Value::Float(f) => format!("{}", f),
+        Value::Float(f) => format!("{:?}", f), // 2.0, not 2: stays a float when parsed back
         Value::Bool(b) => format!("{}", b),
+        Value::Str(s) => format!("{:?}", s),
         Value::Array(elements) => {
             let elems_str = elements
                 .iter()
@@ -166,6 +287,14 @@ fn dump_value(val: &Value) -> String {
                 .join(", ");
             format!("[{}]", elems_str)
         }
+        Value::Tuple(elements) => {
+            let elems_str = elements
+                .iter()
+                .map(dump_value)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("({})", elems_str)
+        }
     }
 }
 
@@ -184,6 +313,8 @@ fn dump_binop(op: BinOp) -> &'static str {
         BinOp::Ge => "ge",
         BinOp::And => "and",
         BinOp::Or => "or",
+        BinOp::Shl => "shl",
+        BinOp::Shr => "shr",
     }
 }
 
@@ -0,0 +1,119 @@
+// Constant Folding: the one evaluator of operations on constants, shared by
+// the parser (array sizes, enum values), `const` lowering and the optimizer,
+// so all three agree on which operations fold and to what
+//
+// Integer arithmetic is checked: an overflow, a division by zero or
+// i64::MIN / -1 is never folded. Float division and remainder by zero are
+// left for the target too. `%` is the truncated remainder for both.
+
+use super::ir::{BinOp, UnOp, Value};
+use crate::frontend::ast;
+
+// Why an operation on constants has no constant result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FoldError {
+    Overflow,       // The result doesn't fit in an int
+    DivisionByZero, // x / 0 or x % 0
+    NotConstant,    // Operand types the operation doesn't fold
+}
+
+impl std::fmt::Display for FoldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FoldError::Overflow => write!(f, "constant overflows int"),
+            FoldError::DivisionByZero => write!(f, "division by zero in constant expression"),
+            FoldError::NotConstant => write!(f, "Cannot evaluate constant expression"),
+        }
+    }
+}
+
+pub(crate) fn fold_binary(op: BinOp, left: &Value, right: &Value) -> Result<Value, FoldError> {
+    use FoldError::*;
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => {
+            let (l, r) = (*l, *r);
+            let checked = |result: Option<i64>| result.map(Value::Int).ok_or(Overflow);
+            match op {
+                BinOp::Add => checked(l.checked_add(r)),
+                BinOp::Sub => checked(l.checked_sub(r)),
+                BinOp::Mul => checked(l.checked_mul(r)),
+                BinOp::Div | BinOp::Mod if r == 0 => Err(DivisionByZero),
+                BinOp::Div => checked(l.checked_div(r)),
+                BinOp::Mod => checked(l.checked_rem(r)),
+                BinOp::Shl | BinOp::Shr if !(0..64).contains(&r) => Err(Overflow),
+                BinOp::Shl => Ok(Value::Int(l << r)),
+                BinOp::Shr => Ok(Value::Int(l >> r)),
+                _ => compare(op, &l, &r),
+            }
+        }
+        (Value::Float(l), Value::Float(r)) => match op {
+            BinOp::Add => Ok(Value::Float(l + r)),
+            BinOp::Sub => Ok(Value::Float(l - r)),
+            BinOp::Mul => Ok(Value::Float(l * r)),
+            BinOp::Div | BinOp::Mod if *r == 0.0 => Err(DivisionByZero),
+            BinOp::Div => Ok(Value::Float(l / r)),
+            // Truncated remainder, matching WGSL and np.fmod
+            BinOp::Mod => Ok(Value::Float(l % r)),
+            _ => compare(op, l, r),
+        },
+        // true && false → false, true == true → true
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            BinOp::And => Ok(Value::Bool(*l && *r)),
+            BinOp::Or => Ok(Value::Bool(*l || *r)),
+            _ => compare(op, l, r),
+        },
+        _ => Err(NotConstant),
+    }
+}
+
+pub(crate) fn fold_unary(op: UnOp, operand: &Value) -> Result<Value, FoldError> {
+    match (op, operand) {
+        (UnOp::Neg, Value::Int(n)) => n.checked_neg().map(Value::Int).ok_or(FoldError::Overflow),
+        (UnOp::Neg, Value::Float(f)) => Ok(Value::Float(-f)),
+        (UnOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        _ => Err(FoldError::NotConstant),
+    }
+}
+
+// A comparison of two constants, as a bool constant
+fn compare<T: PartialOrd>(op: BinOp, l: &T, r: &T) -> Result<Value, FoldError> {
+    let result = match op {
+        BinOp::Eq => l == r,
+        BinOp::Ne => l != r,
+        BinOp::Lt => l < r,
+        BinOp::Le => l <= r,
+        BinOp::Gt => l > r,
+        BinOp::Ge => l >= r,
+        _ => return Err(FoldError::NotConstant),
+    };
+    Ok(Value::Bool(result))
+}
+
+impl From<ast::BinaryOp> for BinOp {
+    fn from(op: ast::BinaryOp) -> Self {
+        match op {
+            ast::BinaryOp::Add => BinOp::Add,
+            ast::BinaryOp::Sub => BinOp::Sub,
+            ast::BinaryOp::Mul => BinOp::Mul,
+            ast::BinaryOp::Div => BinOp::Div,
+            ast::BinaryOp::Mod => BinOp::Mod,
+            ast::BinaryOp::Eq => BinOp::Eq,
+            ast::BinaryOp::Ne => BinOp::Ne,
+            ast::BinaryOp::Lt => BinOp::Lt,
+            ast::BinaryOp::Le => BinOp::Le,
+            ast::BinaryOp::Gt => BinOp::Gt,
+            ast::BinaryOp::Ge => BinOp::Ge,
+            ast::BinaryOp::And => BinOp::And,
+            ast::BinaryOp::Or => BinOp::Or,
+        }
+    }
+}
+
+impl From<ast::UnaryOp> for UnOp {
+    fn from(op: ast::UnaryOp) -> Self {
+        match op {
+            ast::UnaryOp::Neg => UnOp::Neg,
+            ast::UnaryOp::Not => UnOp::Not,
+        }
+    }
+}
@@ -1,22 +1,23 @@
-/// SSA-based Intermediate Representation
-///
-/// Static Single Assignment (SSA): Each variable is assigned exactly once
-/// Three-Address Code: Instructions have at most three operands
-/// Basic Blocks: Sequences of instructions with single entry and exit
-/// Control Flow Graph (CFG): Graph of basic blocks connected by terminators
+//! SSA-based Intermediate Representation
+//!
+//! Static Single Assignment (SSA): Each variable is assigned exactly once
+//! Three-Address Code: Instructions have at most three operands
+//! Basic Blocks: Sequences of instructions with single entry and exit
+//! Control Flow Graph (CFG): Graph of basic blocks connected by terminators
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use crate::frontend::ast::Domain;
 
 // IR Module: Collection of functions (compilation unit)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Module {
     pub functions: Vec<IRFunction>,
 }
 
 // IR Function: SSA form with basic blocks
 // Control Flow Graph: Represented as vector of basic blocks
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IRFunction {
     pub name: String,
     pub params: Vec<(String, IRType)>,
@@ -24,12 +25,16 @@ pub struct IRFunction {
     pub blocks: Vec<BasicBlock>,           // CFG nodes
     pub next_var_id: usize,                // SSA variable counter
     pub domain: Domain,                    // Execution domain (GPU/Quantum)
+    pub var_types: HashMap<SSAVar, IRType>, // Type of each SSA variable
+    pub annotations: HashMap<String, i64>,  // Domain annotation arguments (workgroup, shots, seed)
+    pub device: Option<String>,             // IBM device to run on, from @quantum(backend="...")
+    pub doc: Option<String>,                // `///` doc comment from the source function
 }
 
 // Basic Block: Sequence of instructions with single entry and exit
 // Entry: Only first instruction can be reached from outside
 // Exit: Only terminator transfers control outside
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BasicBlock {
     pub label: String,                     // Block identifier
     pub instructions: Vec<Instruction>,    // Straight-line code
@@ -39,7 +44,7 @@ pub struct BasicBlock {
 // Three-Address Code Instructions
 // Format: dest = operand1 op operand2
 // SSA Property: Each dest is assigned exactly once
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Instruction {
     // dest = value (simple assignment)
     Assign {
@@ -59,6 +64,12 @@ pub enum Instruction {
         op: UnOp,
         operand: Value,
     },
+    // dest = value converted to `to` (to_float / to_int)
+    Cast {
+        dest: SSAVar,
+        value: Value,
+        to: IRType,
+    },
     Load {
         dest: SSAVar,
         array: SSAVar,
@@ -68,11 +79,13 @@ pub enum Instruction {
         array: SSAVar,
         index: Value,
         value: Value,
+        line: Option<usize>, // Source line, for comments in generated code
     },
     Call {
         dest: Option<SSAVar>,
         function: String,
         args: Vec<Value>,
+        line: Option<usize>, // Source line, for comments in generated code
     },
     Phi {
         dest: SSAVar,
@@ -89,14 +102,14 @@ pub enum Instruction {
 }
 
 /// Encoding method for domain conversions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ConversionEncoding {
     AngleEncoding,      // GPU → Quantum: ry(qubit, angle)
     AmplitudeEncoding,  // GPU → Quantum: initialize(statevector)
     MeasurementExtract, // Quantum → GPU: measure + extract counts
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Terminator {
     Return(Value),
     ReturnVoid,
@@ -120,6 +133,8 @@ pub enum Value {
     Float(f64),
     Bool(bool),
     Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    Str(String), // Format string of print_fmt
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -137,6 +152,8 @@ pub enum BinOp {
     Ge,
     And,
     Or,
+    Shl, // Produced by strength reduction; no surface syntax
+    Shr,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -153,6 +170,12 @@ pub enum IRType {
     Array(Box<IRType>, Option<usize>),
     Qubit,
     Void,
+    Complex,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Tuple(Vec<IRType>),
 }
 
 impl SSAVar {
@@ -167,6 +190,23 @@ impl std::fmt::Display for SSAVar {
     }
 }
 
+impl IRType {
+    // Integer of any width (plain int, int32, int64)
+    pub fn is_int(&self) -> bool {
+        matches!(self, IRType::Int | IRType::Int32 | IRType::Int64)
+    }
+
+    // 64-bit types have no WGSL equivalent
+    pub fn is_wide(&self) -> bool {
+        match self {
+            IRType::Int64 | IRType::Float64 => true,
+            IRType::Array(elem, _) => elem.is_wide(),
+            IRType::Tuple(elems) => elems.iter().any(IRType::is_wide),
+            _ => false,
+        }
+    }
+}
+
 impl std::fmt::Display for IRType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -177,6 +217,15 @@ impl std::fmt::Display for IRType {
             IRType::Array(elem, None) => write!(f, "[{}]", elem),
             IRType::Qubit => write!(f, "qubit"),
             IRType::Void => write!(f, "void"),
+            IRType::Complex => write!(f, "complex"),
+            IRType::Int32 => write!(f, "int32"),
+            IRType::Int64 => write!(f, "int64"),
+            IRType::Float32 => write!(f, "float32"),
+            IRType::Float64 => write!(f, "float64"),
+            IRType::Tuple(elems) => {
+                let elems: Vec<String> = elems.iter().map(|e| e.to_string()).collect();
+                write!(f, "({})", elems.join(", "))
+            }
         }
     }
 }
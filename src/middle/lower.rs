@@ -1,24 +1,59 @@
+use super::fold::{fold_binary, fold_unary, FoldError};
 use super::ir::*;
-use crate::frontend::ast;
+use super::options::Options;
+use crate::frontend::{ast, MAX_QUBITS};
 use anyhow::Result;
 use std::collections::HashMap;
 
 pub struct Lowerer {
+    max_unroll: usize,              // Longest constant loop that is unrolled
     current_block: Option<BasicBlock>,
+    blocks: Vec<BasicBlock>,        // Finished blocks of the current function
     var_counter: usize,
+    label_counter: usize,
     var_map: HashMap<String, SSAVar>,
+    var_types: HashMap<SSAVar, IRType>,             // Type of each SSA variable
     function_domains: HashMap<String, ast::Domain>, // Track function domains
+    function_returns: HashMap<String, IRType>,      // Track function return types
+    constants: HashMap<String, Value>,              // Folded module-level constants
     current_domain: ast::Domain, // Current function's domain
+    current_return: IRType,      // Current function's return type
+    loop_stack: Vec<LoopTargets>,   // Innermost loop last
+    qubit_registers: HashMap<SSAVar, (i64, i64)>, // qalloc results: (first qubit, size)
+    next_qubit: i64,                               // First qubit the next qalloc hands out
 }
 
+/// Jump targets for `continue` and `break` inside a lowered loop, and the
+/// edges taken to them so far (for the phis where they land)
+struct LoopTargets {
+    continue_label: String,
+    break_label: String,
+    continue_edges: Vec<Edge>,
+    break_edges: Vec<Edge>,
+}
+
+/// A control-flow edge into a join block: the predecessor's label and the
+/// SSA version of every variable when leaving it
+type Edge = (String, HashMap<String, SSAVar>);
+
 impl Lowerer {
-    fn new() -> Self {
+    fn new(options: &Options) -> Self {
         Self {
+            max_unroll: options.max_unroll,
             current_block: None,
+            blocks: Vec::new(),
             var_counter: 0,
+            label_counter: 0,
             var_map: HashMap::new(),
+            var_types: HashMap::new(),
             function_domains: HashMap::new(),
+            function_returns: HashMap::new(),
+            constants: HashMap::new(),
             current_domain: ast::Domain::Classical,
+            current_return: IRType::Void,
+            loop_stack: Vec::new(),
+            qubit_registers: HashMap::new(),
+            next_qubit: 0,
         }
     }
 
@@ -28,12 +63,59 @@ impl Lowerer {
         SSAVar::new(id)
     }
 
+    fn fresh_label_id(&mut self) -> usize {
+        let id = self.label_counter;
+        self.label_counter += 1;
+        id
+    }
+
+    // Close the current block with a terminator. A no-op when the current
+    // block was already closed (e.g. code following `break`), which makes
+    // any statements after it unreachable and drops them.
+    fn finish_block(&mut self, terminator: Terminator) {
+        if let Some(mut block) = self.current_block.take() {
+            block.terminator = terminator;
+            self.blocks.push(block);
+        }
+    }
+
+    fn start_block(&mut self, label: String) {
+        self.current_block = Some(BasicBlock {
+            label,
+            instructions: Vec::new(),
+            terminator: Terminator::ReturnVoid,
+        });
+    }
+
     fn lower_module(&mut self, program: &ast::Program) -> Result<Module> {
         let mut functions = Vec::new();
 
-        // First pass: collect function domains
+        // Fold enum members and constants up front; uses are substituted by value
+        self.constants.insert("PI".to_string(), Value::Float(std::f64::consts::PI));
+        self.constants.insert("TAU".to_string(), Value::Float(std::f64::consts::TAU));
+        self.constants.insert("E".to_string(), Value::Float(std::f64::consts::E));
+        for e in &program.enums {
+            for member in &e.members {
+                let value = self.eval_const(&member.value)?;
+                self.constants.insert(format!("{}::{}", e.name, member.name), value);
+            }
+        }
+        for constant in &program.constants {
+            let value = self.eval_const(&constant.value)?;
+            self.constants.insert(constant.name.clone(), value);
+        }
+
+        // First pass: collect function domains and return types. Extern
+        // functions are called like any other but never lowered
+        for func in &program.externs {
+            self.function_domains.insert(func.name.clone(), func.domain.clone());
+            let return_type = self.convert_type(&func.return_type);
+            self.function_returns.insert(func.name.clone(), return_type);
+        }
         for func in &program.functions {
             self.function_domains.insert(func.name.clone(), func.domain.clone());
+            let return_type = self.convert_type(&func.return_type);
+            self.function_returns.insert(func.name.clone(), return_type);
         }
 
         // Second pass: lower functions
@@ -46,7 +128,13 @@ impl Lowerer {
 
     fn lower_function(&mut self, func: &ast::Function) -> Result<IRFunction> {
         self.var_counter = 0;
+        self.label_counter = 0;
         self.var_map.clear();
+        self.var_types.clear();
+        self.blocks.clear();
+        self.loop_stack.clear();
+        self.qubit_registers.clear();
+        self.next_qubit = 0;
         self.current_domain = func.domain.clone(); // Set current domain
 
         let params: Vec<(String, IRType)> = func
@@ -56,31 +144,26 @@ impl Lowerer {
             .collect();
 
         // Add parameters to var_map
-        for (name, _) in &params {
+        for (name, ty) in &params {
             let var = self.fresh_var();
             self.var_map.insert(name.clone(), var);
+            self.var_types.insert(var, ty.clone());
         }
 
         let return_type = self.convert_type(&func.return_type);
+        self.current_return = return_type.clone();
 
         // Create entry block
-        self.current_block = Some(BasicBlock {
-            label: "entry".to_string(),
-            instructions: Vec::new(),
-            terminator: Terminator::ReturnVoid,
-        });
-
-        let mut blocks = Vec::new();
+        self.start_block("entry".to_string());
 
         // Lower statements
         for stmt in &func.body {
             self.lower_statement(stmt)?;
         }
 
-        // Finalize current block
-        if let Some(block) = self.current_block.take() {
-            blocks.push(block);
-        }
+        // Finalize current block (falls off the end: implicit void return)
+        self.finish_block(Terminator::ReturnVoid);
+        let blocks = std::mem::take(&mut self.blocks);
 
         Ok(IRFunction {
             name: func.name.clone(),
@@ -89,15 +172,37 @@ impl Lowerer {
             blocks,
             next_var_id: self.var_counter,
             domain: func.domain.clone(), // Pass domain to IR
+            var_types: std::mem::take(&mut self.var_types),
+            annotations: func.annotations.clone(),
+            device: func.device.clone(),
+            doc: func.doc.clone(),
         })
     }
 
     fn lower_statement(&mut self, stmt: &ast::Statement) -> Result<()> {
-        match stmt {
-            ast::Statement::Let { name, value, .. } => {
+        // Dead Code: after return/break/continue closed the block, the rest
+        // of the statement list is unreachable and gets no blocks of its own
+        if self.current_block.is_none() {
+            return Ok(());
+        }
+        match &stmt.kind {
+            ast::StatementKind::Let { name, ty, value, .. } => {
                 let val = self.lower_expression(value)?;
                 let dest = self.fresh_var();
                 self.var_map.insert(name.clone(), dest);
+                if let (ast::Expression::Call { function, .. }, Value::Array(qubits)) = (value, &val) {
+                    if function == "qalloc" && !self.function_domains.contains_key(function) {
+                        let first = match qubits.first() {
+                            Some(Value::Int(q)) => *q,
+                            _ => self.next_qubit,
+                        };
+                        self.qubit_registers.insert(dest, (first, qubits.len() as i64));
+                    }
+                }
+                if let Some(ty) = ty {
+                    let declared = self.convert_type(ty);
+                    self.var_types.insert(dest, declared);
+                }
 
                 self.emit_instruction(Instruction::Assign {
                     dest,
@@ -105,10 +210,11 @@ impl Lowerer {
                 });
                 Ok(())
             }
-            ast::Statement::Assign {
+            ast::StatementKind::Assign {
                 target,
-                index,
+                indices,
                 value,
+                line,
             } => {
                 let val = self.lower_expression(value)?;
                 let var = *self
@@ -116,30 +222,62 @@ impl Lowerer {
                     .get(target)
                     .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", target))?;
 
-                if let Some(idx_expr) = index {
-                    let idx = self.lower_expression(idx_expr)?;
+                if let Some((last, outer)) = indices.split_last() {
+                    // Nested Store: m[i][j] = v loads the row m[i], stores
+                    // into it, and writes the row back (arrays are values
+                    // in WGSL, so the load is a copy)
+                    let mut path = vec![var];
+                    let mut outer_idx = Vec::new();
+                    for idx_expr in outer {
+                        let idx = self.lower_expression(idx_expr)?;
+                        let array = path[path.len() - 1];
+                        let dest = self.fresh_var();
+                        self.emit_instruction(Instruction::Load { dest, array, index: idx.clone() });
+                        path.push(dest);
+                        outer_idx.push(idx);
+                    }
+                    let idx = self.lower_expression(last)?;
                     self.emit_instruction(Instruction::Store {
-                        array: var,
+                        array: path[path.len() - 1],
                         index: idx,
                         value: val,
+                        line: Some(*line),
                     });
+                    for (i, idx) in outer_idx.into_iter().enumerate().rev() {
+                        self.emit_instruction(Instruction::Store {
+                            array: path[i],
+                            index: idx,
+                            value: Value::Var(path[i + 1]),
+                            line: None,
+                        });
+                    }
                 } else {
-                    self.emit_instruction(Instruction::Assign { dest: var, value: val });
+                    // SSA: every assignment defines a new version of the
+                    // variable, keeping the type of the first
+                    let dest = self.fresh_var();
+                    if let Some(ty) = self.var_types.get(&var).cloned() {
+                        self.var_types.insert(dest, ty);
+                    }
+                    self.var_map.insert(target.clone(), dest);
+                    self.emit_instruction(Instruction::Assign { dest, value: val });
                 }
                 Ok(())
             }
-            ast::Statement::Return(expr) => {
-                let val = self.lower_expression(expr)?;
-                if let Some(block) = &mut self.current_block {
-                    block.terminator = Terminator::Return(val);
+            ast::StatementKind::Return(expr) => {
+                // `return;` in a void function carries a placeholder value
+                if self.current_return == IRType::Void {
+                    self.finish_block(Terminator::ReturnVoid);
+                    return Ok(());
                 }
+                let val = self.lower_expression(expr)?;
+                self.finish_block(Terminator::Return(val));
                 Ok(())
             }
-            ast::Statement::Expression(expr) => {
+            ast::StatementKind::Expression(expr) => {
                 self.lower_expression(expr)?;
                 Ok(())
             }
-            ast::Statement::For {
+            ast::StatementKind::For {
                 var,
                 start,
                 end,
@@ -149,83 +287,316 @@ impl Lowerer {
                 let start_val = self.lower_expression(start)?;
                 let end_val = self.lower_expression(end)?;
 
-                // Extract constant values for unrolling
+                // Extract constant values for unrolling. Loops that use
+                // break/continue need real control flow and are never unrolled,
+                // nor are loops longer than --max-unroll.
                 if let (Value::Int(start_int), Value::Int(end_int)) = (&start_val, &end_val) {
-                    // Unroll loop iterations
-                    for i in *start_int..*end_int {
-                        // Create new loop variable for this iteration
-                        let loop_var = self.fresh_var();
-                        self.var_map.insert(var.clone(), loop_var);
-                        self.emit_instruction(Instruction::Assign {
-                            dest: loop_var,
-                            value: Value::Int(i),
-                        });
-
-                        // Lower body for this iteration
-                        for stmt in body {
-                            self.lower_statement(stmt)?;
-                        }
+                    let max_unroll = self.max_unroll;
+                    let short = end_int.saturating_sub(*start_int) <= max_unroll as i64;
+                    if !short {
+                        info!(
+                            "Loop over {}..{} exceeds --max-unroll {}; lowering it as a loop",
+                            start_int, end_int, max_unroll
+                        );
                     }
-                } else {
-                    // Fallback: single iteration with start value
-                    let loop_var = self.fresh_var();
-                    self.var_map.insert(var.clone(), loop_var);
-                    self.emit_instruction(Instruction::Assign {
-                        dest: loop_var,
-                        value: start_val,
-                    });
+                    if short && !contains_loop_control(body) {
+                        // Unroll loop iterations
+                        for i in *start_int..*end_int {
+                            // Create new loop variable for this iteration
+                            let loop_var = self.fresh_var();
+                            self.var_map.insert(var.clone(), loop_var);
+                            self.emit_instruction(Instruction::Assign {
+                                dest: loop_var,
+                                value: Value::Int(i),
+                            });
 
-                    for stmt in body {
-                        self.lower_statement(stmt)?;
+                            // Lower body for this iteration
+                            for stmt in body {
+                                self.lower_statement(stmt)?;
+                            }
+                        }
+                        return Ok(());
                     }
                 }
 
-                Ok(())
+                self.lower_loop(var, start_val, end_val, body)
             }
-            ast::Statement::If {
+            ast::StatementKind::If {
                 condition,
                 then_body,
                 else_body,
             } => {
-                let _cond = self.lower_expression(condition)?;
+                // Lowered even when both arms are empty: a call in the
+                // condition keeps its side effects (DCE never removes calls)
+                let cond = self.lower_expression(condition)?;
+
+                let id = self.fresh_label_id();
+                let then_label = format!("if_then_{}", id);
+                let else_label = format!("if_else_{}", id);
+                let merge_label = format!("if_merge_{}", id);
+
+                // Without an else, the branch itself is an edge into the merge
+                let before = self.var_map.clone();
+                let branch_edge = self.edge();
+                self.finish_block(Terminator::Branch {
+                    condition: cond,
+                    true_label: then_label.clone(),
+                    false_label: if else_body.is_some() {
+                        else_label.clone()
+                    } else {
+                        merge_label.clone()
+                    },
+                });
 
-                // For now, simplified (not creating separate blocks)
+                let mut incoming = Vec::new();
+                self.start_block(then_label);
                 for stmt in then_body {
                     self.lower_statement(stmt)?;
                 }
+                incoming.extend(self.edge());
+                self.finish_block(Terminator::Jump(merge_label.clone()));
+                self.var_map = before.clone();
 
                 if let Some(else_stmts) = else_body {
+                    self.start_block(else_label);
                     for stmt in else_stmts {
                         self.lower_statement(stmt)?;
                     }
+                    incoming.extend(self.edge());
+                    self.finish_block(Terminator::Jump(merge_label.clone()));
+                    self.var_map = before;
+                } else {
+                    incoming.extend(branch_edge);
                 }
 
+                // Both arms returned (or left the loop): nothing reaches the merge
+                if !incoming.is_empty() {
+                    self.start_block(merge_label);
+                    self.merge_versions(&incoming);
+                }
+                Ok(())
+            }
+            ast::StatementKind::Break => {
+                let edge = self.edge();
+                let targets = self
+                    .loop_stack
+                    .last_mut()
+                    .ok_or_else(|| anyhow::anyhow!("`break` outside of loop"))?;
+                targets.break_edges.extend(edge);
+                let target = targets.break_label.clone();
+                self.finish_block(Terminator::Jump(target));
+                Ok(())
+            }
+            ast::StatementKind::Continue => {
+                let edge = self.edge();
+                let targets = self
+                    .loop_stack
+                    .last_mut()
+                    .ok_or_else(|| anyhow::anyhow!("`continue` outside of loop"))?;
+                targets.continue_edges.extend(edge);
+                let target = targets.continue_label.clone();
+                self.finish_block(Terminator::Jump(target));
                 Ok(())
             }
         }
     }
 
+    // Real loop lowering: header (bound check) → body → latch (increment) → header
+    //
+    //   current:  jump header
+    //   header:   i = phi [start, current], [t, latch]; c = lt i end; br c, body, exit
+    //   body:     ...; jump latch        (continue → latch, break → exit)
+    //   latch:    t = add i 1; jump header
+    //   exit:
+    //
+    // Every variable the body reassigns gets a header phi like `i`'s; its
+    // back-edge operand is filled in once the latch has been lowered.
+    fn lower_loop(
+        &mut self,
+        var: &str,
+        start_val: Value,
+        end_val: Value,
+        body: &[ast::Statement],
+    ) -> Result<()> {
+        let id = self.fresh_label_id();
+        let header_label = format!("for_header_{}", id);
+        let body_label = format!("for_body_{}", id);
+        let latch_label = format!("for_latch_{}", id);
+        let exit_label = format!("for_exit_{}", id);
+
+        let mut carried = Vec::new();
+        assigned_names(body, &mut carried);
+        carried.retain(|name| name != var && self.var_map.contains_key(name));
+        carried.sort();
+        carried.dedup();
+
+        let entry_label = self.current_label();
+        self.finish_block(Terminator::Jump(header_label.clone()));
+
+        // Header: phis for the loop variable and carried variables, then
+        // loop while i < end
+        self.start_block(header_label.clone());
+        let loop_var = self.fresh_var();
+        self.emit_instruction(Instruction::Phi {
+            dest: loop_var,
+            incoming: vec![(start_val, entry_label.clone())],
+        });
+        self.var_map.insert(var.to_string(), loop_var);
+        let mut header_phis = vec![(var.to_string(), loop_var)];
+        for name in carried {
+            let entry = self.var_map[&name];
+            let dest = self.fresh_var();
+            if let Some(ty) = self.var_types.get(&entry).cloned() {
+                self.var_types.insert(dest, ty);
+            }
+            self.emit_instruction(Instruction::Phi {
+                dest,
+                incoming: vec![(Value::Var(entry), entry_label.clone())],
+            });
+            self.var_map.insert(name.clone(), dest);
+            header_phis.push((name, dest));
+        }
+        let cond = self.fresh_var();
+        self.emit_instruction(Instruction::BinaryOp {
+            dest: cond,
+            op: BinOp::Lt,
+            left: Value::Var(loop_var),
+            right: end_val,
+        });
+        let header_vars = self.var_map.clone();
+        let header_edge = self.edge();
+        self.finish_block(Terminator::Branch {
+            condition: Value::Var(cond),
+            true_label: body_label.clone(),
+            false_label: exit_label.clone(),
+        });
+
+        // Body
+        self.start_block(body_label);
+        self.loop_stack.push(LoopTargets {
+            continue_label: latch_label.clone(),
+            break_label: exit_label.clone(),
+            continue_edges: Vec::new(),
+            break_edges: Vec::new(),
+        });
+        for stmt in body {
+            self.lower_statement(stmt)?;
+        }
+        let targets = self.loop_stack.pop().expect("loop targets pushed above");
+        let mut latch_incoming: Vec<Edge> = self.edge().into_iter().collect();
+        latch_incoming.extend(targets.continue_edges);
+        self.finish_block(Terminator::Jump(latch_label.clone()));
+
+        // A body that always returns or breaks never reaches the latch: no
+        // increment and no back edge, so the header runs at most once
+        if !latch_incoming.is_empty() {
+            // Latch: i = i + 1
+            self.start_block(latch_label.clone());
+            self.var_map = header_vars.clone();
+            self.merge_versions(&latch_incoming);
+            let next = self.fresh_var();
+            self.emit_instruction(Instruction::BinaryOp {
+                dest: next,
+                op: BinOp::Add,
+                left: Value::Var(loop_var),
+                right: Value::Int(1),
+            });
+            self.var_map.insert(var.to_string(), next);
+            self.finish_block(Terminator::Jump(header_label.clone()));
+
+            // Back edge: complete the header phis with the latch's versions
+            if let Some(header) = self.blocks.iter_mut().find(|b| b.label == header_label) {
+                for inst in &mut header.instructions {
+                    if let Instruction::Phi { dest, incoming } = inst {
+                        if let Some((name, _)) = header_phis.iter().find(|(_, phi)| phi == dest) {
+                            incoming.push((Value::Var(self.var_map[name]), latch_label.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Exit: reached when the bound check fails or on `break`
+        self.start_block(exit_label);
+        self.var_map = header_vars;
+        let mut exit_incoming: Vec<Edge> = header_edge.into_iter().collect();
+        exit_incoming.extend(targets.break_edges);
+        self.merge_versions(&exit_incoming);
+        Ok(())
+    }
+
     fn lower_expression(&mut self, expr: &ast::Expression) -> Result<Value> {
         match expr {
             ast::Expression::IntLiteral(n) => Ok(Value::Int(*n)),
             ast::Expression::FloatLiteral(f) => Ok(Value::Float(*f)),
             ast::Expression::BoolLiteral(b) => Ok(Value::Bool(*b)),
+            ast::Expression::StringLiteral(s) => Ok(Value::Str(s.clone())),
             ast::Expression::Variable(name) => {
-                let var = *self
-                    .var_map
+                // Locals shadow module-level constants
+                if let Some(var) = self.var_map.get(name) {
+                    return Ok(Value::Var(*var));
+                }
+                self.constants
                     .get(name)
-                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))?;
-                Ok(Value::Var(var))
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))
             }
             ast::Expression::ArrayLiteral(elements) => {
                 let values: Result<Vec<Value>> =
                     elements.iter().map(|e| self.lower_expression(e)).collect();
                 Ok(Value::Array(values?))
             }
+            ast::Expression::ArrayRepeat { value, count } => {
+                // The value is computed once and its result repeated
+                let value = self.lower_expression(value)?;
+                if *count == 0 {
+                    // No element to read the type from: like zeros(0), an
+                    // empty array of the value's type
+                    let dest = self.fresh_var();
+                    if let Some(elem) = self.value_type(&value) {
+                        self.var_types.insert(dest, IRType::Array(Box::new(elem), Some(0)));
+                    }
+                    self.emit_instruction(Instruction::Assign { dest, value: Value::Array(Vec::new()) });
+                    return Ok(Value::Var(dest));
+                }
+                Ok(Value::Array(vec![value; *count]))
+            }
+            ast::Expression::TupleLiteral(elements) => {
+                let values: Result<Vec<Value>> =
+                    elements.iter().map(|e| self.lower_expression(e)).collect();
+                Ok(Value::Tuple(values?))
+            }
             ast::Expression::Index { array, index } => {
                 let arr_val = self.lower_expression(array)?;
                 let idx_val = self.lower_expression(index)?;
 
+                // A tuple literal indexed directly is just its element
+                if let (Value::Tuple(elements), Value::Int(i)) = (&arr_val, &idx_val) {
+                    return Ok(elements[*i as usize].clone());
+                }
+
+                // An element of a qalloc register is a plain qubit index
+                let register = match &arr_val {
+                    Value::Var(var) => self.qubit_registers.get(var).copied(),
+                    _ => None,
+                };
+                if let Some((first, size)) = register {
+                    return match idx_val {
+                        Value::Int(i) if (0..size).contains(&i) => Ok(Value::Int(first + i)),
+                        Value::Int(i) => anyhow::bail!("qubit {} is out of range for a register of {} qubits", i, size),
+                        index => {
+                            let dest = self.fresh_var();
+                            self.emit_instruction(Instruction::BinaryOp {
+                                dest,
+                                op: BinOp::Add,
+                                left: Value::Int(first),
+                                right: index,
+                            });
+                            Ok(Value::Var(dest))
+                        }
+                    };
+                }
+
                 // Extract the SSAVar from array
                 if let Value::Var(arr_var) = arr_val {
                     let dest = self.fresh_var();
@@ -239,6 +610,11 @@ impl Lowerer {
                     anyhow::bail!("Array indexing requires variable")
                 }
             }
+            ast::Expression::Binary {
+                op: op @ (ast::BinaryOp::And | ast::BinaryOp::Or),
+                left,
+                right,
+            } => self.lower_short_circuit(*op, left, right),
             ast::Expression::Binary { op, left, right } => {
                 let left_val = self.lower_expression(left)?;
                 let right_val = self.lower_expression(right)?;
@@ -254,6 +630,15 @@ impl Lowerer {
 
                 Ok(Value::Var(dest))
             }
+            // Negative Literals: -1.57 is a constant, not a runtime negation, so
+            // gate angles like rz(0, -3.14) reach the backends as literals
+            ast::Expression::Unary { op: ast::UnaryOp::Neg, operand } if matches!(**operand, ast::Expression::IntLiteral(_) | ast::Expression::FloatLiteral(_)) => {
+                match **operand {
+                    ast::Expression::IntLiteral(n) => Ok(Value::Int(n.wrapping_neg())),
+                    ast::Expression::FloatLiteral(f) => Ok(Value::Float(-f)),
+                    _ => unreachable!(),
+                }
+            }
             ast::Expression::Unary { op, operand } => {
                 let operand_val = self.lower_expression(operand)?;
                 let dest = self.fresh_var();
@@ -267,19 +652,79 @@ impl Lowerer {
 
                 Ok(Value::Var(dest))
             }
-            ast::Expression::Call { function, args } => {
+            ast::Expression::Call { function, args, .. } if function == "amplitude_encode" => {
+                // Explicit amplitude encoding: load the array as the state's amplitudes
+                let source = self.lower_expression(&args[0])?;
+                let dest = self.fresh_var();
+                self.emit_instruction(Instruction::DomainConversion {
+                    dest,
+                    source,
+                    from_domain: self.current_domain.clone(),
+                    to_domain: ast::Domain::Quantum,
+                    encoding: ConversionEncoding::AmplitudeEncoding,
+                });
+                Ok(Value::Var(dest))
+            }
+            ast::Expression::Call { function, args, line } => {
                 let arg_vals: Result<Vec<Value>> =
                     args.iter().map(|a| self.lower_expression(a)).collect();
                 let arg_vals = arg_vals?;
 
+                // len of a statically-sized array is just its size
+                if function == "len" && !self.function_domains.contains_key(function) {
+                    if let [array] = arg_vals.as_slice() {
+                        if let Some(IRType::Array(_, Some(n))) = self.value_type(array) {
+                            return Ok(Value::Int(n as i64));
+                        }
+                    }
+                }
+
+                // qalloc(n): the register is the next n qubit indices of the circuit
+                if let ("qalloc", [Value::Int(n)]) = (function.as_str(), arg_vals.as_slice()) {
+                    if !self.function_domains.contains_key(function) {
+                        // Checked: a qalloc in an unrolled loop allocates once per iteration
+                        let end = match self.next_qubit.checked_add(*n) {
+                            Some(end) if end <= MAX_QUBITS => end,
+                            _ => anyhow::bail!(
+                                "qalloc({}) allocates past the limit of {} qubits ({} already allocated in this function)",
+                                n,
+                                MAX_QUBITS,
+                                self.next_qubit
+                            ),
+                        };
+                        let register = (self.next_qubit..end).map(Value::Int).collect();
+                        self.next_qubit = end;
+                        return Ok(Value::Array(register));
+                    }
+                }
+
+                // to_float / to_int are conversions, not calls
+                if let ("to_float" | "to_int", [value]) = (function.as_str(), arg_vals.as_slice()) {
+                    if !self.function_domains.contains_key(function) {
+                        let to = if function == "to_float" { IRType::Float } else { IRType::Int };
+                        let dest = self.fresh_var();
+                        self.emit_instruction(Instruction::Cast { dest, value: value.clone(), to });
+                        return Ok(Value::Var(dest));
+                    }
+                }
+
                 // Built-in functions - don't convert
                 let builtin_quantum_fns = [
-                    "h", "x", "y", "z", "rx", "ry", "rz",
-                    "cx", "cnot", "cz", "measure"
+                    "h", "x", "y", "z", "rx", "ry", "rz", "u", "p",
+                    "cx", "cnot", "cz", "measure", "measure_all", "reset", "barrier"
                 ];
-                let builtin_io_fns = ["print", "print_float", "print_array"];
-                let is_builtin = builtin_quantum_fns.contains(&function.as_str())
-                    || builtin_io_fns.contains(&function.as_str());
+                let builtin_io_fns = ["print", "print_int", "print_float", "print_bool", "print_array", "print_fmt", "assert", "complex", "zeros", "ones", "range", "matmul", "len", "sum", "product", "dot", "atomic_add", "atomic_max"];
+                // A user-defined function shadows a builtin of the same name, so
+                // its declared domain decides whether arguments are converted
+                let is_builtin = !self.function_domains.contains_key(function)
+                    && (builtin_quantum_fns.contains(&function.as_str())
+                        || builtin_io_fns.contains(&function.as_str()));
+
+                // Callees come from the first pass over the module; anything
+                // else would lower to a call with a guessed domain
+                if !is_builtin && !self.function_domains.contains_key(function) {
+                    anyhow::bail!("Undefined function: {}", function);
+                }
 
                 // Check if this is a cross-domain call
                 let target_domain = self.function_domains.get(function)
@@ -288,8 +733,8 @@ impl Lowerer {
 
                 // If cross-domain (and not builtin), convert arguments
                 let converted_args = if !is_builtin && self.current_domain != target_domain {
-                    eprintln!(
-                        "INFO: Inserting conversion for {:?} → {:?} call to '{}'",
+                    info!(
+                        "Inserting conversion for {:?} → {:?} call to '{}'",
                         self.current_domain, target_domain, function
                     );
 
@@ -327,8 +772,40 @@ impl Lowerer {
                     dest: Some(dest),
                     function: function.clone(),
                     args: converted_args,
+                    line: Some(*line),
+                });
+
+                Ok(Value::Var(dest))
+            }
+            ast::Expression::If { condition, then_body, then_value, else_body, else_value } => {
+                // Like the statement form, with a phi at the merge picking the
+                // value of the arm that ran
+                let cond = self.lower_expression(condition)?;
+
+                let id = self.fresh_label_id();
+                let then_label = format!("if_then_{}", id);
+                let else_label = format!("if_else_{}", id);
+                let merge_label = format!("if_merge_{}", id);
+
+                self.finish_block(Terminator::Branch {
+                    condition: cond,
+                    true_label: then_label.clone(),
+                    false_label: else_label.clone(),
                 });
+                let before = self.var_map.clone();
+                let (then_incoming, then_edge) =
+                    self.lower_value_arm(then_label, then_body, then_value, &merge_label)?;
+                self.var_map = before;
+                let (else_incoming, else_edge) =
+                    self.lower_value_arm(else_label, else_body, else_value, &merge_label)?;
 
+                self.start_block(merge_label);
+                self.merge_versions(&[then_edge, else_edge]);
+                let dest = self.fresh_var();
+                self.emit_instruction(Instruction::Phi {
+                    dest,
+                    incoming: vec![then_incoming, else_incoming],
+                });
                 Ok(Value::Var(dest))
             }
             ast::Expression::Map { function, array } => {
@@ -340,18 +817,264 @@ impl Lowerer {
                     dest: Some(dest),
                     function: format!("map_{}", function),
                     args: vec![arr_val],
+                    line: None,
                 });
                 Ok(Value::Var(dest))
             }
         }
     }
 
+    // Constant Folding: evaluate a `const` initializer to a literal value
+    fn eval_const(&self, expr: &ast::Expression) -> Result<Value> {
+        let value = match expr {
+            ast::Expression::IntLiteral(n) => Value::Int(*n),
+            ast::Expression::FloatLiteral(f) => Value::Float(*f),
+            ast::Expression::BoolLiteral(b) => Value::Bool(*b),
+            ast::Expression::Variable(name) => self
+                .constants
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Undefined constant: {}", name))?,
+            ast::Expression::Unary { op, operand } => fold_unary((*op).into(), &self.eval_const(operand)?)
+                .map_err(|e| match e {
+                    FoldError::NotConstant => anyhow::anyhow!("Invalid operand in constant expression"),
+                    e => anyhow::anyhow!("{}", e),
+                })?,
+            ast::Expression::Binary { op, left, right } => {
+                fold_binary((*op).into(), &self.eval_const(left)?, &self.eval_const(right)?)
+                    .map_err(|e| anyhow::anyhow!("{}", e))?
+            }
+            _ => anyhow::bail!("Constant initializer is not a compile-time expression"),
+        };
+        Ok(value)
+    }
+
+    // Short-Circuit Evaluation: `a && b` only evaluates b when a is true,
+    // `a || b` only when a is false. The result is a phi at the merge block:
+    // the constant decided by the left operand, or the right operand's value.
+    fn lower_short_circuit(
+        &mut self,
+        op: ast::BinaryOp,
+        left: &ast::Expression,
+        right: &ast::Expression,
+    ) -> Result<Value> {
+        let is_and = op == ast::BinaryOp::And;
+        let left_val = self.lower_expression(left)?;
+        let left_label = self.current_label();
+        let left_edge = self.edge();
+
+        let id = self.fresh_label_id();
+        let prefix = if is_and { "and" } else { "or" };
+        let rhs_label = format!("{}_rhs_{}", prefix, id);
+        let merge_label = format!("{}_merge_{}", prefix, id);
+
+        self.finish_block(Terminator::Branch {
+            condition: left_val,
+            true_label: if is_and { rhs_label.clone() } else { merge_label.clone() },
+            false_label: if is_and { merge_label.clone() } else { rhs_label.clone() },
+        });
+
+        // The right operand may itself branch, so the phi's incoming edge is
+        // whichever block it finishes in
+        self.start_block(rhs_label);
+        let right_val = self.lower_expression(right)?;
+        let right_label = self.current_label();
+        let right_edge = self.edge();
+        self.finish_block(Terminator::Jump(merge_label.clone()));
+
+        self.start_block(merge_label);
+        let incoming: Vec<Edge> = left_edge.into_iter().chain(right_edge).collect();
+        self.merge_versions(&incoming);
+        let dest = self.fresh_var();
+        self.emit_instruction(Instruction::Phi {
+            dest,
+            incoming: vec![(Value::Bool(!is_and), left_label), (right_val, right_label)],
+        });
+        Ok(Value::Var(dest))
+    }
+
+    // One arm of an `if` expression: its statements, then its value. Returns
+    // the value with the block it was computed in (the phi's incoming edge),
+    // and the edge into the merge.
+    fn lower_value_arm(
+        &mut self,
+        label: String,
+        body: &[ast::Statement],
+        value: &ast::Expression,
+        merge_label: &str,
+    ) -> Result<((Value, String), Edge)> {
+        self.start_block(label);
+        for stmt in body {
+            self.lower_statement(stmt)?;
+        }
+        if self.current_block.is_none() {
+            anyhow::bail!("A branch of an `if` used as a value cannot return, break or continue");
+        }
+        let value = self.lower_expression(value)?;
+        let edge = self.edge().expect("arm block is open");
+        self.finish_block(Terminator::Jump(merge_label.to_string()));
+        Ok(((value, edge.0.clone()), edge))
+    }
+
+    // The edge leaving the current block, or None once it has been closed
+    // (after return/break/continue, nothing flows on from here)
+    fn edge(&self) -> Option<Edge> {
+        self.current_block
+            .as_ref()
+            .map(|b| (b.label.clone(), self.var_map.clone()))
+    }
+
+    // SSA Merge: at the start of a join block, a variable whose version
+    // differs between incoming edges gets a phi choosing the version of the
+    // edge taken. A variable bound in only some predecessors (a `let` in one
+    // arm) keeps the last one. With no incoming edges the join is
+    // unreachable and the versions are left alone.
+    fn merge_versions(&mut self, incoming: &[Edge]) {
+        if incoming.is_empty() {
+            return;
+        }
+        let mut names: Vec<&String> = incoming.iter().flat_map(|(_, vars)| vars.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        let mut merged = HashMap::new();
+        for name in names {
+            let versions: Vec<(SSAVar, &String)> = incoming
+                .iter()
+                .filter_map(|(label, vars)| vars.get(name).map(|var| (*var, label)))
+                .collect();
+            let (last, _) = versions[versions.len() - 1];
+            if versions.len() < incoming.len() || versions.iter().all(|(var, _)| *var == last) {
+                merged.insert(name.clone(), last);
+                continue;
+            }
+            let dest = self.fresh_var();
+            if let Some(ty) = self.var_types.get(&last).cloned() {
+                self.var_types.insert(dest, ty);
+            }
+            self.emit_instruction(Instruction::Phi {
+                dest,
+                incoming: versions.iter().map(|(var, label)| (Value::Var(*var), (*label).clone())).collect(),
+            });
+            merged.insert(name.clone(), dest);
+        }
+        self.var_map = merged;
+    }
+
+    fn current_label(&self) -> String {
+        self.current_block
+            .as_ref()
+            .map(|b| b.label.clone())
+            .unwrap_or_default()
+    }
+
     fn emit_instruction(&mut self, inst: Instruction) {
+        // Record the type of the defined variable (a declared `let` type or
+        // an earlier definition of a reassigned variable takes precedence)
+        if let Some((dest, ty)) = self.infer_instruction_type(&inst) {
+            self.var_types.entry(dest).or_insert(ty);
+        }
         if let Some(block) = &mut self.current_block {
             block.instructions.push(inst);
         }
     }
 
+    fn infer_instruction_type(&self, inst: &Instruction) -> Option<(SSAVar, IRType)> {
+        match inst {
+            Instruction::Assign { dest, value } => Some((*dest, self.value_type(value)?)),
+            Instruction::BinaryOp { dest, op, left, right } => {
+                let ty = match op {
+                    BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge
+                    | BinOp::And | BinOp::Or => IRType::Bool,
+                    // A variable operand carries any explicit width (2.0 * x: float32)
+                    _ => match (left, right) {
+                        (Value::Var(_), _) | (_, Value::Array(_)) => self.value_type(left)?,
+                        _ => self.value_type(right).or_else(|| self.value_type(left))?,
+                    },
+                };
+                Some((*dest, ty))
+            }
+            Instruction::UnaryOp { dest, op, operand } => {
+                let ty = match op {
+                    UnOp::Not => IRType::Bool,
+                    UnOp::Neg => self.value_type(operand)?,
+                };
+                Some((*dest, ty))
+            }
+            Instruction::Cast { dest, to, .. } => Some((*dest, to.clone())),
+            Instruction::Load { dest, array, index } => match self.var_types.get(array)? {
+                IRType::Array(elem, _) => Some((*dest, (**elem).clone())),
+                IRType::Tuple(elems) => match index {
+                    Value::Int(i) => Some((*dest, elems.get(*i as usize)?.clone())),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Instruction::Call {
+                dest: Some(dest),
+                function,
+                args,
+                ..
+            } => {
+                let ty = if let Some(ty) = self.function_returns.get(function) {
+                    ty.clone()
+                } else if let ("zeros" | "ones" | "range", Some(Value::Int(n))) = (function.as_str(), args.first()) {
+                    let elem = if function == "range" { IRType::Int } else { IRType::Float };
+                    IRType::Array(Box::new(elem), Some(*n as usize))
+                } else if let ("matmul", [a, b]) = (function.as_str(), args.as_slice()) {
+                    // (R x K) * (K x C) -> (R x C): a's row count, b's row type
+                    match (self.value_type(a)?, self.value_type(b)?) {
+                        (IRType::Array(_, rows), IRType::Array(b_row, _)) => IRType::Array(b_row, rows),
+                        _ => return None,
+                    }
+                } else if let ("sum" | "product", [array]) = (function.as_str(), args.as_slice()) {
+                    // A reduction has its array's element type
+                    match self.value_type(array)? {
+                        IRType::Array(elem, _) => *elem,
+                        _ => return None,
+                    }
+                } else if let Some(mapped) = function.strip_prefix("map_") {
+                    // map(f, arr) is lowered to a call to map_f
+                    let elem = self.function_returns.get(mapped)?.clone();
+                    let size = match args.first().and_then(|a| self.value_type(a)) {
+                        Some(IRType::Array(_, size)) => size,
+                        _ => None,
+                    };
+                    IRType::Array(Box::new(elem), size)
+                } else {
+                    builtin_return_type(function)
+                };
+                Some((*dest, ty))
+            }
+            Instruction::Call { dest: None, .. } | Instruction::Store { .. } => None,
+            Instruction::Phi { dest, incoming } => {
+                let (first, _) = incoming.first()?;
+                Some((*dest, self.value_type(first)?))
+            }
+            Instruction::DomainConversion { dest, source, .. } => {
+                Some((*dest, self.value_type(source)?))
+            }
+        }
+    }
+
+    fn value_type(&self, value: &Value) -> Option<IRType> {
+        match value {
+            Value::Int(_) => Some(IRType::Int),
+            Value::Float(_) => Some(IRType::Float),
+            Value::Bool(_) => Some(IRType::Bool),
+            Value::Var(v) => self.var_types.get(v).cloned(),
+            Value::Array(elements) => {
+                let elem = elements.first().and_then(|e| self.value_type(e))?;
+                Some(IRType::Array(Box::new(elem), Some(elements.len())))
+            }
+            Value::Tuple(elements) => {
+                let elems: Option<Vec<IRType>> = elements.iter().map(|e| self.value_type(e)).collect();
+                Some(IRType::Tuple(elems?))
+            }
+            Value::Str(_) => None,
+        }
+    }
+
     fn convert_type(&self, ty: &ast::Type) -> IRType {
         match ty {
             ast::Type::Int => IRType::Int,
@@ -362,11 +1085,24 @@ impl Lowerer {
             }
             ast::Type::Qubit => IRType::Qubit,
             ast::Type::Void => IRType::Void,
+            ast::Type::Complex => IRType::Complex,
+            ast::Type::Int32 => IRType::Int32,
+            ast::Type::Int64 => IRType::Int64,
+            ast::Type::Float32 => IRType::Float32,
+            ast::Type::Float64 => IRType::Float64,
             ast::Type::Tensor(elem) => {
                 // For now, treat tensor<T> as array<T> in IR
                 // Later we'll add proper IR support for tensors
                 IRType::Array(Box::new(self.convert_type(elem)), None)
             }
+            ast::Type::Matrix(elem, dims) => {
+                // matrix<T, R, C> is an array of R rows of C elements
+                let row = IRType::Array(Box::new(self.convert_type(elem)), dims.map(|(_, cols)| cols));
+                IRType::Array(Box::new(row), dims.map(|(rows, _)| rows))
+            }
+            ast::Type::Tuple(elems) => {
+                IRType::Tuple(elems.iter().map(|e| self.convert_type(e)).collect())
+            }
             ast::Type::QState => {
                 // For now, treat qstate as opaque type
                 // Later we'll add proper IR support
@@ -376,33 +1112,122 @@ impl Lowerer {
     }
 
     fn convert_binop(&self, op: ast::BinaryOp) -> BinOp {
-        match op {
-            ast::BinaryOp::Add => BinOp::Add,
-            ast::BinaryOp::Sub => BinOp::Sub,
-            ast::BinaryOp::Mul => BinOp::Mul,
-            ast::BinaryOp::Div => BinOp::Div,
-            ast::BinaryOp::Mod => BinOp::Mod,
-            ast::BinaryOp::Eq => BinOp::Eq,
-            ast::BinaryOp::Ne => BinOp::Ne,
-            ast::BinaryOp::Lt => BinOp::Lt,
-            ast::BinaryOp::Le => BinOp::Le,
-            ast::BinaryOp::Gt => BinOp::Gt,
-            ast::BinaryOp::Ge => BinOp::Ge,
-            ast::BinaryOp::And => BinOp::And,
-            ast::BinaryOp::Or => BinOp::Or,
-        }
+        op.into()
     }
 
     fn convert_unop(&self, op: ast::UnaryOp) -> UnOp {
-        match op {
-            ast::UnaryOp::Neg => UnOp::Neg,
-            ast::UnaryOp::Not => UnOp::Not,
+        op.into()
+    }
+}
+
+
+// Return types of builtins that have no user-visible definition
+fn builtin_return_type(function: &str) -> IRType {
+    match function {
+        "print" | "print_int" | "print_float" | "print_bool" | "print_array" | "print_fmt" | "assert"
+        | "barrier" => IRType::Void,
+        "complex" => IRType::Complex,
+        "dot" => IRType::Float,
+        "measure_all" => IRType::Array(Box::new(IRType::Int), None),
+        // Quantum gates and measurement return an int
+        _ => IRType::Int,
+    }
+}
+
+// Names reassigned (`x = ...`, not `x[i] = ...`) anywhere in a loop body,
+// including nested blocks and the arms of `if` expressions
+fn assigned_names(body: &[ast::Statement], names: &mut Vec<String>) {
+    for stmt in body {
+        match &stmt.kind {
+            ast::StatementKind::Let { value, .. } | ast::StatementKind::Return(value) | ast::StatementKind::Expression(value) => {
+                assigned_in_expression(value, names)
+            }
+            ast::StatementKind::Assign { target, indices, value, .. } => {
+                if indices.is_empty() {
+                    names.push(target.clone());
+                }
+                assigned_in_expression(value, names);
+            }
+            ast::StatementKind::For { start, end, body, .. } => {
+                assigned_in_expression(start, names);
+                assigned_in_expression(end, names);
+                assigned_names(body, names);
+            }
+            ast::StatementKind::If { condition, then_body, else_body } => {
+                assigned_in_expression(condition, names);
+                assigned_names(then_body, names);
+                assigned_names(else_body.as_deref().unwrap_or_default(), names);
+            }
+            ast::StatementKind::Break | ast::StatementKind::Continue => {}
+        }
+    }
+}
+
+fn assigned_in_expression(expr: &ast::Expression, names: &mut Vec<String>) {
+    match expr {
+        ast::Expression::If { condition, then_body, then_value, else_body, else_value } => {
+            assigned_in_expression(condition, names);
+            assigned_names(then_body, names);
+            assigned_in_expression(then_value, names);
+            assigned_names(else_body, names);
+            assigned_in_expression(else_value, names);
         }
+        ast::Expression::ArrayLiteral(elements) | ast::Expression::TupleLiteral(elements) => {
+            elements.iter().for_each(|e| assigned_in_expression(e, names))
+        }
+        ast::Expression::ArrayRepeat { value, .. } => assigned_in_expression(value, names),
+        ast::Expression::Call { args, .. } => args.iter().for_each(|e| assigned_in_expression(e, names)),
+        ast::Expression::Index { array, index } => {
+            assigned_in_expression(array, names);
+            assigned_in_expression(index, names);
+        }
+        ast::Expression::Binary { left, right, .. } => {
+            assigned_in_expression(left, names);
+            assigned_in_expression(right, names);
+        }
+        ast::Expression::Unary { operand, .. } => assigned_in_expression(operand, names),
+        ast::Expression::Map { array, .. } => assigned_in_expression(array, names),
+        ast::Expression::IntLiteral(_)
+        | ast::Expression::FloatLiteral(_)
+        | ast::Expression::BoolLiteral(_)
+        | ast::Expression::StringLiteral(_)
+        | ast::Expression::Variable(_) => {}
     }
 }
 
-pub fn lower_to_ir(program: &ast::Program) -> Result<Module> {
-    let mut lowerer = Lowerer::new();
+// Does this loop body contain a `break`/`continue` that targets it?
+// Nested loops own their own break/continue, so they are not searched.
+fn contains_loop_control(body: &[ast::Statement]) -> bool {
+    body.iter().any(|stmt| match &stmt.kind {
+        ast::StatementKind::Break | ast::StatementKind::Continue => true,
+        ast::StatementKind::If {
+            then_body,
+            else_body,
+            ..
+        } => {
+            contains_loop_control(then_body)
+                || else_body.as_deref().is_some_and(contains_loop_control)
+        }
+        _ => false,
+    })
+}
+
+/// Lower a type-checked program to SSA IR.
+///
+/// Each assignment defines a new variable rather than overwriting the old
+/// one, and control-flow joins merge diverging versions with phis:
+///
+/// ```
+/// let ast = quarkdsl::parse("fn main() -> int { let mut x = 1; x = x + 2; return x; }").unwrap();
+/// let module = quarkdsl::lower(&ast, &Default::default()).unwrap();
+/// assert_eq!(module.functions[0].blocks[0].label, "entry");
+/// ```
+///
+/// A `return` inside a loop ends its block with the return itself, leaving
+/// the loop rather than jumping back to the header; statements after it are
+/// dead and get no blocks.
+pub fn lower_to_ir(program: &ast::Program, options: &Options) -> Result<Module> {
+    let mut lowerer = Lowerer::new(options);
     lowerer.lower_module(program)
 }
 
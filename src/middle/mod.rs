@@ -2,8 +2,19 @@ pub mod ir;
 mod lower;
 mod optimize;
 mod dump;
+mod verify;
+mod analysis;
+mod phi;
+mod parse;
+mod fold;
+mod options;
 
 pub use lower::lower_to_ir;
-pub use optimize::optimize;
-pub use dump::dump_ir;
+pub use options::{Options, DEFAULT_MAX_UNROLL};
+pub use optimize::{inline_quantum_calls, optimize, pass_names, resolve_constants};
+pub use dump::{dump_cfg_dot, dump_inst, dump_ir, dump_ir_typed};
+pub use verify::verify;
+pub use phi::{eliminate_function_phis, eliminate_phis};
+pub use parse::parse_ir;
+pub(crate) use fold::{fold_binary, fold_unary, FoldError};
 
@@ -2,48 +2,419 @@
 // Dataflow Analysis: Analyze how data flows through the program
 // SSA Form: Simplifies optimization by making def-use chains explicit
 
+use super::analysis::{block_successors, compute_dominators, reachable_blocks, terminator_targets};
+use super::fold::{fold_binary, fold_unary};
 use super::ir::*;
+use super::options::Options;
+use anyhow::{bail, Result};
 use std::collections::{HashMap, HashSet};
 
-// Optimization Pipeline: Multiple passes for better results
-pub fn optimize(module: &mut Module) {
-    eprintln!("INFO: Running optimization passes...");
+// Function inlining works on the whole module before the per-function
+// pipeline, so it is selectable but not part of PIPELINE
+const INLINE_PASS: &str = "inline";
+
+/// Optimize every function of a module in place.
+///
+/// A branch whose condition folds to a constant becomes a jump, and the arm
+/// it skips is removed. Blocks joined only by a `jump` are then merged, so a
+/// function whose control flow folds away ends up as a single block.
+///
+/// Integer arithmetic that would overflow is not folded (the compiler never
+/// panics on it); the instruction is left for the target to evaluate.
+///
+/// `options.passes` restricts the run to the named passes (see
+/// [`pass_names`]); an unknown name is an error.
+pub fn optimize(module: &mut Module, options: &Options) -> Result<()> {
+    let selection = select_passes(&options.passes)?;
+    info!("Running optimization passes...");
+    if selection.as_ref().is_none_or(|passes| passes.contains(&INLINE_PASS)) {
+        crate::timing::time("inline_functions", || inline_functions(module));
+    }
+    let pipeline = selected_pipeline(&selection);
+    for func in &mut module.functions {
+        info!("Optimizing function '{}'", func.name);
+        optimize_function(func, &pipeline);
+    }
+    info!("Optimization complete");
+    Ok(())
+}
+
+// Callees with at most this many instructions are inlined
+const INLINE_THRESHOLD: usize = 20;
+
+/// Function Inlining: Replace calls to small functions with a copy of the
+/// callee's body, removing call overhead and exposing it to the caller's
+/// optimizations. Example: add(a, b) { return a + b; } ... x = add(1, 2);
+/// → x = 1 + 2;
+/// Only single-block, non-recursive callees in the caller's own domain are
+/// inlined (cross-domain calls need their DomainConversion), and quantum
+/// functions keep their own circuits.
+pub fn inline_functions(module: &mut Module) {
+    let recursive = recursive_functions(module);
+    let candidates: HashMap<String, IRFunction> = module
+        .functions
+        .iter()
+        .filter(|f| {
+            f.domain != crate::frontend::ast::Domain::Quantum
+                && f.blocks.len() == 1
+                && f.blocks[0].instructions.len() <= INLINE_THRESHOLD
+                && !recursive.contains(&f.name)
+        })
+        .map(|f| (f.name.clone(), f.clone()))
+        .collect();
+
     for func in &mut module.functions {
-        eprintln!("INFO: Optimizing function '{}'", func.name);
-        optimize_function(func);
+        // Repeat: an inlined body may itself contain inlinable calls
+        loop {
+            let site = func.blocks.iter().enumerate().find_map(|(b, block)| {
+                block.instructions.iter().position(|inst| match inst {
+                    Instruction::Call { function, .. } => candidates
+                        .get(function)
+                        .is_some_and(|callee| callee.name != func.name && callee.domain == func.domain),
+                    _ => false,
+                })
+                .map(|i| (b, i))
+            });
+            let Some((b, i)) = site else { break };
+
+            let Instruction::Call { dest, function, args, .. } = func.blocks[b].instructions[i].clone() else {
+                unreachable!()
+            };
+            info!("Inlining '{}' into '{}'", function, func.name);
+            let body = inline_body(func, &candidates[&function], dest, &args);
+            func.blocks[b].instructions.splice(i..=i, body);
+        }
+    }
+}
+
+/// Quantum Helper Inlining: a @quantum function calling another @quantum
+/// function gets a copy of the callee's gates with the parameters replaced
+/// by the call's arguments. Qiskit needs literal qubit indices, so
+/// entangle(0, 1) and entangle(2, 3) become gates on two different pairs.
+/// Run by the quantum and orchestrator backends (optimization not needed).
+pub fn inline_quantum_calls(module: &mut Module) {
+    use crate::frontend::ast::Domain;
+
+    let recursive = recursive_functions(module);
+    let helpers: HashMap<String, IRFunction> = module
+        .functions
+        .iter()
+        .filter(|f| f.domain == Domain::Quantum && f.blocks.len() == 1 && !recursive.contains(&f.name))
+        .map(|f| (f.name.clone(), f.clone()))
+        .collect();
+
+    for func in module.functions.iter_mut().filter(|f| f.domain == Domain::Quantum) {
+        loop {
+            let site = func.blocks.iter().enumerate().find_map(|(b, block)| {
+                block.instructions.iter().position(|inst| match inst {
+                    Instruction::Call { function, .. } => function != &func.name && helpers.contains_key(function),
+                    _ => false,
+                })
+                .map(|i| (b, i))
+            });
+            let Some((b, i)) = site else { break };
+
+            let Instruction::Call { dest, function, args, .. } = func.blocks[b].instructions[i].clone() else {
+                unreachable!()
+            };
+            info!("Inlining quantum helper '{}' into '{}'", function, func.name);
+            let mut body = inline_body(func, &helpers[&function], dest, &args);
+
+            // Substitute the arguments for the parameter copies, so qubit
+            // indices reach the gates as literals
+            let params: HashMap<SSAVar, Value> = body
+                .drain(..args.len())
+                .filter_map(|inst| match inst {
+                    Instruction::Assign { dest, value } => Some((dest, value)),
+                    _ => None,
+                })
+                .collect();
+            for inst in &mut body {
+                replace_value_uses(inst, &params);
+            }
+            func.blocks[b].instructions.splice(i..=i, body);
+        }
+    }
+}
+
+/// Constant Resolution: replace every use of a variable whose only
+/// definition is a constant assignment with the constant itself, folding
+/// constant operations as they appear, until nothing changes (so q = 1;
+/// r = q + 1 resolves r too). Gate calls
+/// need literal qubit indices, so the quantum and orchestrator backends run
+/// this on @quantum code whether or not -O was given.
+/// Example: q = 1; h(q); → q = 1; h(1);
+pub fn resolve_constants(func: &mut IRFunction) {
+    loop {
+        let def_counts = count_definitions(func);
+        let constants: HashMap<SSAVar, Value> = func
+            .blocks
+            .iter()
+            .flat_map(|b| b.instructions.iter())
+            .filter_map(|inst| match inst {
+                Instruction::Assign { dest, value: value @ (Value::Int(_) | Value::Float(_) | Value::Bool(_)) }
+                    if def_counts.get(dest) == Some(&1) =>
+                {
+                    Some((*dest, value.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut changed = false;
+        for block in &mut func.blocks {
+            for inst in &mut block.instructions {
+                changed |= replace_value_uses(inst, &constants);
+            }
+            changed |= replace_terminator_uses(&mut block.terminator, &constants);
+        }
+        changed |= constant_folding(func);
+        if !changed {
+            break;
+        }
+    }
+}
+
+// Copy of the callee's instructions with variables renamed into the caller.
+// Parameters become copies of the arguments and the return becomes a copy
+// into the call's destination.
+fn inline_body(
+    caller: &mut IRFunction,
+    callee: &IRFunction,
+    dest: Option<SSAVar>,
+    args: &[Value],
+) -> Vec<Instruction> {
+    let mut rename: HashMap<SSAVar, SSAVar> = HashMap::new();
+    let mut body = Vec::new();
+
+    let mut fresh = |var: SSAVar, caller: &mut IRFunction| {
+        *rename.entry(var).or_insert_with(|| {
+            let new_var = SSAVar::new(caller.next_var_id);
+            caller.next_var_id += 1;
+            if let Some(ty) = callee.var_types.get(&var) {
+                caller.var_types.insert(new_var, ty.clone());
+            }
+            new_var
+        })
+    };
+
+    for (i, arg) in args.iter().enumerate() {
+        let param = fresh(SSAVar::new(i), caller);
+        body.push(Instruction::Assign { dest: param, value: arg.clone() });
+    }
+
+    let block = &callee.blocks[0];
+    for inst in &block.instructions {
+        let mut inst = inst.clone();
+        rename_instruction(&mut inst, &mut |v| fresh(v, caller));
+        body.push(inst);
     }
-    eprintln!("INFO: Optimization complete");
+
+    if let (Some(dest), Terminator::Return(value)) = (dest, &block.terminator) {
+        let mut value = value.clone();
+        rename_value(&mut value, &mut |v| fresh(v, caller));
+        body.push(Instruction::Assign { dest, value });
+    }
+    body
+}
+
+fn rename_instruction(inst: &mut Instruction, rename: &mut impl FnMut(SSAVar) -> SSAVar) {
+    match inst {
+        Instruction::Assign { dest, value } => {
+            *dest = rename(*dest);
+            rename_value(value, rename);
+        }
+        Instruction::BinaryOp { dest, left, right, .. } => {
+            *dest = rename(*dest);
+            rename_value(left, rename);
+            rename_value(right, rename);
+        }
+        Instruction::UnaryOp { dest, operand, .. } => {
+            *dest = rename(*dest);
+            rename_value(operand, rename);
+        }
+        Instruction::Cast { dest, value, .. } => {
+            *dest = rename(*dest);
+            rename_value(value, rename);
+        }
+        Instruction::Load { dest, array, index } => {
+            *dest = rename(*dest);
+            *array = rename(*array);
+            rename_value(index, rename);
+        }
+        Instruction::Store { array, index, value, .. } => {
+            *array = rename(*array);
+            rename_value(index, rename);
+            rename_value(value, rename);
+        }
+        Instruction::Call { dest, args, .. } => {
+            if let Some(d) = dest {
+                *d = rename(*d);
+            }
+            for arg in args {
+                rename_value(arg, rename);
+            }
+        }
+        Instruction::Phi { dest, incoming } => {
+            *dest = rename(*dest);
+            for (value, _) in incoming {
+                rename_value(value, rename);
+            }
+        }
+        Instruction::DomainConversion { dest, source, .. } => {
+            *dest = rename(*dest);
+            rename_value(source, rename);
+        }
+    }
+}
+
+fn rename_value(value: &mut Value, rename: &mut impl FnMut(SSAVar) -> SSAVar) {
+    match value {
+        Value::Var(v) => *v = rename(*v),
+        Value::Array(elements) | Value::Tuple(elements) => {
+            for elem in elements {
+                rename_value(elem, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Call Graph: functions that can reach themselves through calls
+fn recursive_functions(module: &Module) -> HashSet<String> {
+    let callees: HashMap<&str, HashSet<&str>> = module
+        .functions
+        .iter()
+        .map(|f| {
+            let called = f
+                .blocks
+                .iter()
+                .flat_map(|b| b.instructions.iter())
+                .filter_map(|inst| match inst {
+                    Instruction::Call { function, .. } => Some(function.as_str()),
+                    _ => None,
+                })
+                .collect();
+            (f.name.as_str(), called)
+        })
+        .collect();
+
+    let mut recursive = HashSet::new();
+    for &start in callees.keys() {
+        let mut seen = HashSet::new();
+        let mut worklist: Vec<&str> = callees[start].iter().copied().collect();
+        while let Some(name) = worklist.pop() {
+            if name == start {
+                recursive.insert(start.to_string());
+                break;
+            }
+            if seen.insert(name) {
+                if let Some(next) = callees.get(name) {
+                    worklist.extend(next.iter().copied());
+                }
+            }
+        }
+    }
+    recursive
 }
 
 // Function-level optimization: Apply multiple passes iteratively
 // Iterative Dataflow Analysis: Repeat until fixed point
-fn optimize_function(func: &mut IRFunction) {
-    // Run optimization passes in order (multiple iterations for better results)
-    for _ in 0..3 {
-        copy_propagation(func);                    // Replace copies with originals
-        constant_folding(func);                    // Evaluate constants at compile time
-        inline_single_use_vars(func);              // Inline single-use expressions
-        common_subexpression_elimination(func);    // CSE: Reuse computed values
-        dead_code_elimination(func);               // DCE: Remove unused code
+fn optimize_function(func: &mut IRFunction, pipeline: &[(&'static str, Pass)]) {
+    // Run optimization passes in order until an iteration changes nothing
+    // (each pass reports whether it rewrote anything)
+    for _ in 0..MAX_OPTIMIZE_ITERATIONS {
+        let mut changed = false;
+        for &(name, pass) in pipeline {
+            changed |= crate::timing::time(name, || pass(func));
+        }
+        if !changed {
+            break;
+        }
     }
-    // TODO: map_fusion, LICM (Loop-Invariant Code Motion)
+    // TODO: map_fusion
 }
 
+// A pass rewrites one function and reports whether anything changed
+type Pass = fn(&mut IRFunction) -> bool;
+
+// Pass order within one iteration: (timing name, --passes name, pass)
+const PIPELINE: &[(&str, &str, Pass)] = &[
+    ("copy_propagation", "copy-propagation", copy_propagation),                                 // Replace copies with originals
+    ("peephole", "peephole", peephole),                                                         // x = x, -(-x), !!b
+    ("constant_folding", "constant-folding", constant_folding),                                 // Evaluate constants at compile time
+    ("branch_folding", "branch-folding", branch_folding),                                       // br true → jump
+    ("unreachable_block_elimination", "unreachable-blocks", unreachable_block_elimination),     // Drop blocks no path reaches
+    ("strength_reduction", "strength-reduction", strength_reduction),                           // Mul/div by 2^k → shifts
+    ("inline_single_use_vars", "inline-single-use", inline_single_use_vars),                    // Inline single-use expressions
+    ("common_subexpression_elimination", "cse", common_subexpression_elimination),              // CSE: Reuse computed values
+    ("loop_invariant_code_motion", "licm", loop_invariant_code_motion),                         // LICM: Hoist invariant code out of loops
+    ("dead_code_elimination", "dce", dead_code_elimination),                                    // DCE: Remove unused code
+    ("dead_store_elimination", "dse", dead_store_elimination),                                  // DSE: Remove overwritten array stores
+    ("simplify_cfg", "simplify-cfg", simplify_cfg),                                             // Merge straight-line blocks
+];
+
+/// Names accepted in [`Options::passes`] (and `--passes`), in pipeline order.
+pub fn pass_names() -> impl Iterator<Item = &'static str> {
+    std::iter::once(INLINE_PASS).chain(PIPELINE.iter().map(|&(_, name, _)| name))
+}
+
+// Pass Selection (--passes): the named passes, in the given order, instead
+// of the whole pipeline; None (no names) runs everything
+fn select_passes(names: &[String]) -> Result<Option<Vec<&'static str>>> {
+    let mut selected = Vec::new();
+    for name in names {
+        match pass_names().find(|pass| pass == name) {
+            Some(pass) => selected.push(pass),
+            None => bail!(
+                "Unknown pass '{}' (expected one of: {})",
+                name,
+                pass_names().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+    Ok(if selected.is_empty() { None } else { Some(selected) })
+}
+
+// The per-function passes to run: the --passes selection, or the whole pipeline
+fn selected_pipeline(selection: &Option<Vec<&'static str>>) -> Vec<(&'static str, Pass)> {
+    let by_name = |name: &str| PIPELINE.iter().find(|&&(_, n, _)| n == name).map(|&(timing, _, pass)| (timing, pass));
+    match selection {
+        Some(passes) => passes.iter().filter_map(|name| by_name(name)).collect(),
+        None => PIPELINE.iter().map(|&(timing, _, pass)| (timing, pass)).collect(),
+    }
+}
+
+// Safety cap on pipeline iterations, in case passes keep undoing each other
+const MAX_OPTIMIZE_ITERATIONS: usize = 20;
+
 /// Copy Propagation: Replace variable uses with their assigned values
 /// Dataflow Analysis: Forward propagation of copy assignments
 /// Example: x = y; z = x + 1; → z = y + 1;
-fn copy_propagation(func: &mut IRFunction) {
-    let mut copy_map: HashMap<SSAVar, Value> = HashMap::new();
+fn copy_propagation(func: &mut IRFunction) -> bool {
+    // copy var -> (value, defining block)
+    let mut copies: HashMap<SSAVar, (Value, usize)> = HashMap::new();
 
-    // Build copy map: v = x -> replace all uses of v with x
+    // Reassigned variables (loop counters, `x = ...` statements) have more
+    // than one reaching definition, so only single-definition copies are safe
+    let def_counts = count_definitions(func);
+    let single_def = |v: &SSAVar| def_counts.get(v).copied().unwrap_or(0) <= 1;
+
+    // Build copy map: v = x -> replace uses of v with x
     // Reaching Definitions: Track which assignments reach each use
-    for block in &func.blocks {
+    for (b, block) in func.blocks.iter().enumerate() {
         for inst in &block.instructions {
             if let Instruction::Assign { dest, value } = inst {
+                if !single_def(dest) {
+                    continue;
+                }
                 // Propagate constants and variable copies
                 match value {
-                    Value::Var(_) | Value::Int(_) | Value::Float(_) | Value::Bool(_) => {
-                        copy_map.insert(*dest, value.clone());
+                    Value::Var(src) if single_def(src) => {
+                        copies.insert(*dest, (value.clone(), b));
+                    }
+                    Value::Int(_) | Value::Float(_) | Value::Bool(_) => {
+                        copies.insert(*dest, (value.clone(), b));
                     }
                     _ => {}
                 }
@@ -52,69 +423,492 @@ fn copy_propagation(func: &mut IRFunction) {
     }
 
     // Replace uses with propagated values
-    // Def-Use Chain: Follow uses of each definition
-    for block in &mut func.blocks {
+    // Dominance: a copy only reaches uses in blocks its definition dominates
+    // (a copy made in one branch arm never leaks into the sibling arm);
+    // within its own block it applies from the definition onwards
+    let successors = block_successors(func);
+    let dominators = compute_dominators(&successors, &reachable_blocks(&successors));
+    let mut changed = false;
+    for (b, block) in func.blocks.iter_mut().enumerate() {
+        let mut copy_map: HashMap<SSAVar, Value> = copies
+            .iter()
+            .filter(|(_, (_, def_block))| *def_block != b && dominators[b].contains(def_block))
+            .map(|(var, (value, _))| (*var, value.clone()))
+            .collect();
+
         for inst in &mut block.instructions {
-            replace_value_uses(inst, &copy_map);
+            changed |= replace_value_uses(inst, &copy_map);
+            if let Instruction::Assign { dest, .. } = inst {
+                if let Some((value, def_block)) = copies.get(dest) {
+                    if *def_block == b {
+                        copy_map.insert(*dest, value.clone());
+                    }
+                }
+            }
         }
-        replace_terminator_uses(&mut block.terminator, &copy_map);
+        changed |= replace_terminator_uses(&mut block.terminator, &copy_map);
     }
+    changed
 }
 
 /// Inline Single-Use Variables - replace variables used only once with their values
-fn inline_single_use_vars(func: &mut IRFunction) {
+fn inline_single_use_vars(_func: &mut IRFunction) -> bool {
     // This optimization is complex and can break code if not done carefully
     // For now, copy propagation + DCE already handles most cases
     // TODO: Implement safe expression inlining for Load and BinaryOp
+    false
 }
 
 /// Constant Folding: Evaluate constant expressions at compile time
 /// Optimization: Reduce runtime computation by computing at compile time
-/// Example: x = 2 + 3; → x = 5;
-fn constant_folding(func: &mut IRFunction) {
+/// Example: x = 2 + 3; → x = 5; y = -(x) → y = -5; c = 1 == 1 → c = true;
+/// What folds (and what overflows) is decided by the shared folder in fold.rs
+fn constant_folding(func: &mut IRFunction) -> bool {
+    let mut changed = false;
     for block in &mut func.blocks {
+        // Constant Propagation: variables this block has already set to a
+        // constant (so far), so a chain `a = 1; b = a + 1; c = b * 2` folds in
+        // one sweep instead of one link per pipeline iteration
+        let mut known: HashMap<SSAVar, Value> = HashMap::new();
+        let constant = |value: &Value, known: &HashMap<SSAVar, Value>| match value {
+            Value::Var(v) => known.get(v).cloned().unwrap_or_else(|| value.clone()),
+            _ => value.clone(),
+        };
         for inst in &mut block.instructions {
             if let Instruction::BinaryOp { dest, op, left, right } = inst {
                 // Try to fold if both operands are constants
-                // Constant Propagation: Use known constant values
-                if let (Value::Int(l), Value::Int(r)) = (&*left, &*right) {
-                    let result = match op {
-                        BinOp::Add => Some(*l + *r),
-                        BinOp::Sub => Some(*l - *r),
-                        BinOp::Mul => Some(*l * *r),
-                        BinOp::Div if *r != 0 => Some(*l / *r),
-                        _ => None,
-                    };
-                    if let Some(val) = result {
-                        *inst = Instruction::Assign {
-                            dest: *dest,
-                            value: Value::Int(val),
-                        };
+                let result = fold_binary(*op, &constant(left, &known), &constant(right, &known)).ok();
+                if let Some(value) = result {
+                    *inst = Instruction::Assign { dest: *dest, value };
+                    changed = true;
+                }
+            } else if let Instruction::UnaryOp { dest, op, operand } = inst {
+                // -(3) → -3, !true → false
+                let value = fold_unary(*op, &constant(operand, &known)).ok();
+                if let Some(value) = value {
+                    *inst = Instruction::Assign { dest: *dest, value };
+                    changed = true;
+                }
+            } else if let Instruction::Cast { dest, value, to } = inst {
+                // cast 3 to float → 3.0; float to int truncates toward zero
+                let value = match (constant(value, &known), to) {
+                    (Value::Int(n), IRType::Float | IRType::Float32 | IRType::Float64) => Some(Value::Float(n as f64)),
+                    (Value::Float(f), IRType::Int | IRType::Int32 | IRType::Int64) => Some(Value::Int(f as i64)),
+                    (value @ Value::Int(_), IRType::Int | IRType::Int32 | IRType::Int64)
+                    | (value @ Value::Float(_), IRType::Float | IRType::Float32 | IRType::Float64) => Some(value),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    *inst = Instruction::Assign { dest: *dest, value };
+                    changed = true;
+                }
+            }
+
+            // Track what this instruction leaves in its destination; a
+            // reassigned variable forgets its earlier constant
+            if let Some(dest) = get_dest(inst) {
+                let value = match inst {
+                    Instruction::Assign { value, .. } => constant(value, &known),
+                    _ => Value::Var(dest),
+                };
+                if matches!(value, Value::Int(_) | Value::Float(_) | Value::Bool(_)) {
+                    known.insert(dest, value);
+                } else {
+                    known.remove(&dest);
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Branch Folding: a branch on a constant condition always goes one way
+/// Example: br true, if_then_0, if_else_0 → jump if_then_0
+/// The arm no longer taken from this block loses its phi entries for it;
+/// unreachable_block_elimination then removes it if nothing else leads there.
+fn branch_folding(func: &mut IRFunction) -> bool {
+    let mut dropped_edges = Vec::new();
+    for block in &mut func.blocks {
+        if let Terminator::Branch { condition: Value::Bool(taken), true_label, false_label } = &block.terminator {
+            let (target, other) = if *taken { (true_label, false_label) } else { (false_label, true_label) };
+            if target != other {
+                dropped_edges.push((block.label.clone(), other.clone()));
+            }
+            block.terminator = Terminator::Jump(target.clone());
+        }
+    }
+    for (from, to) in &dropped_edges {
+        if let Some(block) = func.blocks.iter_mut().find(|b| &b.label == to) {
+            remove_phi_entries(block, |label| label == from);
+        }
+    }
+    !dropped_edges.is_empty()
+}
+
+/// Unreachable Block Elimination: remove blocks no path from the entry
+/// reaches, and their entries in the phis of blocks that remain
+fn unreachable_block_elimination(func: &mut IRFunction) -> bool {
+    let successors = block_successors(func);
+    let mut reachable = HashSet::new();
+    let mut worklist = vec![0];
+    while let Some(b) = worklist.pop() {
+        if reachable.insert(b) {
+            worklist.extend(successors[b].iter().copied());
+        }
+    }
+    if reachable.len() == func.blocks.len() {
+        return false;
+    }
+
+    let removed: HashSet<String> = func
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(b, _)| !reachable.contains(b))
+        .map(|(_, block)| block.label.clone())
+        .collect();
+    func.blocks.retain(|block| !removed.contains(&block.label));
+    for block in &mut func.blocks {
+        remove_phi_entries(block, |label| removed.contains(label));
+    }
+    true
+}
+
+/// Simplify CFG: concatenate a block onto its predecessor when that
+/// predecessor ends in `jump` to it and is its only predecessor, so
+/// straight-line chains left by lowering and branch folding become one block
+fn simplify_cfg(func: &mut IRFunction) -> bool {
+    let mut changed = false;
+    loop {
+        let successors = block_successors(func);
+        let mut predecessors = vec![0; func.blocks.len()];
+        for &b in successors.iter().flatten() {
+            predecessors[b] += 1;
+        }
+        let merge = func.blocks.iter().enumerate().find_map(|(a, block)| match block.terminator {
+            Terminator::Jump(_) => successors[a]
+                .first()
+                .filter(|&&b| b != a && b != 0 && predecessors[b] == 1)
+                .map(|&b| (a, b)),
+            _ => None,
+        });
+        let Some((a, b)) = merge else {
+            return changed;
+        };
+
+        let mut merged = func.blocks.remove(b);
+        let a = if b < a { a - 1 } else { a };
+        // With a single predecessor every phi is a plain copy
+        remove_phi_entries(&mut merged, |_| false);
+        let target = func.blocks[a].label.clone();
+        func.blocks[a].instructions.append(&mut merged.instructions);
+        func.blocks[a].terminator = merged.terminator;
+
+        // The merged block's successors are now entered from its predecessor
+        for block in &mut func.blocks {
+            for inst in &mut block.instructions {
+                if let Instruction::Phi { incoming, .. } = inst {
+                    for (_, label) in incoming.iter_mut().filter(|(_, label)| *label == merged.label) {
+                        *label = target.clone();
                     }
-                } else if let (Value::Float(l), Value::Float(r)) = (&*left, &*right) {
-                    let result = match op {
-                        BinOp::Add => Some(*l + *r),
-                        BinOp::Sub => Some(*l - *r),
-                        BinOp::Mul => Some(*l * *r),
-                        BinOp::Div if *r != 0.0 => Some(*l / *r),
-                        _ => None,
-                    };
-                    if let Some(val) = result {
-                        *inst = Instruction::Assign {
+                }
+            }
+        }
+        changed = true;
+    }
+}
+
+// Drop phi entries for predecessors that no longer branch here; a phi
+// left with one entry is a plain copy
+fn remove_phi_entries(block: &mut BasicBlock, gone: impl Fn(&String) -> bool) {
+    for inst in &mut block.instructions {
+        if let Instruction::Phi { dest, incoming } = inst {
+            incoming.retain(|(_, label)| !gone(label));
+            if let [(value, _)] = incoming.as_slice() {
+                *inst = Instruction::Assign { dest: *dest, value: value.clone() };
+            }
+        }
+    }
+}
+
+/// Peephole Optimization: Local rewrites of small instruction patterns
+/// Example: x = x; → (removed); t = -x; y = -t; → y = x; t = !b; c = !t; → c = b;
+fn peephole(func: &mut IRFunction) -> bool {
+    // Only single-definition variables are safe: a reassigned operand could
+    // hold a different value by the time the outer negation runs
+    let def_counts = count_definitions(func);
+    let single_def = |v: &SSAVar| def_counts.get(v).copied().unwrap_or(0) <= 1;
+
+    let mut unary_defs: HashMap<SSAVar, (UnOp, Value)> = HashMap::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            if let Instruction::UnaryOp { dest, op, operand } = inst {
+                let stable = match operand {
+                    Value::Var(v) => single_def(v),
+                    _ => true,
+                };
+                if single_def(dest) && stable {
+                    unary_defs.insert(*dest, (*op, operand.clone()));
+                }
+            }
+        }
+    }
+
+    let mut changed = false;
+    for block in &mut func.blocks {
+        // Self-assignment: x = x does nothing
+        let before = block.instructions.len();
+        block.instructions.retain(|inst| {
+            !matches!(inst, Instruction::Assign { dest, value: Value::Var(src) } if dest == src)
+        });
+        changed |= block.instructions.len() != before;
+
+        // Double negation: -(-x) → x, !(!b) → b
+        for inst in &mut block.instructions {
+            if let Instruction::UnaryOp { dest, op, operand: Value::Var(inner) } = inst {
+                if let Some((inner_op, value)) = unary_defs.get(inner) {
+                    if inner_op == op {
+                        *inst = Instruction::Assign { dest: *dest, value: value.clone() };
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Strength Reduction: Replace expensive operations with cheaper equivalents
+/// Example: x * 8 → x << 3; x / 4 → x >> 2 (x known non-negative)
+/// Integer only: shifts are meaningless on floats. Left shift equals
+/// multiplication for any int; right shift rounds toward -inf while division
+/// rounds toward zero, so division is only rewritten for non-negative x.
+fn strength_reduction(func: &mut IRFunction) -> bool {
+    let nonnegative = nonnegative_vars(func);
+    let is_int = |v: &Value, types: &HashMap<SSAVar, IRType>| match v {
+        Value::Int(_) => true,
+        Value::Var(var) => types.get(var).is_some_and(IRType::is_int),
+        _ => false,
+    };
+
+    let mut changed = false;
+    for block in &mut func.blocks {
+        for inst in &mut block.instructions {
+            if let Instruction::BinaryOp { dest, op, left, right } = inst {
+                let rewrite = match (*op, &*left, &*right) {
+                    (BinOp::Mul, x, Value::Int(c)) | (BinOp::Mul, Value::Int(c), x) => {
+                        power_of_two(*c).map(|k| (BinOp::Shl, x.clone(), k))
+                    }
+                    (BinOp::Div, x, Value::Int(c)) if is_nonnegative(x, &nonnegative) => {
+                        power_of_two(*c).map(|k| (BinOp::Shr, x.clone(), k))
+                    }
+                    _ => None,
+                };
+                if let Some((new_op, x, k)) = rewrite {
+                    if k > 0 && is_int(&x, &func.var_types) {
+                        *inst = Instruction::BinaryOp {
                             dest: *dest,
-                            value: Value::Float(val),
+                            op: new_op,
+                            left: x,
+                            right: Value::Int(k),
                         };
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    changed
+}
+
+// log2(c) when c is a positive power of two
+fn power_of_two(c: i64) -> Option<i64> {
+    if c > 0 && c & (c - 1) == 0 {
+        Some(c.trailing_zeros() as i64)
+    } else {
+        None
+    }
+}
+
+fn is_nonnegative(value: &Value, nonnegative: &HashSet<SSAVar>) -> bool {
+    match value {
+        Value::Int(n) => *n >= 0,
+        Value::Var(v) => nonnegative.contains(v),
+        _ => false,
+    }
+}
+
+// Sign Analysis: variables whose every definition yields a non-negative int.
+// Greatest fixed point: assume all candidates are non-negative, then drop any
+// with a definition that cannot be shown so (handles `i = i + 1` loop counters).
+fn nonnegative_vars(func: &IRFunction) -> HashSet<SSAVar> {
+    let params: HashSet<SSAVar> = (0..func.params.len()).map(SSAVar::new).collect();
+    let mut nonnegative: HashSet<SSAVar> = func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instructions.iter())
+        .filter_map(get_dest)
+        .filter(|d| !params.contains(d))
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in &func.blocks {
+            for inst in &block.instructions {
+                let Some(dest) = get_dest(inst) else { continue };
+                if !nonnegative.contains(&dest) {
+                    continue;
+                }
+                let ok = match inst {
+                    Instruction::Assign { value, .. } => is_nonnegative(value, &nonnegative),
+                    Instruction::BinaryOp { op, left, right, .. } => {
+                        matches!(op, BinOp::Add | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Shl | BinOp::Shr)
+                            && is_nonnegative(left, &nonnegative)
+                            && is_nonnegative(right, &nonnegative)
+                    }
+                    Instruction::Phi { incoming, .. } => {
+                        incoming.iter().all(|(v, _)| is_nonnegative(v, &nonnegative))
+                    }
+                    _ => false,
+                };
+                if !ok {
+                    nonnegative.remove(&dest);
+                    changed = true;
+                }
+            }
+        }
+    }
+    nonnegative
+}
+
+/// Loop-Invariant Code Motion (LICM): Move computations that produce the same
+/// value on every iteration into the loop's preheader, so they run once
+/// Example: for i in 0..n { k = x * 2; a[i] = k; } → k = x * 2; for ... { a[i] = k; }
+/// Only pure, non-trapping instructions move: they may now run even when the
+/// loop body would not have (zero iterations, or a skipped branch)
+fn loop_invariant_code_motion(func: &mut IRFunction) -> bool {
+    let def_counts = count_definitions(func);
+    let mut changed = false;
+
+    for (header, body) in find_loops(func) {
+        let Some(preheader) = find_preheader(func, header, &body) else {
+            continue;
+        };
+
+        // Hoist until nothing else becomes invariant (an invariant instruction
+        // may feed another one)
+        loop {
+            let defined_in_loop: HashSet<SSAVar> = body
+                .iter()
+                .flat_map(|&b| func.blocks[b].instructions.iter())
+                .filter_map(get_dest)
+                .collect();
+
+            let mut hoisted = None;
+            'search: for &b in &body {
+                for (i, inst) in func.blocks[b].instructions.iter().enumerate() {
+                    let Some(dest) = get_dest(inst) else { continue };
+                    let invariant = is_hoistable(inst)
+                        && def_counts.get(&dest).copied().unwrap_or(0) == 1
+                        && get_operands(inst).iter().all(|v| !uses_any(v, &defined_in_loop));
+                    if invariant {
+                        hoisted = Some((b, i));
+                        break 'search;
                     }
                 }
             }
+
+            match hoisted {
+                Some((b, i)) => {
+                    let inst = func.blocks[b].instructions.remove(i);
+                    func.blocks[preheader].instructions.push(inst);
+                    changed = true;
+                }
+                None => break,
+            }
         }
     }
+    changed
+}
+
+// Pure instructions that cannot fault when executed speculatively
+fn is_hoistable(inst: &Instruction) -> bool {
+    match inst {
+        Instruction::Assign { .. } | Instruction::UnaryOp { .. } | Instruction::Cast { .. } => true,
+        Instruction::BinaryOp { op, right, .. } => match op {
+            BinOp::Div | BinOp::Mod => match right {
+                Value::Int(n) => *n != 0,
+                Value::Float(_) => true,
+                _ => false,
+            },
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+fn uses_any(value: &Value, vars: &HashSet<SSAVar>) -> bool {
+    match value {
+        Value::Var(v) => vars.contains(v),
+        Value::Array(elements) | Value::Tuple(elements) => elements.iter().any(|e| uses_any(e, vars)),
+        _ => false,
+    }
+}
+
+// Natural Loops: a back edge B → H (H dominates B) defines a loop made of H
+// and every block that reaches B without passing through H.
+// Returns (header, body blocks), innermost (smallest) loops first.
+fn find_loops(func: &IRFunction) -> Vec<(usize, Vec<usize>)> {
+    let successors = block_successors(func);
+    let dominators = compute_dominators(&successors, &reachable_blocks(&successors));
+
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); successors.len()];
+    for (b, succ) in successors.iter().enumerate() {
+        for &s in succ {
+            predecessors[s].push(b);
+        }
+    }
+
+    let mut loops: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (b, succ) in successors.iter().enumerate() {
+        for &h in succ {
+            if !dominators[b].contains(&h) {
+                continue;
+            }
+            let mut body: HashSet<usize> = HashSet::from([h]);
+            let mut worklist = vec![b];
+            while let Some(n) = worklist.pop() {
+                if body.insert(n) {
+                    worklist.extend(predecessors[n].iter().copied());
+                }
+            }
+            let mut body: Vec<usize> = body.into_iter().collect();
+            body.sort();
+            loops.push((h, body));
+        }
+    }
+    loops.sort_by_key(|(_, body)| body.len());
+    loops
+}
+
+// The single block outside the loop that jumps unconditionally to its header
+fn find_preheader(func: &IRFunction, header: usize, body: &[usize]) -> Option<usize> {
+    let header_label = &func.blocks[header].label;
+    let mut entries = func.blocks.iter().enumerate().filter(|(i, block)| {
+        !body.contains(i) && terminator_targets(&block.terminator).contains(&header_label.as_str())
+    });
+    let (preheader, block) = entries.next()?;
+    if entries.next().is_some() || !matches!(block.terminator, Terminator::Jump(_)) {
+        return None;
+    }
+    Some(preheader)
 }
 
 /// Dead Code Elimination (DCE): Remove instructions whose results are never used
 /// Liveness Analysis: Determine which variables are live at each program point
 /// Example: x = 5; y = 3; return y; → y = 3; return y; (x is dead)
-fn dead_code_elimination(func: &mut IRFunction) {
+fn dead_code_elimination(func: &mut IRFunction) -> bool {
     let mut used_vars = HashSet::new();
 
     // Liveness Analysis: Mark variables that are live (used)
@@ -130,7 +924,7 @@ fn dead_code_elimination(func: &mut IRFunction) {
         // Mark variables in side-effecting instructions
         for inst in &block.instructions {
             match inst {
-                Instruction::Store { array, index, value } => {
+                Instruction::Store { array, index, value, .. } => {
                     // Store is side-effecting - mark array and all operands as used
                     used_vars.insert(*array);
                     mark_value_used(index, &mut used_vars);
@@ -157,14 +951,16 @@ fn dead_code_elimination(func: &mut IRFunction) {
             for inst in &block.instructions {
                 if let Some(dest) = get_dest(inst) {
                     if used_vars.contains(&dest) {
-                        // Mark operands as used
+                        // Mark operands as used (including array/tuple elements,
+                        // and the variable a load reads from)
+                        let before = used_vars.len();
                         for operand in get_operands(inst) {
-                            if let Value::Var(v) = operand {
-                                if used_vars.insert(*v) {
-                                    changed = true;
-                                }
-                            }
+                            mark_value_used(operand, &mut used_vars);
+                        }
+                        if let Instruction::Load { array, .. } = inst {
+                            used_vars.insert(*array);
                         }
+                        changed |= used_vars.len() != before;
                     }
                 }
             }
@@ -172,7 +968,9 @@ fn dead_code_elimination(func: &mut IRFunction) {
     }
 
     // Remove unused instructions
+    let mut removed = false;
     for block in &mut func.blocks {
+        let before = block.instructions.len();
         block.instructions.retain(|inst| {
             if let Some(dest) = get_dest(inst) {
                 used_vars.contains(&dest) || is_side_effecting(inst)
@@ -180,49 +978,107 @@ fn dead_code_elimination(func: &mut IRFunction) {
                 true
             }
         });
+        removed |= block.instructions.len() != before;
+    }
+    removed
+}
+
+/// Dead Store Elimination (DSE): Remove array stores overwritten before any read
+/// Example: a[0] = 1; a[0] = 2; → a[0] = 2;
+/// Local (per-block) and constant indices only. Walking backwards, a store is
+/// dead if a later store hits the same a[k] with nothing in between that could
+/// read it: any load (copies may alias the array), call or conversion clears
+/// everything, and any other use of a clears a's pending stores.
+fn dead_store_elimination(func: &mut IRFunction) -> bool {
+    let mut changed = false;
+    for block in &mut func.blocks {
+        let mut overwritten: HashSet<(SSAVar, i64)> = HashSet::new();
+        let mut dead = vec![false; block.instructions.len()];
+        for (i, inst) in block.instructions.iter().enumerate().rev() {
+            match inst {
+                Instruction::Load { .. }
+                | Instruction::Call { .. }
+                | Instruction::DomainConversion { .. }
+                | Instruction::Phi { .. } => overwritten.clear(),
+                _ => {
+                    if let Instruction::Store { array, index: Value::Int(k), .. } = inst {
+                        // A store with an unknown index overwrites nothing for sure
+                        dead[i] = !overwritten.insert((*array, *k));
+                    }
+                    let mut read = HashSet::new();
+                    for operand in get_operands(inst) {
+                        mark_value_used(operand, &mut read);
+                    }
+                    overwritten.retain(|(array, _)| !read.contains(array));
+                }
+            }
+        }
+
+        let before = block.instructions.len();
+        let mut i = 0;
+        block.instructions.retain(|_| {
+            i += 1;
+            !dead[i - 1]
+        });
+        changed |= block.instructions.len() != before;
     }
+    changed
 }
 
 /// Common Subexpression Elimination (CSE): Reuse previously computed values
 /// Available Expressions: Track which expressions have been computed
 /// Example: a = b + c; d = b + c; → a = b + c; d = a;
-fn common_subexpression_elimination(func: &mut IRFunction) {
-    let mut expr_map: HashMap<String, SSAVar> = HashMap::new();
-
+fn common_subexpression_elimination(func: &mut IRFunction) -> bool {
+    let mut changed = false;
     // Available Expressions Analysis: Track computed expressions
+    // Local (per-block) only: an expression from one branch or loop iteration
+    // is not available in another block without dominance information
     for block in &mut func.blocks {
+        let mut expr_map: HashMap<String, SSAVar> = HashMap::new();
         for inst in &mut block.instructions {
-            match inst {
-                Instruction::BinaryOp {
-                    dest,
-                    op,
-                    left,
-                    right,
-                } => {
-                    // Hash expression for lookup
-                    let expr_key = format!("{:?} {:?} {:?}", op, left, right);
-                    if let Some(&existing_var) = expr_map.get(&expr_key) {
-                        // Expression already computed, reuse result
-                        // Replace computation with copy
-                        *inst = Instruction::Assign {
-                            dest: *dest,
-                            value: Value::Var(existing_var),
-                        };
-                    } else {
-                        // First occurrence, record it
-                        expr_map.insert(expr_key, *dest);
-                    }
+            if let Instruction::BinaryOp {
+                dest,
+                op,
+                left,
+                right,
+            } = inst
+            {
+                // Hash expression for lookup
+                let expr_key = format!("{:?} {:?} {:?}", op, left, right);
+                if let Some(&existing_var) = expr_map.get(&expr_key) {
+                    // Expression already computed, reuse result
+                    // Replace computation with copy
+                    *inst = Instruction::Assign {
+                        dest: *dest,
+                        value: Value::Var(existing_var),
+                    };
+                    changed = true;
+                } else {
+                    // First occurrence, record it
+                    expr_map.insert(expr_key, *dest);
                 }
-                _ => {}
             }
         }
     }
+    changed
+}
+
+fn count_definitions(func: &IRFunction) -> HashMap<SSAVar, usize> {
+    let mut counts = HashMap::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            if let Some(dest) = get_dest(inst) {
+                *counts.entry(dest).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
 }
 
 fn mark_value_used(val: &Value, used: &mut HashSet<SSAVar>) {
     if let Value::Var(v) = val {
         used.insert(*v);
-    } else if let Value::Array(elements) = val {
+    } else if let Value::Array(elements) | Value::Tuple(elements) = val {
         for elem in elements {
             mark_value_used(elem, used);
         }
@@ -234,6 +1090,7 @@ fn get_dest(inst: &Instruction) -> Option<SSAVar> {
         Instruction::Assign { dest, .. }
         | Instruction::BinaryOp { dest, .. }
         | Instruction::UnaryOp { dest, .. }
+        | Instruction::Cast { dest, .. }
         | Instruction::Load { dest, .. }
         | Instruction::Phi { dest, .. } => Some(*dest),
         Instruction::Call { dest, .. } => *dest,
@@ -246,6 +1103,7 @@ fn get_operands(inst: &Instruction) -> Vec<&Value> {
         Instruction::Assign { value, .. } => vec![value],
         Instruction::BinaryOp { left, right, .. } => vec![left, right],
         Instruction::UnaryOp { operand, .. } => vec![operand],
+        Instruction::Cast { value, .. } => vec![value],
         Instruction::Load { index, .. } => vec![index],
         Instruction::Store { index, value, .. } => vec![index, value],
         Instruction::Call { args, .. } => args.iter().collect(),
@@ -254,19 +1112,6 @@ fn get_operands(inst: &Instruction) -> Vec<&Value> {
     }
 }
 
-fn get_operands_mut(inst: &mut Instruction) -> Vec<&mut Value> {
-    match inst {
-        Instruction::Assign { value, .. } => vec![value],
-        Instruction::BinaryOp { left, right, .. } => vec![left, right],
-        Instruction::UnaryOp { operand, .. } => vec![operand],
-        Instruction::Load { index, .. } => vec![index],
-        Instruction::Store { index, value, .. } => vec![index, value],
-        Instruction::Call { args, .. } => args.iter_mut().collect(),
-        Instruction::Phi { incoming, .. } => incoming.iter_mut().map(|(v, _)| v).collect(),
-        Instruction::DomainConversion { source, .. } => vec![source],
-    }
-}
-
 fn is_side_effecting(inst: &Instruction) -> bool {
     matches!(
         inst,
@@ -274,125 +1119,55 @@ fn is_side_effecting(inst: &Instruction) -> bool {
     )
 }
 
-fn replace_value_uses(inst: &mut Instruction, copy_map: &HashMap<SSAVar, Value>) {
+// Returns whether any operand was replaced
+fn replace_value_uses(inst: &mut Instruction, copy_map: &HashMap<SSAVar, Value>) -> bool {
     match inst {
         Instruction::Assign { value, .. } => replace_value(value, copy_map),
         Instruction::BinaryOp { left, right, .. } => {
-            replace_value(left, copy_map);
-            replace_value(right, copy_map);
+            replace_value(left, copy_map) | replace_value(right, copy_map)
         }
         Instruction::UnaryOp { operand, .. } => replace_value(operand, copy_map),
+        Instruction::Cast { value, .. } => replace_value(value, copy_map),
         Instruction::Load { index, .. } => replace_value(index, copy_map),
         Instruction::Store { index, value, .. } => {
-            replace_value(index, copy_map);
-            replace_value(value, copy_map);
+            replace_value(index, copy_map) | replace_value(value, copy_map)
         }
         Instruction::Call { args, .. } => {
+            let mut changed = false;
             for arg in args {
-                replace_value(arg, copy_map);
+                changed |= replace_value(arg, copy_map);
             }
+            changed
         }
         Instruction::DomainConversion { source, .. } => replace_value(source, copy_map),
-        _ => {}
+        _ => false,
     }
 }
 
-fn replace_value(value: &mut Value, copy_map: &HashMap<SSAVar, Value>) {
+fn replace_value(value: &mut Value, copy_map: &HashMap<SSAVar, Value>) -> bool {
     if let Value::Var(v) = value {
         if let Some(replacement) = copy_map.get(v) {
-            *value = replacement.clone();
+            if replacement != value {
+                *value = replacement.clone();
+                return true;
+            }
         }
-    } else if let Value::Array(elements) = value {
+        false
+    } else if let Value::Array(elements) | Value::Tuple(elements) = value {
+        let mut changed = false;
         for elem in elements {
-            replace_value(elem, copy_map);
+            changed |= replace_value(elem, copy_map);
         }
+        changed
+    } else {
+        false
     }
 }
 
-fn replace_terminator_uses(term: &mut Terminator, copy_map: &HashMap<SSAVar, Value>) {
+fn replace_terminator_uses(term: &mut Terminator, copy_map: &HashMap<SSAVar, Value>) -> bool {
     match term {
         Terminator::Return(val) => replace_value(val, copy_map),
         Terminator::Branch { condition, .. } => replace_value(condition, copy_map),
-        _ => {}
-    }
-}
-
-fn count_value_uses(inst: &Instruction, use_count: &mut HashMap<SSAVar, usize>) {
-    for operand in get_operands(inst) {
-        count_value_in_value(operand, use_count);
-    }
-}
-
-fn count_value_in_value(value: &Value, use_count: &mut HashMap<SSAVar, usize>) {
-    match value {
-        Value::Var(v) => *use_count.entry(*v).or_insert(0) += 1,
-        Value::Array(elements) => {
-            for elem in elements {
-                count_value_in_value(elem, use_count);
-            }
-        }
-        _ => {}
-    }
-}
-
-fn count_terminator_uses(term: &Terminator, use_count: &mut HashMap<SSAVar, usize>) {
-    match term {
-        Terminator::Return(val) => count_value_in_value(val, use_count),
-        Terminator::Branch { condition, .. } => count_value_in_value(condition, use_count),
-        _ => {}
+        _ => false,
     }
 }
-
-fn inline_instruction_uses(inst: &mut Instruction, inline_map: &HashMap<SSAVar, Instruction>) {
-    match inst {
-        Instruction::Assign { value, .. } => inline_value_uses(value, inline_map),
-        Instruction::BinaryOp { left, right, .. } => {
-            inline_value_uses(left, inline_map);
-            inline_value_uses(right, inline_map);
-        }
-        Instruction::UnaryOp { operand, .. } => inline_value_uses(operand, inline_map),
-        Instruction::Load { index, .. } => inline_value_uses(index, inline_map),
-        Instruction::Store { index, value, .. } => {
-            inline_value_uses(index, inline_map);
-            inline_value_uses(value, inline_map);
-        }
-        Instruction::Call { args, .. } => {
-            for arg in args {
-                inline_value_uses(arg, inline_map);
-            }
-        }
-        Instruction::DomainConversion { source, .. } => inline_value_uses(source, inline_map),
-        _ => {}
-    }
-}
-
-fn inline_value_uses(value: &mut Value, inline_map: &HashMap<SSAVar, Instruction>) {
-    if let Value::Var(v) = value {
-        if let Some(inst) = inline_map.get(v) {
-            // Replace with the value from the inlined instruction
-            match inst {
-                Instruction::Assign { value: val, .. } => {
-                    *value = val.clone();
-                }
-                Instruction::Load { array, index, .. } => {
-                    // Can't inline complex expressions into Value - keep as is
-                    // This will be handled by backend
-                }
-                _ => {}
-            }
-        }
-    } else if let Value::Array(elements) = value {
-        for elem in elements {
-            inline_value_uses(elem, inline_map);
-        }
-    }
-}
-
-fn inline_terminator_instruction_uses(term: &mut Terminator, inline_map: &HashMap<SSAVar, Instruction>) {
-    match term {
-        Terminator::Return(val) => inline_value_uses(val, inline_map),
-        Terminator::Branch { condition, .. } => inline_value_uses(condition, inline_map),
-        _ => {}
-    }
-}
-
@@ -0,0 +1,23 @@
+// Middle-end settings: what the CLI flags decide about lowering and
+// optimization, handed to each stage rather than kept process-wide
+
+/// Constant `for` loops with at most this many iterations are unrolled
+pub const DEFAULT_MAX_UNROLL: usize = 256;
+
+/// Settings for [`lower_to_ir`](super::lower_to_ir) and
+/// [`optimize`](super::optimize).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// A constant loop with more iterations is lowered as a real loop
+    /// instead of being unrolled (`--max-unroll`)
+    pub max_unroll: usize,
+    /// Run only these optimization passes, in this order; empty runs the
+    /// full pipeline (`--passes`, names from [`pass_names`](super::pass_names))
+    pub passes: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { max_unroll: DEFAULT_MAX_UNROLL, passes: Vec::new() }
+    }
+}
@@ -0,0 +1,556 @@
+// Textual IR: reads the `dump_ir` / `dump_ir_typed` format back into a
+// Module, so a dump can be edited by hand and fed to the optimizer or a
+// backend (`quarkdsl lower -O file.ir`). Line-oriented: every instruction,
+// terminator and block label sits on its own line.
+use super::ir::*;
+use crate::frontend::ast::Domain;
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+
+/// Parse textual IR as printed by [`super::dump_ir`] or [`super::dump_ir_typed`].
+///
+/// Types come from the signature (parameters) and the `%n: type` annotations
+/// of a typed dump; a plain dump leaves its other variables untyped. A typed
+/// dump of a lowered module reads back as the same module:
+///
+/// ```
+/// use quarkdsl::middle::{dump_ir_typed, parse_ir};
+///
+/// let source = "/// Doubles
+///               @gpu fn double(xs: [float; 2]) -> [float; 2] {
+///                   let mut ys = xs;
+///                   for i in 0..2 { ys[i] = xs[i] * 2.0; }
+///                   return ys;
+///               }
+///               fn main() -> int { let r = double([1.5, 2.0]); print_fmt(\"r={}\", r[0]); return 0; }";
+/// let module = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+/// assert_eq!(parse_ir(&dump_ir_typed(&module)).unwrap(), module);
+///
+/// let error = parse_ir("fn f() -> int {\n  entry:\n    %0 = frob 1\n    return %0\n}\n").unwrap_err();
+/// assert!(format!("{:#}", error).contains("IR line 3"));
+/// ```
+pub fn parse_ir(text: &str) -> Result<Module> {
+    let mut functions = Vec::new();
+    let mut doc: Vec<&str> = Vec::new();
+    let mut header: Option<(Domain, HashMap<String, i64>, Option<String>)> = None;
+    let mut current: Option<IRFunction> = None;
+
+    for (n, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        let parsed = (|| -> Result<()> {
+            if line.is_empty() {
+                return Ok(());
+            }
+            let Some(func) = current.as_mut() else {
+                // Between functions: doc comments, an annotation, a signature
+                if let Some(text) = line.strip_prefix("///") {
+                    doc.push(text.trim());
+                } else if line.starts_with('@') {
+                    header = Some(parse_annotation(line)?);
+                } else if line.starts_with("fn ") {
+                    let (domain, annotations, device) = header.take().unwrap_or((Domain::Classical, HashMap::new(), None));
+                    let mut func = parse_signature(line, domain, annotations)?;
+                    func.device = device;
+                    func.doc = (!doc.is_empty()).then(|| doc.join("\n"));
+                    doc.clear();
+                    current = Some(func);
+                } else {
+                    bail!("expected a function, got '{}'", line);
+                }
+                return Ok(());
+            };
+            if line == "}" {
+                let mut func = current.take().unwrap();
+                if func.blocks.is_empty() {
+                    bail!("function '{}' has no blocks", func.name);
+                }
+                func.next_var_id = next_var_id(&func);
+                functions.push(func);
+            } else if let Some(label) = line.strip_suffix(':').filter(|l| is_ident(l)) {
+                // A new block; its terminator is filled in when it ends
+                func.blocks.push(BasicBlock {
+                    label: label.to_string(),
+                    instructions: Vec::new(),
+                    terminator: Terminator::ReturnVoid,
+                });
+            } else {
+                let Some(block) = func.blocks.last_mut() else {
+                    bail!("instruction outside of a block");
+                };
+                match parse_terminator(line)? {
+                    Some(terminator) => block.terminator = terminator,
+                    None => {
+                        let (inst, ty) = parse_instruction(line)?;
+                        if let (Some(dest), Some(ty)) = (get_dest(&inst), ty) {
+                            func.var_types.insert(dest, ty);
+                        }
+                        block.instructions.push(inst);
+                    }
+                }
+            }
+            Ok(())
+        })();
+        parsed.with_context(|| format!("IR line {}: {}", n + 1, line))?;
+    }
+    if let Some(func) = current {
+        bail!("function '{}' is missing its closing '}}'", func.name);
+    }
+    Ok(Module { functions })
+}
+
+// `@gpu`, `@quantum(backend="ibm_brisbane", shots=100, seed=7)`, `@classical`
+fn parse_annotation(line: &str) -> Result<(Domain, HashMap<String, i64>, Option<String>)> {
+    let mut s = Scanner::new(&line[1..]);
+    let domain = match s.ident()? {
+        "gpu" => Domain::Gpu,
+        "quantum" => Domain::Quantum,
+        "classical" => Domain::Classical,
+        other => bail!("unknown domain '@{}'", other),
+    };
+    let mut annotations = HashMap::new();
+    let mut device = None;
+    if s.eat("(") {
+        loop {
+            let key = s.ident()?.to_string();
+            s.expect("=")?;
+            if key == "backend" {
+                device = Some(s.string()?);
+            } else {
+                let value = s.word()?;
+                annotations.insert(key, value.parse().map_err(|_| anyhow!("annotation value must be an integer, got '{}'", value))?);
+            }
+            if !s.eat(",") {
+                break;
+            }
+        }
+        s.expect(")")?;
+    }
+    s.end()?;
+    Ok((domain, annotations, device))
+}
+
+// `fn name(a: int, xs: [float]) -> int {`; parameters are %0, %1, ...
+fn parse_signature(line: &str, domain: Domain, annotations: HashMap<String, i64>) -> Result<IRFunction> {
+    let mut s = Scanner::new(line);
+    s.expect("fn")?;
+    let name = s.ident()?.to_string();
+    s.expect("(")?;
+    let mut params = Vec::new();
+    if !s.eat(")") {
+        loop {
+            let param = s.ident()?.to_string();
+            s.expect(":")?;
+            params.push((param, s.ty()?));
+            if !s.eat(",") {
+                break;
+            }
+        }
+        s.expect(")")?;
+    }
+    s.expect("->")?;
+    let return_type = s.ty()?;
+    s.expect("{")?;
+    s.end()?;
+
+    let var_types = params.iter().enumerate().map(|(i, (_, ty))| (SSAVar::new(i), ty.clone())).collect();
+    Ok(IRFunction {
+        name,
+        params,
+        return_type,
+        blocks: Vec::new(),
+        next_var_id: 0,
+        domain,
+        var_types,
+        annotations,
+        device: None,
+        doc: None,
+    })
+}
+
+// One past the highest variable the function mentions
+fn next_var_id(func: &IRFunction) -> usize {
+    let mut next = func.params.len();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        if let Some(dest) = get_dest(inst) {
+            next = next.max(dest.id + 1);
+        }
+    }
+    next
+}
+
+fn parse_terminator(line: &str) -> Result<Option<Terminator>> {
+    let mut s = Scanner::new(line);
+    let terminator = match s.peek_word() {
+        "return" => {
+            s.word()?;
+            if s.eat("void") {
+                Terminator::ReturnVoid
+            } else {
+                Terminator::Return(s.value()?)
+            }
+        }
+        "br" => {
+            s.word()?;
+            let condition = s.value()?;
+            s.expect(",")?;
+            let true_label = s.ident()?.to_string();
+            s.expect(",")?;
+            let false_label = s.ident()?.to_string();
+            Terminator::Branch { condition, true_label, false_label }
+        }
+        "jump" => {
+            s.word()?;
+            Terminator::Jump(s.ident()?.to_string())
+        }
+        _ => return Ok(None),
+    };
+    s.end()?;
+    Ok(Some(terminator))
+}
+
+// An instruction and, in a typed dump, its destination's type
+fn parse_instruction(line: &str) -> Result<(Instruction, Option<IRType>)> {
+    // `// line N` after a call or store is its source line
+    let (code, line_number) = match line.split_once("  // line ") {
+        Some((code, n)) => (code, Some(n.trim().parse::<usize>().context("bad source line")?)),
+        None => (line, None),
+    };
+    let mut s = Scanner::new(code);
+
+    if s.eat_word("store") {
+        let array = s.var()?;
+        s.expect("[")?;
+        let index = s.value()?;
+        s.expect("]")?;
+        s.expect("=")?;
+        let value = s.value()?;
+        s.end()?;
+        return Ok((Instruction::Store { array, index, value, line: line_number }, None));
+    }
+    if s.eat_word("call") {
+        let (function, args) = parse_call(&mut s)?;
+        return Ok((Instruction::Call { dest: None, function, args, line: line_number }, None));
+    }
+
+    let dest = s.var()?;
+    let ty = if s.eat(":") { Some(s.ty()?) } else { None };
+    s.expect("=")?;
+    let word = s.peek_word();
+    let inst = if let Some(op) = binop(word) {
+        s.word()?;
+        let left = s.value()?;
+        let right = s.value()?;
+        Instruction::BinaryOp { dest, op, left, right }
+    } else if let Some(op) = unop(word) {
+        s.word()?;
+        Instruction::UnaryOp { dest, op, operand: s.value()? }
+    } else if word == "cast" {
+        s.word()?;
+        let value = s.value()?;
+        s.expect("to")?;
+        Instruction::Cast { dest, value, to: s.ty()? }
+    } else if word == "load" {
+        s.word()?;
+        let array = s.var()?;
+        s.expect("[")?;
+        let index = s.value()?;
+        s.expect("]")?;
+        Instruction::Load { dest, array, index }
+    } else if word == "call" {
+        s.word()?;
+        let (function, args) = parse_call(&mut s)?;
+        Instruction::Call { dest: Some(dest), function, args, line: line_number }
+    } else if word == "phi" {
+        s.word()?;
+        let mut incoming = Vec::new();
+        loop {
+            s.expect("[")?;
+            let value = s.value()?;
+            s.expect(",")?;
+            incoming.push((value, s.ident()?.to_string()));
+            s.expect("]")?;
+            if !s.eat(",") {
+                break;
+            }
+        }
+        Instruction::Phi { dest, incoming }
+    } else if let Some(domains) = word.strip_prefix("convert_") {
+        // convert_Gpu_to_Quantum(%1, AngleEncoding)
+        s.word()?;
+        let (from, to) = domains.split_once("_to_").ok_or_else(|| anyhow!("bad conversion '{}'", word))?;
+        s.expect("(")?;
+        let source = s.value()?;
+        s.expect(",")?;
+        let encoding = match s.ident()? {
+            "AngleEncoding" => ConversionEncoding::AngleEncoding,
+            "AmplitudeEncoding" => ConversionEncoding::AmplitudeEncoding,
+            "MeasurementExtract" => ConversionEncoding::MeasurementExtract,
+            other => bail!("unknown encoding '{}'", other),
+        };
+        s.expect(")")?;
+        Instruction::DomainConversion { dest, source, from_domain: domain(from)?, to_domain: domain(to)?, encoding }
+    } else {
+        Instruction::Assign { dest, value: s.value()? }
+    };
+    s.end()?;
+    Ok((inst, ty))
+}
+
+// `name(arg, ...)` after `call`
+fn parse_call(s: &mut Scanner) -> Result<(String, Vec<Value>)> {
+    let function = s.ident()?.to_string();
+    s.expect("(")?;
+    let args = s.values_until(")")?;
+    s.end()?;
+    Ok((function, args))
+}
+
+fn domain(name: &str) -> Result<Domain> {
+    match name {
+        "Gpu" => Ok(Domain::Gpu),
+        "Quantum" => Ok(Domain::Quantum),
+        "Classical" => Ok(Domain::Classical),
+        other => bail!("unknown domain '{}'", other),
+    }
+}
+
+fn binop(word: &str) -> Option<BinOp> {
+    Some(match word {
+        "add" => BinOp::Add,
+        "sub" => BinOp::Sub,
+        "mul" => BinOp::Mul,
+        "div" => BinOp::Div,
+        "mod" => BinOp::Mod,
+        "eq" => BinOp::Eq,
+        "ne" => BinOp::Ne,
+        "lt" => BinOp::Lt,
+        "le" => BinOp::Le,
+        "gt" => BinOp::Gt,
+        "ge" => BinOp::Ge,
+        "and" => BinOp::And,
+        "or" => BinOp::Or,
+        "shl" => BinOp::Shl,
+        "shr" => BinOp::Shr,
+        _ => return None,
+    })
+}
+
+fn unop(word: &str) -> Option<UnOp> {
+    match word {
+        "neg" => Some(UnOp::Neg),
+        "not" => Some(UnOp::Not),
+        _ => None,
+    }
+}
+
+fn get_dest(inst: &Instruction) -> Option<SSAVar> {
+    match inst {
+        Instruction::Assign { dest, .. }
+        | Instruction::BinaryOp { dest, .. }
+        | Instruction::UnaryOp { dest, .. }
+        | Instruction::Cast { dest, .. }
+        | Instruction::Load { dest, .. }
+        | Instruction::Phi { dest, .. }
+        | Instruction::DomainConversion { dest, .. } => Some(*dest),
+        Instruction::Call { dest, .. } => *dest,
+        Instruction::Store { .. } => None,
+    }
+}
+
+fn is_ident(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Cursor over one line of IR
+struct Scanner<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(line: &'a str) -> Self {
+        Scanner { rest: line.trim() }
+    }
+
+    // Consume `token` (after any spaces) if it comes next
+    fn eat(&mut self, token: &str) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if !self.eat(token) {
+            bail!("expected '{}' at '{}'", token, self.rest);
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        if !self.rest.trim().is_empty() {
+            bail!("unexpected '{}'", self.rest.trim());
+        }
+        Ok(())
+    }
+
+    // The next run of identifier characters (plus '.', '-' and '+' for numbers)
+    fn peek_word(&self) -> &'a str {
+        let rest = self.rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '+')))
+            .unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    fn word(&mut self) -> Result<&'a str> {
+        let word = self.peek_word();
+        match self.rest.trim_start() {
+            "" => bail!("unexpected end of line"),
+            rest if word.is_empty() => bail!("unexpected '{}'", rest),
+            _ => {}
+        }
+        self.rest = &self.rest.trim_start()[word.len()..];
+        Ok(word)
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if self.peek_word() == word {
+            self.rest = &self.rest.trim_start()[word.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        let word = self.word()?;
+        if !is_ident(word) {
+            bail!("expected a name, got '{}'", word);
+        }
+        Ok(word)
+    }
+
+    // %3
+    fn var(&mut self) -> Result<SSAVar> {
+        self.expect("%")?;
+        let id = self.word()?;
+        Ok(SSAVar::new(id.parse().map_err(|_| anyhow!("bad variable '%{}'", id))?))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.rest = self.rest.trim_start();
+        if self.rest.starts_with('%') {
+            return Ok(Value::Var(self.var()?));
+        }
+        if self.eat("[") {
+            return Ok(Value::Array(self.values_until("]")?));
+        }
+        if self.eat("(") {
+            return Ok(Value::Tuple(self.values_until(")")?));
+        }
+        if self.rest.starts_with('"') {
+            return Ok(Value::Str(self.string()?));
+        }
+        let word = self.word()?;
+        match word {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "inf" => Ok(Value::Float(f64::INFINITY)),
+            "-inf" => Ok(Value::Float(f64::NEG_INFINITY)),
+            "NaN" => Ok(Value::Float(f64::NAN)),
+            _ if word.contains(['.', 'e', 'E']) => {
+                Ok(Value::Float(word.parse().map_err(|_| anyhow!("bad float '{}'", word))?))
+            }
+            _ => Ok(Value::Int(word.parse().map_err(|_| anyhow!("expected a value, got '{}'", word))?)),
+        }
+    }
+
+    // Comma-separated values up to the closing bracket (already past the opening one)
+    fn values_until(&mut self, close: &str) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+        if self.eat(close) {
+            return Ok(values);
+        }
+        loop {
+            values.push(self.value()?);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(values)
+    }
+
+    // A string in Rust debug syntax: "a\"b\n"
+    fn string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('0') => out.push('\0'),
+                    Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().map(|(_, c)| c).skip(1).take_while(|&c| c != '}').collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        out.push(c.ok_or_else(|| anyhow!("bad escape '\\u{{{}}}'", hex))?);
+                    }
+                    other => bail!("bad escape '\\{}'", other.map_or(String::new(), String::from)),
+                },
+                c => out.push(c),
+            }
+        }
+        bail!("unterminated string")
+    }
+
+    // int, [float; 4], [int], (int, float), ...
+    fn ty(&mut self) -> Result<IRType> {
+        if self.eat("[") {
+            let elem = self.ty()?;
+            let size = if self.eat(";") {
+                let n = self.word()?;
+                Some(n.parse().map_err(|_| anyhow!("bad array size '{}'", n))?)
+            } else {
+                None
+            };
+            self.expect("]")?;
+            return Ok(IRType::Array(Box::new(elem), size));
+        }
+        if self.eat("(") {
+            let mut elems = Vec::new();
+            loop {
+                elems.push(self.ty()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+            return Ok(IRType::Tuple(elems));
+        }
+        Ok(match self.ident()? {
+            "int" => IRType::Int,
+            "float" => IRType::Float,
+            "bool" => IRType::Bool,
+            "qubit" => IRType::Qubit,
+            "void" => IRType::Void,
+            "complex" => IRType::Complex,
+            "int32" => IRType::Int32,
+            "int64" => IRType::Int64,
+            "float32" => IRType::Float32,
+            "float64" => IRType::Float64,
+            other => bail!("unknown type '{}'", other),
+        })
+    }
+}
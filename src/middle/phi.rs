@@ -0,0 +1,69 @@
+// Phi Elimination (out-of-SSA): replace each phi with a copy at the end of
+// every predecessor block, so backends without phi support see plain
+// assignments. `%d = phi [v1, b1], [v2, b2]` becomes `%d = v1` before b1's
+// terminator and `%d = v2` before b2's.
+//
+// The copies into one block happen at once: loop-header phis can read each
+// other (`a, b = b, a` across the back edge), so when a copy reads another
+// copy's destination, every source is first saved to a temporary.
+
+use super::ir::*;
+use std::collections::HashSet;
+
+pub fn eliminate_phis(module: &mut Module) {
+    for func in &mut module.functions {
+        eliminate_function_phis(func);
+    }
+}
+
+pub fn eliminate_function_phis(func: &mut IRFunction) {
+    // Collect the copies per predecessor label, then drop the phis
+    let mut copies: Vec<(String, Vec<(SSAVar, Value)>)> = Vec::new();
+    for block in &mut func.blocks {
+        block.instructions.retain(|inst| match inst {
+            Instruction::Phi { dest, incoming } => {
+                for (value, label) in incoming {
+                    let copy = (*dest, value.clone());
+                    match copies.iter_mut().find(|(l, _)| l == label) {
+                        Some((_, group)) => group.push(copy),
+                        None => copies.push((label.clone(), vec![copy])),
+                    }
+                }
+                false
+            }
+            _ => true,
+        });
+    }
+
+    for (label, group) in copies {
+        let dests: HashSet<SSAVar> = group.iter().map(|(dest, _)| *dest).collect();
+        let overlapping = group
+            .iter()
+            .any(|(dest, value)| matches!(value, Value::Var(src) if src != dest && dests.contains(src)));
+
+        let mut instructions = Vec::new();
+        if overlapping {
+            let mut saved = Vec::new();
+            for (dest, value) in group {
+                let temp = SSAVar::new(func.next_var_id);
+                func.next_var_id += 1;
+                if let Some(ty) = func.var_types.get(&dest).cloned() {
+                    func.var_types.insert(temp, ty);
+                }
+                instructions.push(Instruction::Assign { dest: temp, value });
+                saved.push((dest, temp));
+            }
+            for (dest, temp) in saved {
+                instructions.push(Instruction::Assign { dest, value: Value::Var(temp) });
+            }
+        } else {
+            for (dest, value) in group {
+                instructions.push(Instruction::Assign { dest, value });
+            }
+        }
+
+        if let Some(block) = func.blocks.iter_mut().find(|b| b.label == label) {
+            block.instructions.extend(instructions);
+        }
+    }
+}
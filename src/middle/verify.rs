@@ -0,0 +1,217 @@
+// IR Verification: Check structural and SSA invariants of a Module
+// Catches passes that rewrite instructions into invalid IR (use before
+// definition, double assignment, dangling branch targets)
+//
+// SSA requires every use of a variable to be dominated by its definition
+
+use super::analysis::{compute_dominators, reachable_blocks, terminator_targets};
+use super::ir::*;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+
+pub fn verify(module: &Module) -> Result<()> {
+    for func in &module.functions {
+        verify_function(func)?;
+    }
+    Ok(())
+}
+
+fn verify_function(func: &IRFunction) -> Result<()> {
+    if func.blocks.is_empty() {
+        bail!("{}: function has no blocks", func.name);
+    }
+
+    // Block labels must be unique. Each block holds exactly one terminator by
+    // construction, so the remaining structural check is that every branch
+    // target names an existing block.
+    let mut block_index: HashMap<&str, usize> = HashMap::new();
+    for (i, block) in func.blocks.iter().enumerate() {
+        if block_index.insert(block.label.as_str(), i).is_some() {
+            bail!("{}: duplicate block label '{}'", func.name, block.label);
+        }
+    }
+
+    let mut successors: Vec<Vec<usize>> = Vec::new();
+    for block in &func.blocks {
+        let mut succ = Vec::new();
+        for target in terminator_targets(&block.terminator) {
+            match block_index.get(target) {
+                Some(&idx) => succ.push(idx),
+                None => bail!(
+                    "{}: block '{}' jumps to unknown label '{}'",
+                    func.name,
+                    block.label,
+                    target
+                ),
+            }
+        }
+        successors.push(succ);
+    }
+
+    // Single definition: parameters are defined on entry, every other
+    // variable by exactly one instruction
+    // def_site: var -> (block index, instruction index)
+    let mut def_site: HashMap<SSAVar, (usize, usize)> = HashMap::new();
+    for i in 0..func.params.len() {
+        def_site.insert(SSAVar::new(i), (0, 0));
+    }
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            if let Some(dest) = defined_var(inst) {
+                if def_site.insert(dest, (b, i + 1)).is_some() {
+                    bail!(
+                        "{}: {} is defined more than once (again in block '{}')",
+                        func.name,
+                        dest,
+                        block.label
+                    );
+                }
+            }
+        }
+    }
+
+    let reachable = reachable_blocks(&successors);
+    let dominators = compute_dominators(&successors, &reachable);
+
+    // Dominance: check every use in reachable code
+    for (b, block) in func.blocks.iter().enumerate() {
+        if !reachable.contains(&b) {
+            continue;
+        }
+
+        for (i, inst) in block.instructions.iter().enumerate() {
+            if let Instruction::Phi { incoming, .. } = inst {
+                // Phi operands are used at the end of the incoming edge's block
+                for (value, label) in incoming {
+                    let pred = match block_index.get(label.as_str()) {
+                        Some(&idx) => idx,
+                        None => bail!(
+                            "{}: phi in block '{}' names unknown predecessor '{}'",
+                            func.name,
+                            block.label,
+                            label
+                        ),
+                    };
+                    let end = func.blocks[pred].instructions.len() + 1;
+                    for var in value_vars(value) {
+                        check_dominated(func, &def_site, &dominators, var, pred, end)?;
+                    }
+                }
+                continue;
+            }
+
+            for var in used_vars(inst) {
+                check_dominated(func, &def_site, &dominators, var, b, i + 1)?;
+            }
+        }
+
+        let end = block.instructions.len() + 1;
+        for var in terminator_vars(&block.terminator) {
+            check_dominated(func, &def_site, &dominators, var, b, end)?;
+        }
+    }
+
+    Ok(())
+}
+
+// A use at (block, position) is valid if the definition is earlier in the
+// same block or in a block that dominates it
+fn check_dominated(
+    func: &IRFunction,
+    def_site: &HashMap<SSAVar, (usize, usize)>,
+    dominators: &[HashSet<usize>],
+    var: SSAVar,
+    use_block: usize,
+    use_pos: usize,
+) -> Result<()> {
+    let (def_block, def_pos) = match def_site.get(&var) {
+        Some(site) => *site,
+        None => bail!(
+            "{}: {} is used in block '{}' but never defined",
+            func.name,
+            var,
+            func.blocks[use_block].label
+        ),
+    };
+
+    let ok = if def_block == use_block {
+        def_pos < use_pos
+    } else {
+        dominators[use_block].contains(&def_block)
+    };
+
+    if !ok {
+        bail!(
+            "{}: use of {} in block '{}' is not dominated by its definition in block '{}'",
+            func.name,
+            var,
+            func.blocks[use_block].label,
+            func.blocks[def_block].label
+        );
+    }
+    Ok(())
+}
+
+fn defined_var(inst: &Instruction) -> Option<SSAVar> {
+    match inst {
+        Instruction::Assign { dest, .. }
+        | Instruction::BinaryOp { dest, .. }
+        | Instruction::UnaryOp { dest, .. }
+        | Instruction::Cast { dest, .. }
+        | Instruction::Load { dest, .. }
+        | Instruction::Phi { dest, .. }
+        | Instruction::DomainConversion { dest, .. } => Some(*dest),
+        Instruction::Call { dest, .. } => *dest,
+        Instruction::Store { .. } => None,
+    }
+}
+
+fn used_vars(inst: &Instruction) -> Vec<SSAVar> {
+    let mut vars = Vec::new();
+    match inst {
+        Instruction::Assign { value, .. } => vars.extend(value_vars(value)),
+        Instruction::BinaryOp { left, right, .. } => {
+            vars.extend(value_vars(left));
+            vars.extend(value_vars(right));
+        }
+        Instruction::UnaryOp { operand, .. } => vars.extend(value_vars(operand)),
+        Instruction::Cast { value, .. } => vars.extend(value_vars(value)),
+        Instruction::Load { array, index, .. } => {
+            vars.push(*array);
+            vars.extend(value_vars(index));
+        }
+        Instruction::Store { array, index, value, .. } => {
+            vars.push(*array);
+            vars.extend(value_vars(index));
+            vars.extend(value_vars(value));
+        }
+        Instruction::Call { args, .. } => {
+            for arg in args {
+                vars.extend(value_vars(arg));
+            }
+        }
+        Instruction::Phi { incoming, .. } => {
+            for (value, _) in incoming {
+                vars.extend(value_vars(value));
+            }
+        }
+        Instruction::DomainConversion { source, .. } => vars.extend(value_vars(source)),
+    }
+    vars
+}
+
+fn terminator_vars(term: &Terminator) -> Vec<SSAVar> {
+    match term {
+        Terminator::Return(val) => value_vars(val),
+        Terminator::Branch { condition, .. } => value_vars(condition),
+        Terminator::ReturnVoid | Terminator::Jump(_) => vec![],
+    }
+}
+
+fn value_vars(value: &Value) -> Vec<SSAVar> {
+    match value {
+        Value::Var(v) => vec![*v],
+        Value::Array(elements) | Value::Tuple(elements) => elements.iter().flat_map(value_vars).collect(),
+        _ => vec![],
+    }
+}
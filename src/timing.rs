@@ -0,0 +1,52 @@
+// Pass Timing: with --time-passes every optimization pass and backend is
+// wrapped in an Instant measurement; runs of the same pass (one per
+// fixed-point iteration, per function) add up under its name
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// (pass name, total time, runs), in order of first run
+thread_local! {
+    static TIMINGS: RefCell<Vec<(&'static str, Duration, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+// Run `f`, charging its duration to `name` when timing is on
+pub fn time<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    TIMINGS.with(|t| {
+        let mut timings = t.borrow_mut();
+        match timings.iter_mut().find(|(n, ..)| *n == name) {
+            Some((_, total, runs)) => {
+                *total += elapsed;
+                *runs += 1;
+            }
+            None => timings.push((name, elapsed, 1)),
+        }
+    });
+    result
+}
+
+// Table of everything timed so far (clearing it), for stderr
+pub fn report() -> String {
+    let timings = TIMINGS.with(|t| std::mem::take(&mut *t.borrow_mut()));
+    let mut output = String::from("=== Pass timings ===\n");
+    output.push_str(&format!("{:<32} {:>10} {:>10}\n", "pass", "ms", "iterations"));
+    let mut total = Duration::ZERO;
+    for (name, elapsed, runs) in &timings {
+        output.push_str(&format!("{:<32} {:>10.3} {:>10}\n", name, elapsed.as_secs_f64() * 1000.0, runs));
+        total += *elapsed;
+    }
+    output.push_str(&format!("{:<32} {:>10.3}\n", "total", total.as_secs_f64() * 1000.0));
+    output
+}
@@ -0,0 +1,62 @@
+// The library API as an embedder (e.g. a playground server) uses it: source
+// text in, generated code or a located error out, without the CLI.
+
+use quarkdsl::frontend::SourceError;
+use quarkdsl::{codegen, compile_to_ir, compile_to_string, Target};
+
+const SOURCE: &str = "@gpu fn square(x: float) -> float { return x * x; }
+@quantum fn coin() -> int { let q = qalloc(1); h(q[0]); return measure(q[0]); }
+fn main() -> float { let c = coin(); return square(2.0); }
+";
+
+#[test]
+fn compiles_source_to_each_target() {
+    let wgsl = compile_to_string(SOURCE, Target::Wgsl, true).unwrap();
+    assert!(wgsl.contains("fn square(x: f32) -> f32"), "{}", wgsl);
+
+    let quantum = compile_to_string(SOURCE, Target::Quantum, true).unwrap();
+    assert!(quantum.contains("circuit.h(0)"), "{}", quantum);
+
+    let python = compile_to_string(SOURCE, Target::Orchestrator, true).unwrap();
+    assert!(python.contains("def main():"), "{}", python);
+
+    // compile_to_string is compile_to_ir followed by codegen
+    let ir = compile_to_ir(SOURCE, true, &Default::default()).unwrap();
+    assert_eq!(ir.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["square", "coin", "main"]);
+    assert_eq!(codegen(&ir, Target::Orchestrator, None, false).unwrap(), python);
+
+    // Several files at once go through codegen_all instead
+    let error = compile_to_string(SOURCE, Target::All, true).unwrap_err();
+    assert!(error.to_string().contains("use codegen_all"), "{}", error);
+}
+
+#[test]
+fn errors_carry_their_source_location() {
+    let source = "fn main() -> int {\n    let x: bool = 1;\n    return 0;\n}\n";
+    let error = compile_to_string(source, Target::Wgsl, false).unwrap_err();
+    assert_eq!(error.to_string(), "Type checking failed");
+
+    // An embedder can underline the offending statement itself
+    let at = error.chain().find_map(|e| e.downcast_ref::<SourceError>()).expect("no location");
+    assert_eq!((at.line, at.column), (2, 5));
+    assert_eq!(&source[at.span.clone()], "let x: bool = 1;");
+
+    let error = compile_to_ir("fn main() -> int { return 1 +; }", false, &Default::default()).unwrap_err();
+    assert_eq!(error.to_string(), "Failed to parse source");
+    assert!(error.chain().any(|e| e.downcast_ref::<SourceError>().is_some()));
+}
+
+#[test]
+fn strict_codegen_rejects_map() {
+    let source = "fn double(x: int) -> int { return x * 2; }
+                  fn main() -> int { let ys = map(double, [1, 2]); return ys[0]; }";
+    let ir = compile_to_ir(source, false, &Default::default()).unwrap();
+
+    for target in [Target::Wgsl, Target::Orchestrator] {
+        // Without strict the call to the undefined `map_double` is kept (with a warning)
+        assert!(codegen(&ir, target, None, false).unwrap().contains("map_double("));
+
+        let error = codegen(&ir, target, None, true).unwrap_err();
+        assert_eq!(error.to_string(), "map(double, ...) in 'main' is not supported by this backend");
+    }
+}
@@ -0,0 +1,249 @@
+// The quarkdsl binary end to end: commands, input handling and where
+// diagnostics go.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A source file in a fresh per-test temp directory
+fn write_source(test: &str, name: &str, source: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("quarkdsl-cli-{}-{}", std::process::id(), test));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn quarkdsl(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_quarkdsl"))
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    assert!(!output.status.success(), "expected a failure");
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn estimate_bell_state() {
+    let input = write_source(
+        "estimate",
+        "bell.qk",
+        "@quantum fn bell() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[0]); }",
+    );
+    let table = stdout(&quarkdsl(&["estimate", input.to_str().unwrap()]));
+
+    assert!(table.contains("Function: bell\n"), "{}", table);
+    assert!(table.contains("  Qubits:          2\n"), "{}", table);
+    assert!(table.contains("  2-qubit depth:   1\n"), "{}", table);
+    assert!(table.contains("    h              1\n"), "{}", table);
+    assert!(table.contains("    cx             1\n"), "{}", table);
+}
+
+#[test]
+fn emit_deps_writes_requirements_next_to_output() {
+    let quantum = write_source(
+        "deps-quantum",
+        "bell.qk",
+        "@quantum fn bell() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[0]); }",
+    );
+    let out = quantum.with_file_name("bell.py");
+    stdout(&quarkdsl(&[
+        "compile", quantum.to_str().unwrap(), "-t", "orchestrator", "-o", out.to_str().unwrap(), "--emit-deps",
+    ]));
+    let deps = std::fs::read_to_string(out.with_file_name("requirements.txt")).unwrap();
+    for package in ["numpy==", "qiskit==", "qiskit-aer==", "qiskit-ibm-runtime=="] {
+        assert!(deps.lines().any(|l| l.starts_with(package)), "{}", deps);
+    }
+
+    let gpu = write_source("deps-gpu", "kernel.qk", "@gpu fn k(x: [float; 4]) -> float { return x[0]; }");
+    let out = gpu.with_file_name("kernel.py");
+    stdout(&quarkdsl(&[
+        "compile", gpu.to_str().unwrap(), "-t", "orchestrator", "-o", out.to_str().unwrap(), "--emit-deps",
+    ]));
+    let deps = std::fs::read_to_string(out.with_file_name("requirements.txt")).unwrap();
+    assert!(deps.contains("numpy=="), "{}", deps);
+    assert!(!deps.contains("qiskit"), "{}", deps);
+}
+
+#[test]
+fn info_only_with_verbose_and_diagnostics_on_stderr() {
+    let input = write_source(
+        "verbosity",
+        "hybrid.qk",
+        "@quantum fn q(theta: float) -> int { let q = qalloc(1); ry(q[0], theta); return 0; }
+         fn main() -> int { return q(0.5); }",
+    );
+    let input = input.to_str().unwrap();
+    let run = |flags: &[&str]| {
+        let mut args = flags.to_vec();
+        args.extend(["compile", "-O", "-t", "wgsl", input]);
+        let output = quarkdsl(&args);
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+        (stdout(&output), stderr)
+    };
+
+    let (shader, stderr) = run(&[]);
+    assert!(!stderr.contains("INFO:"), "{}", stderr);
+    assert!(stderr.contains("WARNING: @quantum function 'q' applies gates but never measures"), "{}", stderr);
+    // Diagnostics never end up in the generated code
+    assert!(!shader.contains("WARNING") && !shader.contains("INFO"), "{}", shader);
+
+    let (verbose_shader, stderr) = run(&["-v"]);
+    assert!(stderr.contains("INFO: Running optimization passes..."), "{}", stderr);
+    assert_eq!(verbose_shader, shader);
+
+    let (_, stderr) = run(&["-q"]);
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn stdin_compiles_like_a_file() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let source = "@gpu fn double(x: [float; 4]) -> [float; 4] { return x + x; }
+                  fn main() -> int { return 1 + 2; }";
+    let input = write_source("stdin", "prog.qk", source);
+
+    for command in [&["compile", "-t", "wgsl"][..], &["compile", "-t", "orchestrator"], &["lower"], &["parse"]] {
+        let from_file = stdout(&quarkdsl(&[command, &[input.to_str().unwrap()]].concat()));
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_quarkdsl"))
+            .args([command, &["-"]].concat())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(source.as_bytes()).unwrap();
+        let from_stdin = stdout(&child.wait_with_output().unwrap());
+
+        assert_eq!(from_stdin, from_file, "{:?}", command);
+    }
+}
+
+#[test]
+fn target_all_writes_three_files() {
+    let input = write_source(
+        "target-all",
+        "mixed.qk",
+        "@gpu fn k(x: [float; 4]) -> float { return x[0]; }
+         @quantum fn q() -> int { let q = qalloc(1); h(q[0]); return measure(q[0]); }
+         fn main() -> int { return q(); }",
+    );
+    let out = input.with_file_name("out");
+    stdout(&quarkdsl(&["compile", input.to_str().unwrap(), "-t", "all", "-o", out.to_str().unwrap()]));
+
+    // Each function goes to the backend of its domain
+    let shader = std::fs::read_to_string(out.join("out.wgsl")).unwrap();
+    assert!(shader.contains("fn k(x: array<f32, 4>) -> f32 {"), "{}", shader);
+    assert!(!shader.contains("fn q(") && !shader.contains("fn main("), "{}", shader);
+
+    let circuits = std::fs::read_to_string(out.join("out_quantum.py")).unwrap();
+    assert!(circuits.contains("def build_q():"), "{}", circuits);
+    assert!(!circuits.contains("build_k") && !circuits.contains("build_main"), "{}", circuits);
+
+    let orchestrator = std::fs::read_to_string(out.join("orchestrator.py")).unwrap();
+    for def in ["def k(x):", "def q():", "def main():"] {
+        assert!(orchestrator.contains(def), "{}", orchestrator);
+    }
+}
+
+#[test]
+fn error_points_at_the_source() {
+    let input = write_source("caret", "bad.qk", "fn main() -> int {\n    let x = 1 +;\n    return x;\n}\n");
+    let error = stderr(&quarkdsl(&["compile", input.to_str().unwrap(), "-t", "wgsl"]));
+
+    assert!(!error.contains('\x1b'), "{:?}", error);
+    assert!(error.contains("Unexpected token ';' in expression"), "{}", error);
+    assert!(error.contains("--> line 2, column 16"), "{}", error);
+
+    // The source line, with the caret right under the `;`
+    let lines: Vec<&str> = error.lines().collect();
+    let at = lines.iter().position(|l| l.ends_with("2 |     let x = 1 +;")).expect(&error);
+    assert_eq!(lines[at + 1].find('^'), lines[at].find(';'), "{}", error);
+    assert_eq!(lines[at + 1].matches('^').count(), 1, "{}", error);
+}
+
+#[test]
+fn strict_rejects_dropped_gates() {
+    // A gate on a runtime qubit index has no circuit form
+    let input = write_source("strict", "f.qk", "@quantum fn f(a: int) -> int { let q = qalloc(1); h(q[a]); return measure(q[0]); }");
+    let input = input.to_str().unwrap();
+
+    let python = stdout(&quarkdsl(&["compile", input, "-t", "orchestrator"]));
+    assert!(python.contains("    # h gate (invalid args)\n"), "{}", python);
+
+    let error = stderr(&quarkdsl(&["compile", "--strict", input, "-t", "orchestrator"]));
+    assert!(error.contains("unsupported instruction in @quantum function 'f': %3 = call h(%2)"), "{}", error);
+    let error = stderr(&quarkdsl(&["compile", "--strict", input, "-t", "quantum"]));
+    assert!(error.contains("unsupported instruction in circuit 'f': %3 = call h(%2)"), "{}", error);
+}
+
+#[test]
+fn time_passes_reports_each_pass() {
+    let input = write_source("time-passes", "f.qk", "fn f(x: int) -> int { return x * 3 + 1; }");
+    let output = quarkdsl(&["compile", "--time-passes", "-O", input.to_str().unwrap(), "-t", "wgsl"]);
+    assert!(stdout(&output).contains("fn f(x: i32) -> i32"));
+
+    // The table goes to stderr: name, milliseconds, iterations
+    let report = String::from_utf8(output.stderr).unwrap();
+    assert!(report.contains("=== Pass timings ===\n"), "{}", report);
+    for pass in ["parse", "typecheck", "lower", "constant_folding", "dead_code_elimination", "codegen wgsl"] {
+        let line = report.lines().find(|l| l.starts_with(&format!("{} ", pass))).unwrap_or_else(|| panic!("{}", report));
+        let columns: Vec<&str> = line[pass.len()..].split_whitespace().collect();
+        assert!(columns.len() == 2 && columns[0].parse::<f64>().is_ok(), "{}", line);
+    }
+    assert!(report.lines().any(|l| l.starts_with("total ")), "{}", report);
+}
+
+#[test]
+fn json_error_and_warning() {
+    let source = "@quantum fn q() -> int { let q = qalloc(1); h(q[0]); return 0; }\nfn f() -> int {\n    let x = 1;\n    let y: bool = x;\n    return x;\n}\n";
+    let input = write_source("json", "two.qk", source);
+    let output = quarkdsl(&["--message-format", "json", "compile", input.to_str().unwrap(), "-t", "wgsl"]);
+    let diagnostics: Vec<serde_json::Value> =
+        stderr(&output).lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+
+    // The lint runs before the type error stops compilation
+    assert_eq!(diagnostics[0]["severity"], "warning");
+    assert!(diagnostics[0]["message"].as_str().unwrap().starts_with("@quantum function 'q' applies gates but never measures"));
+
+    // Located at the statement
+    let start = source.find("let y").unwrap();
+    assert_eq!(
+        diagnostics[1],
+        serde_json::json!({
+            "severity": "error",
+            "message": "Type mismatch: expected bool, got int",
+            "line": 4,
+            "col": 5,
+            "span": { "start": start, "end": start + "let y: bool = x;".len() },
+        })
+    );
+}
+
+#[test]
+fn warns_when_a_circuit_never_measures() {
+    let warning = "WARNING: @quantum function 'q' applies gates but never measures";
+
+    let input = write_source("unmeasured", "q.qk", "@quantum fn q() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return 0; }");
+    let output = quarkdsl(&["compile", input.to_str().unwrap(), "-t", "quantum"]);
+    stdout(&output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains(warning), "{:?}", output);
+
+    let input = write_source("measured", "q.qk", "@quantum fn q() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[1]); }");
+    let output = quarkdsl(&["compile", input.to_str().unwrap(), "-t", "quantum"]);
+    stdout(&output);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("never measures"), "{:?}", output);
+}
@@ -0,0 +1,82 @@
+// IR dumps: textual IR, typed IR and the Graphviz CFG.
+
+use quarkdsl::compile_to_ir;
+use quarkdsl::middle::{dump_cfg_dot, dump_ir, dump_ir_typed, parse_ir};
+
+#[test]
+fn cfg_dot_has_branch_edges() {
+    let ir = compile_to_ir(
+        "fn clamp(x: int) -> int {
+             if x > 10 { return 10; } else { return x; }
+         }",
+        false,
+        &Default::default(),
+    )
+    .unwrap();
+    let dot = dump_cfg_dot(&ir);
+
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("subgraph \"cluster_clamp\""));
+    assert!(dot.contains("\"clamp.entry\" [label=\"entry\\n1 instrs\"];"));
+    assert!(dot.contains("\"clamp.entry\" -> \"clamp.if_then_0\" [label=\"true\"];"));
+    assert!(dot.contains("\"clamp.entry\" -> \"clamp.if_else_0\" [label=\"false\"];"));
+    // Returning blocks have no outgoing edges
+    assert_eq!(dot.matches(" -> ").count(), 2);
+}
+
+#[test]
+fn typed_dump_annotates_each_ssa_var() {
+    let ir = compile_to_ir(
+        "fn f(x: int, y: float) -> float { let a = x + 1; return to_float(a) * y; }",
+        false,
+        &Default::default(),
+    )
+    .unwrap();
+    let dump = dump_ir_typed(&ir);
+
+    assert!(dump.contains("%2: int = add %0 1"), "{}", dump);
+    assert!(dump.contains(": float = cast %3 to float"), "{}", dump);
+    assert!(dump.contains(": float = mul "), "{}", dump);
+    // The untyped dump is unchanged
+    assert!(dump_ir(&ir).contains("%2 = add %0 1"));
+}
+
+#[test]
+fn casts_dump_fold_and_emit() {
+    let source = "fn f(n: int, x: float) -> float { let y = to_float(n); let m = to_int(x); print_int(m); return y; }
+                  fn g() -> float { return to_float(3); }";
+    let dump = dump_ir(&compile_to_ir(source, false, &Default::default()).unwrap());
+    assert!(dump.contains("    %2 = cast %0 to float\n"), "{}", dump);
+    assert!(dump.contains("    %4 = cast %1 to int\n"), "{}", dump);
+
+    // A constant cast folds away
+    let optimized = compile_to_ir(source, true, &Default::default()).unwrap();
+    let g = optimized.functions.iter().find(|f| f.name == "g").unwrap();
+    assert_eq!(g.blocks[0].terminator, quarkdsl::middle::ir::Terminator::Return(quarkdsl::middle::ir::Value::Float(3.0)));
+
+    let shader = quarkdsl::compile_to_string(source, quarkdsl::Target::Wgsl, false).unwrap();
+    assert!(shader.contains(" = f32(n);\n") && shader.contains(" = i32(x);\n"), "{}", shader);
+    let python = quarkdsl::compile_to_string(source, quarkdsl::Target::Orchestrator, false).unwrap();
+    assert!(python.contains(" = float(n)\n") && python.contains(" = int(x)\n"), "{}", python);
+}
+
+#[test]
+fn plain_dump_reads_back_as_the_same_module() {
+    let source = "fn f(c: bool, n: int) -> int {
+                      let mut y = 0;
+                      if c { y = 1; } else { y = 2; }
+                      let xs = [1, 2, 3];
+                      print_fmt(\"y={} \\\"{}\\\"\", y, xs[0]);
+                      return y + n;
+                  }";
+    // A plain dump carries no call lines or inferred types, so start from
+    // the module it describes
+    let lowered = compile_to_ir(source, false, &Default::default()).unwrap();
+    let module = parse_ir(&dump_ir(&lowered)).unwrap();
+    let dump = dump_ir(&module);
+    assert!(dump.contains("phi ["), "{}", dump);
+    assert!(dump.contains("br %0"), "{}", dump);
+    assert!(dump.contains("[1, 2, 3]"), "{}", dump);
+    assert!(dump.contains(r#"print_fmt("y={} \"{}\""#), "{}", dump);
+    assert_eq!(parse_ir(&dump).unwrap(), module, "{}", dump);
+}
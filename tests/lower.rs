@@ -0,0 +1,322 @@
+// Lowering regressions: the shape of the IR for specific language features,
+// checked on the blocks and instructions rather than on a full dump.
+
+use quarkdsl::middle::ir::*;
+
+fn lower(source: &str) -> Module {
+    let ast = quarkdsl::parse(source).unwrap();
+    quarkdsl::typecheck(&ast).unwrap();
+    quarkdsl::lower(&ast, &Default::default()).unwrap()
+}
+
+fn block<'a>(func: &'a IRFunction, label: &str) -> &'a BasicBlock {
+    func.blocks
+        .iter()
+        .find(|b| b.label == label)
+        .unwrap_or_else(|| panic!("no block '{}' in {}", label, func.name))
+}
+
+#[test]
+fn break_and_continue_jump_to_loop_exit_and_latch() {
+    let module = lower(
+        "fn main(n: int) -> int {
+             let mut s = 0;
+             for i in 0..n {
+                 if i == 3 { continue; }
+                 if i == 7 { break; }
+                 s = s + i;
+             }
+             return s;
+         }",
+    );
+    let func = &module.functions[0];
+
+    // A loop with break/continue is kept as a CFG, not unrolled
+    let header = block(func, "for_header_0");
+    assert!(matches!(
+        &header.terminator,
+        Terminator::Branch { true_label, false_label, .. }
+            if true_label == "for_body_0" && false_label == "for_exit_0"
+    ));
+    assert_eq!(block(func, "if_then_1").terminator, Terminator::Jump("for_latch_0".into()));
+    assert_eq!(block(func, "if_then_2").terminator, Terminator::Jump("for_exit_0".into()));
+    assert_eq!(block(func, "for_latch_0").terminator, Terminator::Jump("for_header_0".into()));
+}
+
+#[test]
+fn logical_and_short_circuits_through_a_phi() {
+    let module = lower("fn f(a: bool, b: bool) -> bool { return a && b; }");
+    let func = &module.functions[0];
+
+    // The right operand is only evaluated when `a` is true
+    assert!(matches!(
+        &block(func, "entry").terminator,
+        Terminator::Branch { condition: Value::Var(SSAVar { id: 0 }), true_label, false_label }
+            if true_label == "and_rhs_0" && false_label == "and_merge_0"
+    ));
+    assert_eq!(block(func, "and_rhs_0").terminator, Terminator::Jump("and_merge_0".into()));
+
+    let merge = block(func, "and_merge_0");
+    let Instruction::Phi { dest, incoming } = &merge.instructions[0] else {
+        panic!("expected a phi, got {:?}", merge.instructions[0]);
+    };
+    assert_eq!(
+        incoming,
+        &vec![
+            (Value::Bool(false), "entry".to_string()),
+            (Value::Var(SSAVar { id: 1 }), "and_rhs_0".to_string()),
+        ]
+    );
+    assert_eq!(merge.terminator, Terminator::Return(Value::Var(*dest)));
+}
+
+#[test]
+fn logical_or_short_circuits_on_true() {
+    let module = lower("fn f(a: bool, b: bool) -> bool { return a || b; }");
+    let func = &module.functions[0];
+    let Terminator::Branch { false_label, .. } = &block(func, "entry").terminator else {
+        panic!("expected a branch");
+    };
+    let Instruction::Phi { incoming, .. } = &func.blocks.last().unwrap().instructions[0] else {
+        panic!("expected a phi");
+    };
+    // `b` is only evaluated when `a` is false; otherwise the result is true
+    assert!(incoming.contains(&(Value::Bool(true), "entry".to_string())));
+    assert!(incoming.iter().any(|(_, label)| label == false_label));
+}
+
+#[test]
+fn const_is_inlined_as_angle_and_array_size() {
+    let source = "const THETA: float = 0.25;
+                  const N: int = 3;
+                  @quantum fn q() -> int { let q = qalloc(1); ry(q[0], THETA); return measure(q[0]); }
+                  fn g() -> int { let a: [int; N] = [1, 2, 3]; return a[0]; }";
+    let module = lower(source);
+
+    let ry = &module.functions[0].blocks[0].instructions[1];
+    assert!(
+        matches!(ry, Instruction::Call { function, args, .. }
+            if function == "ry" && args == &vec![Value::Int(0), Value::Float(0.25)]),
+        "{:?}",
+        ry
+    );
+
+    let circuit = quarkdsl::compile_to_string(source, quarkdsl::Target::Quantum, false).unwrap();
+    assert!(circuit.contains("circuit.ry(0.25, "), "{}", circuit);
+}
+
+#[test]
+fn complex_values_and_addition() {
+    let source = "fn f() -> complex { let a = complex(1.0, 0.0); let b = complex(0.5, 2.0); return a + b; }";
+    let module = lower(source);
+    let func = &module.functions[0];
+
+    let Instruction::Call { dest: Some(a), function, args, .. } = &func.blocks[0].instructions[0] else {
+        panic!("expected a call to complex");
+    };
+    assert_eq!(function, "complex");
+    assert_eq!(args, &vec![Value::Float(1.0), Value::Float(0.0)]);
+    assert_eq!(func.var_types[a], IRType::Complex);
+
+    let sum = func.blocks[0].instructions.iter().find_map(|inst| match inst {
+        Instruction::BinaryOp { dest, op: BinOp::Add, .. } => Some(dest),
+        _ => None,
+    });
+    assert_eq!(func.var_types[sum.unwrap()], IRType::Complex);
+
+    let python = quarkdsl::compile_to_string(source, quarkdsl::Target::Orchestrator, false).unwrap();
+    assert!(python.contains(" = complex(1.0, 0.0)\n"), "{}", python);
+    let wgsl = quarkdsl::compile_to_string(source, quarkdsl::Target::Wgsl, false).unwrap();
+    assert!(wgsl.contains("fn f() -> vec2<f32> {"), "{}", wgsl);
+    assert!(wgsl.contains(" = vec2<f32>(1.0, 0.0);"), "{}", wgsl);
+}
+
+#[test]
+fn enum_member_lowers_to_its_integer() {
+    let module = lower(
+        "enum Qubit { Ancilla = 0, Data, Spare = 5 }
+         fn f() -> int { return Qubit::Data + Qubit::Spare; }",
+    );
+    let Instruction::BinaryOp { left, right, .. } = &module.functions[0].blocks[0].instructions[0] else {
+        panic!("expected an add");
+    };
+    assert_eq!((left, right), (&Value::Int(1), &Value::Int(5)));
+}
+
+#[test]
+fn conversions_only_on_cross_domain_calls() {
+    use quarkdsl::frontend::ast::Domain;
+
+    let module = lower(
+        "@quantum fn prep(a: float) -> int { let q = qalloc(1); ry(q[0], a); return measure(q[0]); }
+         @quantum fn outer() -> int { let q = qalloc(2); h(q[1]); let r = prep(0.5); return r; }
+         fn main() -> int { let x = 0.25; let r = prep(x); return r; }",
+    );
+    let conversions = |name: &str| {
+        let func = module.functions.iter().find(|f| f.name == name).unwrap();
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::DomainConversion { from_domain, to_domain, .. } => Some((from_domain.clone(), to_domain.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Quantum to quantum: the argument is passed through untouched
+    assert_eq!(conversions("outer"), vec![]);
+    assert_eq!(conversions("main"), vec![(Domain::Classical, Domain::Quantum)]);
+}
+
+#[test]
+fn undefined_callee_fails_in_both_phases() {
+    let source = "fn f() -> int { return later(1); }
+                  fn later(x: int) -> int { return x; }
+                  fn g() -> int { return nope(1); }";
+    let ast = quarkdsl::parse(source).unwrap();
+    let typecheck_error = format!("{:#}", quarkdsl::typecheck(&ast).unwrap_err());
+    let lower_error = format!("{:#}", quarkdsl::lower(&ast, &Default::default()).unwrap_err());
+    assert!(typecheck_error.contains("Undefined function: nope"), "{}", typecheck_error);
+    assert!(lower_error.contains("Undefined function: nope"), "{}", lower_error);
+
+    // Forward references are fine
+    lower("fn f() -> int { return later(1); } fn later(x: int) -> int { return x; }");
+}
+
+#[test]
+fn if_expression_merges_through_a_phi() {
+    let module = lower("fn f(c: bool) -> int { let x = if c { let a = 1; a + 1 } else { 2 }; return x; }");
+    let func = &module.functions[0];
+    assert!(matches!(func.blocks[0].terminator, Terminator::Branch { .. }));
+
+    let Instruction::Phi { incoming, .. } = &block(func, "if_merge_0").instructions[0] else {
+        panic!("expected a phi");
+    };
+    let else_value = incoming.iter().find(|(_, label)| label == "if_else_0").map(|(value, _)| value);
+    assert_eq!(else_value, Some(&Value::Int(2)));
+
+    let error = |source: &str| format!("{:#}", quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap_err());
+    let mismatch = error("fn f(c: bool) -> int { let x = if c { 1 } else { 2.0 }; return x; }");
+    assert!(mismatch.contains("`if` branches have different types: int and float"), "{}", mismatch);
+    let missing = error("fn f(c: bool) -> int { let x = if c { 1 }; return x; }");
+    assert!(missing.contains("`if` used as a value needs an `else` branch"), "{}", missing);
+}
+
+#[test]
+fn qalloc_in_unrolled_loop_counts_every_iteration() {
+    let source = "@quantum fn q() -> int {
+                      let mut m = 0;
+                      for i in 0..2 { let r = qalloc(3000); m = measure(r[0]); }
+                      return m;
+                  }";
+    let ast = quarkdsl::parse(source).unwrap();
+    quarkdsl::typecheck(&ast).unwrap();
+    let error = format!("{:#}", quarkdsl::lower(&ast, &Default::default()).unwrap_err());
+    assert_eq!(error, "qalloc(3000) allocates past the limit of 4096 qubits (3000 already allocated in this function)");
+
+    // Each iteration gets fresh qubits
+    let module = lower("@quantum fn q() -> int { let mut m = 0; for i in 0..2 { let r = qalloc(3); m = measure(r[0]); } return m; }");
+    let measured: Vec<&Value> = module.functions[0]
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .filter_map(|inst| match inst {
+            Instruction::Call { function, args, .. } if function == "measure" => Some(&args[0]),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(measured, vec![&Value::Int(0), &Value::Int(3)]);
+}
+
+#[test]
+fn reassignment_defines_a_new_version() {
+    let module = lower("fn main() -> int { let mut x = 1; x = x + 2; return x; }");
+    let entry = block(&module.functions[0], "entry");
+    let v = |id| SSAVar { id };
+
+    assert_eq!(
+        entry.instructions,
+        vec![
+            Instruction::Assign { dest: v(0), value: Value::Int(1) },
+            Instruction::BinaryOp { dest: v(1), op: BinOp::Add, left: Value::Var(v(0)), right: Value::Int(2) },
+            Instruction::Assign { dest: v(2), value: Value::Var(v(1)) },
+        ]
+    );
+    // The return reads the latest version, not the first binding
+    assert_eq!(entry.terminator, Terminator::Return(Value::Var(v(2))));
+}
+
+#[test]
+fn empty_array_repeat_keeps_its_type() {
+    let module = lower("fn main() -> int { let a = [1; 0]; let z = zeros(0); return len(a) + len(z); }");
+    let func = &module.functions[0];
+    let array_type = |value: &Value| match value {
+        Value::Var(v) => func.var_types[v].clone(),
+        _ => panic!("expected a variable, got {:?}", value),
+    };
+    let entry = block(func, "entry");
+
+    let Instruction::Assign { dest, value: Value::Array(elements) } = &entry.instructions[0] else {
+        panic!("expected an empty array, got {:?}", entry.instructions[0]);
+    };
+    assert!(elements.is_empty());
+    assert_eq!(array_type(&Value::Var(*dest)), IRType::Array(Box::new(IRType::Int), Some(0)));
+    // Both sizes are known, so len folds to 0 for each
+    let Terminator::Return(Value::Var(sum)) = &entry.terminator else { panic!() };
+    assert!(entry.instructions.iter().any(|inst| matches!(
+        inst,
+        Instruction::BinaryOp { dest, op: BinOp::Add, left: Value::Int(0), right: Value::Int(0) } if dest == sum
+    )));
+}
+
+#[test]
+fn return_inside_loop_leaves_it() {
+    // A runtime bound keeps the loop from being unrolled
+    let module = lower(
+        "fn find(a: [int; 10], target: int, n: int) -> int {
+             for i in 0..n { if a[i] == target { return i; print(i); } }
+             return -1;
+         }",
+    );
+    let func = &module.functions[0];
+
+    let Instruction::Phi { dest: i, .. } = &block(func, "for_header_0").instructions[0] else {
+        panic!("expected the induction variable's phi");
+    };
+    let then_arm = block(func, "if_then_1");
+    assert!(then_arm.instructions.is_empty(), "{:?}", then_arm.instructions);
+    assert_eq!(then_arm.terminator, Terminator::Return(Value::Var(*i)));
+    assert_eq!(block(func, "for_exit_0").terminator, Terminator::Return(Value::Int(-1)));
+
+    // The print after the return is dead and was never lowered
+    let calls = func.blocks.iter().flat_map(|b| &b.instructions).filter(|inst| matches!(inst, Instruction::Call { .. }));
+    assert_eq!(calls.count(), 0);
+}
+
+#[test]
+fn len_of_a_sized_array_folds() {
+    let module = lower("fn main() -> int { return len([1, 2, 3]); } fn g(a: [int; 4]) -> int { return len(a); }");
+    for (func, n) in module.functions.iter().zip([3, 4]) {
+        assert_eq!(block(func, "entry").terminator, Terminator::Return(Value::Int(n)), "{}", func.name);
+        assert!(block(func, "entry").instructions.is_empty(), "{:?}", func.blocks);
+    }
+
+    // An unsized buffer's length is only known at run time
+    let module = lower("@gpu fn total(xs: [float]) -> int { return len(xs); }");
+    assert!(matches!(&block(&module.functions[0], "entry").instructions[..], [Instruction::Call { function, .. }] if function == "len"));
+}
+
+#[test]
+fn max_unroll_decides_which_loops_unroll() {
+    use quarkdsl::middle::{dump_ir, Options, DEFAULT_MAX_UNROLL};
+
+    let lowered = |source: &str, max_unroll: usize| {
+        let ast = quarkdsl::parse(source).unwrap();
+        dump_ir(&quarkdsl::lower(&ast, &Options { max_unroll, ..Options::default() }).unwrap())
+    };
+    let source = "fn main() -> int { let mut s = 0; for i in 0..10000 { s = s + i; } return s; }";
+    assert!(lowered(source, DEFAULT_MAX_UNROLL).contains("for_header_0:"));
+    assert!(!lowered(source, 20000).contains("for_header"));
+    assert!(lowered("fn main() -> int { let mut s = 0; for i in 0..2 { s = s + i; } return s; }", 0).contains("for_header_0:"));
+}
@@ -0,0 +1,239 @@
+// Optimizer passes, each checked on the instructions it should (and should
+// not) rewrite, running the default pipeline unless a test selects passes.
+
+use quarkdsl::compile_to_ir;
+use quarkdsl::middle::ir::*;
+
+fn optimized(source: &str) -> Module {
+    compile_to_ir(source, true, &Default::default()).unwrap()
+}
+
+fn instructions(func: &IRFunction) -> Vec<&Instruction> {
+    func.blocks.iter().flat_map(|b| &b.instructions).collect()
+}
+
+fn binary_ops(func: &IRFunction) -> Vec<(BinOp, Value, Value)> {
+    instructions(func)
+        .into_iter()
+        .filter_map(|inst| match inst {
+            Instruction::BinaryOp { op, left, right, .. } => Some((*op, left.clone(), right.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+const X: Value = Value::Var(SSAVar { id: 0 });
+
+#[test]
+fn multiplication_by_power_of_two_becomes_shift() {
+    let module = optimized(
+        "fn f(x: int) -> int { return x * 16; }
+         fn g(x: float) -> float { return x * 16.0; }",
+    );
+    assert_eq!(binary_ops(&module.functions[0]), vec![(BinOp::Shl, X, Value::Int(4))]);
+    // Floats are left alone
+    assert_eq!(binary_ops(&module.functions[1]), vec![(BinOp::Mul, X, Value::Float(16.0))]);
+}
+
+fn block_of(func: &IRFunction, pred: impl Fn(&Instruction) -> bool) -> &str {
+    &func.blocks.iter().find(|b| b.instructions.iter().any(&pred)).expect("no such instruction").label
+}
+
+#[test]
+fn loop_invariant_code_is_hoisted() {
+    let module = optimized(
+        "fn f(n: int, a: int, b: int) -> int {
+             let mut s = 0;
+             for i in 0..n { s = s + a * b + i * 3; }
+             return s;
+         }",
+    );
+    let func = &module.functions[0];
+
+    // a * b only depends on parameters: computed once, before the loop
+    let invariant = |inst: &Instruction| {
+        matches!(inst, Instruction::BinaryOp { op: BinOp::Mul, left: Value::Var(SSAVar { id: 1 }), .. })
+    };
+    assert_eq!(block_of(func, invariant), "entry");
+    // i * 3 changes every iteration and stays in the body
+    let variant = |inst: &Instruction| matches!(inst, Instruction::BinaryOp { right: Value::Int(3), .. });
+    assert_eq!(block_of(func, variant), "for_body_0");
+}
+
+fn block<'a>(func: &'a IRFunction, label: &str) -> &'a BasicBlock {
+    func.blocks.iter().find(|b| b.label == label).unwrap()
+}
+
+#[test]
+fn copies_stay_in_the_blocks_they_dominate() {
+    let module = optimized(
+        "fn f(c: bool, y: int) -> int {
+             let mut x = 0;
+             if c { x = 5; } else { x = y + 10; }
+             return x;
+         }",
+    );
+    let func = &module.functions[0];
+    quarkdsl::middle::verify(&module).unwrap();
+
+    // `x = 5` is on the then arm, which doesn't dominate the merge: the phi
+    // still reads the arm's variable rather than the propagated constant
+    let Instruction::Phi { incoming, .. } = &block(func, "if_merge_0").instructions[0] else {
+        panic!("expected a phi");
+    };
+    let (then_value, _) = incoming.iter().find(|(_, label)| label == "if_then_0").unwrap();
+    assert!(matches!(then_value, Value::Var(_)), "{:?}", incoming);
+
+    // ...and nothing in the sibling arm picks it up
+    let else_arm = &block(func, "if_else_0").instructions;
+    assert!(!format!("{:?}", else_arm).contains("Int(5)"), "{:?}", else_arm);
+}
+
+#[test]
+fn small_helper_is_inlined() {
+    let module = optimized(
+        "fn add(a: int, b: int) -> int { return a + b; }
+         fn main(x: int) -> int { return add(x, 2) * 3; }",
+    );
+    let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+
+    assert!(!instructions(main).iter().any(|inst| matches!(inst, Instruction::Call { .. })));
+    let ops = binary_ops(main);
+    assert_eq!(ops[0], (BinOp::Add, X, Value::Int(2)));
+    assert_eq!(ops[1].0, BinOp::Mul);
+    // The helper itself is kept for other callers
+    assert!(module.functions.iter().any(|f| f.name == "add"));
+}
+
+#[test]
+fn double_negation_collapses() {
+    let module = optimized(
+        "fn f(x: int) -> int { return -(-x); }
+         fn g(b: bool) -> bool { return !(!b); }",
+    );
+    for func in &module.functions {
+        assert!(instructions(func).is_empty(), "{:?}", func.blocks);
+        assert_eq!(func.blocks[0].terminator, Terminator::Return(X));
+    }
+}
+
+#[test]
+fn self_assignment_is_dropped() {
+    let mut module = quarkdsl::middle::parse_ir(
+        "fn f(x: int) -> int {\n  entry:\n    %1 = add %0 1\n    %1 = %1\n    return %1\n}\n",
+    )
+    .unwrap();
+    quarkdsl::optimize(&mut module, &Default::default()).unwrap();
+    let func = &module.functions[0];
+    assert_eq!(instructions(func).len(), 1, "{:?}", func.blocks);
+    assert!(matches!(instructions(func)[0], Instruction::BinaryOp { op: BinOp::Add, .. }));
+}
+
+#[test]
+fn constant_chain_folds_completely() {
+    // Each step needs the previous one folded and propagated first: more
+    // rounds than any fixed iteration count would give
+    let mut body = String::from("let a0 = 1; ");
+    for i in 1..=12 {
+        body.push_str(&format!("let a{} = a{} * 2 + 1; ", i, i - 1));
+    }
+    let module = optimized(&format!("fn f() -> int {{ {} return a12; }}", body));
+    let func = &module.functions[0];
+
+    assert!(instructions(func).is_empty(), "{:?}", func.blocks);
+    assert_eq!(func.blocks[0].terminator, Terminator::Return(Value::Int(8191)));
+}
+
+#[test]
+fn overwritten_store_is_removed() {
+    let module = optimized("fn f(i: int) -> int { let mut a = [0, 0]; a[0] = 1; a[0] = 2; a[i] = 3; a[i] = 4; return a[0]; }");
+    let stores: Vec<(Value, Value)> = instructions(&module.functions[0])
+        .into_iter()
+        .filter_map(|inst| match inst {
+            Instruction::Store { index, value, .. } => Some((index.clone(), value.clone())),
+            _ => None,
+        })
+        .collect();
+    // Constant indices: the first store is dead. Variable indices are kept
+    assert_eq!(stores, vec![(Value::Int(0), Value::Int(2)), (X, Value::Int(3)), (X, Value::Int(4))]);
+}
+
+#[test]
+fn condition_with_call_survives_empty_arms() {
+    let module = optimized(
+        "fn check(x: int) -> bool { print_int(x); return x > 0; }
+         fn f(x: int) -> int { if check(x) { } else { } return x; }",
+    );
+    let f = module.functions.iter().find(|f| f.name == "f").unwrap();
+    // The call to check(), or its print once inlined, is still there
+    let calls = instructions(f)
+        .into_iter()
+        .filter(|inst| matches!(inst, Instruction::Call { function, .. } if function == "print_int" || function == "check"))
+        .count();
+    assert_eq!(calls, 1, "{:?}", f.blocks);
+}
+
+#[test]
+fn constant_branch_becomes_a_jump() {
+    let module = optimized("fn main() -> int { if 1 == 1 { return 5; } return 7; }");
+    let func = &module.functions[0];
+
+    // Only the taken arm is left, and nothing branches
+    assert_eq!(func.blocks.len(), 1, "{:?}", func.blocks);
+    assert_eq!(func.blocks[0].terminator, Terminator::Return(Value::Int(5)));
+
+    // A condition only known at run time keeps both arms
+    let module = optimized("fn main(x: int) -> int { if x == 1 { return 5; } return 7; }");
+    let func = &module.functions[0];
+    assert!(matches!(func.blocks[0].terminator, Terminator::Branch { .. }), "{:?}", func.blocks);
+    assert!(func.blocks.iter().any(|b| b.terminator == Terminator::Return(Value::Int(7))));
+}
+
+#[test]
+fn folded_control_flow_merges_into_one_block() {
+    let source = "fn f(x: int) -> int { let mut y = x; if true { y = x * 3; } else { y = 0; } return y + 1; }";
+    assert!(compile_to_ir(source, false, &Default::default()).unwrap().functions[0].blocks.len() > 1);
+
+    let module = optimized(source);
+    let func = &module.functions[0];
+    assert_eq!(func.blocks.len(), 1, "{:?}", func.blocks);
+    // The taken arm's multiply feeds the add directly, with no phi left
+    let ops = binary_ops(func);
+    assert_eq!(ops[0], (BinOp::Mul, X, Value::Int(3)));
+    assert_eq!(ops[1].0, BinOp::Add);
+    assert!(!instructions(func).iter().any(|inst| matches!(inst, Instruction::Phi { .. })));
+}
+
+#[test]
+fn overflowing_arithmetic_is_not_folded() {
+    let module = optimized("fn main() -> int { let a = 9223372036854775807; let b = a + 1; let c = a - 1; return b + c; }");
+    let ops = binary_ops(&module.functions[0]);
+
+    // a + 1 would overflow and stays an add; a - 1 folds
+    assert_eq!(ops[0], (BinOp::Add, Value::Int(i64::MAX), Value::Int(1)));
+    assert_eq!(ops[1].0, BinOp::Add);
+    assert_eq!(ops[1].2, Value::Int(i64::MAX - 1));
+}
+
+#[test]
+fn passes_run_only_the_selected_ones() {
+    use quarkdsl::middle::Options;
+
+    let source = "fn main() -> int { let unused = 7; let x = 2 + 3; return x; }";
+    let optimized = |passes: &[&str]| {
+        let options = Options { passes: passes.iter().map(|name| name.to_string()).collect(), ..Options::default() };
+        let mut ir = compile_to_ir(source, false, &options).unwrap();
+        quarkdsl::optimize(&mut ir, &options).map(|()| ir.functions.remove(0).blocks.remove(0).instructions)
+    };
+
+    let instructions = optimized(&["constant-folding"]).unwrap();
+    // Folded, but not removed as dead code
+    assert!(instructions.contains(&Instruction::Assign { dest: SSAVar { id: 1 }, value: Value::Int(5) }), "{:?}", instructions);
+    assert!(instructions.contains(&Instruction::Assign { dest: SSAVar { id: 0 }, value: Value::Int(7) }), "{:?}", instructions);
+
+    // An empty selection is the whole pipeline
+    assert!(optimized(&[]).unwrap().is_empty());
+
+    let error = optimized(&["gvn"]).unwrap_err().to_string();
+    assert!(error.starts_with("Unknown pass 'gvn' (expected one of: inline, copy-propagation,"), "{}", error);
+}
@@ -0,0 +1,274 @@
+// Python orchestrator: the generated functions, entry point and
+// cross-domain glue.
+
+use quarkdsl::{compile_to_string, Target};
+
+fn orchestrator(source: &str) -> String {
+    compile_to_string(source, Target::Orchestrator, false).unwrap()
+}
+
+// The generated Python for one function, from its `def` to the next one
+fn python_function<'a>(code: &'a str, name: &str) -> &'a str {
+    let start = code.find(&format!("def {}(", name)).unwrap_or_else(|| panic!("no def {} in\n{}", name, code));
+    let end = code[start + 1..].find("\ndef ").map_or(code.len(), |i| start + 1 + i);
+    &code[start..end]
+}
+
+#[test]
+fn main_guard_calls_main() {
+    let code = orchestrator("fn main() -> int { return 3; }");
+    assert!(code.ends_with("if __name__ == \"__main__\":\n    print(main())\n"), "{}", code);
+
+    let code = orchestrator("fn main() -> void { print_int(1); }");
+    assert!(code.trim_end().ends_with("if __name__ == \"__main__\":\n    main()"), "{}", code);
+
+    // A main with parameters gets a commented stub instead of a call
+    let code = orchestrator("fn main(x: int) -> int { return x; }");
+    assert!(code.contains("    # print(main(x))\n    pass\n"), "{}", code);
+}
+
+#[test]
+fn array_returns() {
+    let code = orchestrator(
+        "fn make() -> [int; 3] { return [1, 2, 3]; }
+         fn main() -> int { let a = make(); print_array(a); return a[1]; }",
+    );
+    assert!(python_function(&code, "make").ends_with("    return [1, 2, 3]\n"), "{}", code);
+    assert!(python_function(&code, "main").contains(" = make()\n"), "{}", code);
+
+    let error = compile_to_string("fn make() -> [int; 3] { return [1, 2]; }", Target::Orchestrator, true)
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("Return type mismatch: expected [int; 3], got [int; 2]"));
+}
+
+#[test]
+fn amplitude_encoding_initializes_the_statevector() {
+    let source = "@quantum fn prep(data: [float; 4]) -> int { let q = qalloc(2); amplitude_encode(data); return measure(q[0]); }";
+
+    let ir = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+    let conversion = ir.functions[0].blocks[0].instructions.iter().find_map(|inst| match inst {
+        quarkdsl::middle::ir::Instruction::DomainConversion { encoding, .. } => Some(encoding.clone()),
+        _ => None,
+    });
+    assert_eq!(conversion, Some(quarkdsl::middle::ir::ConversionEncoding::AmplitudeEncoding));
+
+    let code = orchestrator(source);
+    assert!(code.contains("def encode_amplitude("), "{}", code);
+    assert!(
+        python_function(&code, "prep").contains("    circuit.initialize(encode_amplitude(data, 2 ** len(qr)), qr)\n"),
+        "{}",
+        code
+    );
+}
+
+#[test]
+fn measurement_extract_conversion_uses_its_encoding() {
+    let code = orchestrator(
+        "fn double(x: int) -> int { return x * 2; }
+         @quantum fn q() -> int { let q = qalloc(1); h(q[0]); let m = measure(q[0]); let d = double(m); return d; }",
+    );
+    let body = python_function(&code, "q");
+
+    // The measured bit crosses into the classical call through extract_measurement
+    let bit = body.lines().find(|l| l.ends_with("= (result >> 0) & 1")).expect(body);
+    let bit = bit.trim().split(" = ").next().unwrap();
+    assert!(body.contains(&format!(" = extract_measurement({})\n", bit)), "{}", body);
+    assert!(!body.contains("encode_angle("), "{}", body);
+}
+
+#[test]
+fn source_line_comments() {
+    let source = "fn main() -> int {\n    let a = [1, 2];\n    print_int(a[0]);\n    return 0;\n}\n";
+    let code = orchestrator(source);
+    assert!(python_function(&code, "main").contains("    # line 3\n    print(v0[0])\n"), "{}", code);
+
+    let shader = compile_to_string(source, Target::Wgsl, false).unwrap();
+    assert!(shader.contains("  // line 3\n"), "{}", shader);
+}
+
+#[test]
+fn doc_comment_becomes_docstring() {
+    let source = "/// Doubles its input.\n/// Second line.\nfn twice(x: int) -> int { return x * 2; }\n";
+    let code = orchestrator(source);
+    assert!(
+        python_function(&code, "twice").starts_with("def twice(x):\n    \"\"\"\n    Doubles its input.\n    Second line.\n    \"\"\"\n"),
+        "{}",
+        code
+    );
+    assert!(!python_function(&code, "twice").contains("Domain: Classical"), "{}", code);
+
+    let shader = compile_to_string(source, Target::Wgsl, false).unwrap();
+    assert!(shader.contains("// Doubles its input.\n// Second line.\nfn twice(x: i32) -> i32 {"), "{}", shader);
+}
+
+#[test]
+fn array_binding_copies() {
+    let code = orchestrator("fn main() -> int { let a = [1, 2, 3]; let mut b = a; b[0] = 9; return a[0] + b[0]; }");
+    let body = python_function(&code, "main");
+    assert!(body.contains("    v1 = copy.deepcopy(v0)\n    # line 1\n    v1[0] = 9\n"), "{}", body);
+    assert!(code.contains("\nimport copy\n"), "{}", code);
+
+    // Nothing writes through either name: no copy needed
+    let code = orchestrator("fn main() -> int { let a = [1, 2, 3]; let b = a; return a[0] + b[1]; }");
+    assert!(!code.contains("copy.deepcopy"), "{}", code);
+}
+
+#[test]
+fn floats_keep_their_precision() {
+    let source = "fn main() -> float { let a = 1.0; let b = 0.1; let c = 1e-7; print_float(a); print_float(b); print_float(c); return 3.0; }";
+    let code = orchestrator(source);
+    for line in ["    print(1.0)\n", "    print(0.1)\n", "    print(1e-7)\n", "    return 3.0\n"] {
+        assert!(code.contains(line), "{}", code);
+    }
+
+    let shader = compile_to_string(source, Target::Wgsl, false).unwrap();
+    for line in [" = 1.0;\n", " = 0.1;\n", " = 1e-7;\n", "return 3.0;\n"] {
+        assert!(shader.contains(line), "{}", shader);
+    }
+}
+
+#[test]
+fn repeated_rows_do_not_alias() {
+    let code = orchestrator(
+        "fn main() -> int { let row = [0, 0]; let mut grid = [row; 2]; grid[0][1] = 5; print_array(grid); return grid[1][1]; }",
+    );
+    let body = python_function(&code, "main");
+    assert!(body.contains(" = [copy.deepcopy(v0), copy.deepcopy(v0)]\n"), "{}", body);
+
+    // Rows written out as literals are separate lists already
+    let code = orchestrator("fn main() -> int { let mut grid = [[0, 0]; 2]; grid[0][1] = 5; return grid[1][1]; }");
+    assert!(python_function(&code, "main").contains(" = [[0, 0], [0, 0]]\n"), "{}", code);
+}
+
+#[test]
+fn assert_names_its_line() {
+    let code = orchestrator("fn main(x: int) -> int {\n    assert(x > 2);\n    return x;\n}\n");
+    assert!(python_function(&code, "main").contains("    assert (x > 2), \"assertion failed on line 2\"\n"), "{}", code);
+}
+
+#[test]
+fn print_bool_and_print_fmt() {
+    let code = orchestrator("fn main(x: int) -> int { print_bool(true); print_fmt(\"x={} {y}\", x); return 0; }");
+    let body = python_function(&code, "main");
+    assert!(body.contains("    print(True)\n"), "{}", body);
+    // Each {} takes the next value; literal braces are doubled
+    assert!(body.contains("    print(f\"x={x} {{y}}\")\n"), "{}", body);
+}
+
+#[test]
+fn reductions_map_to_numpy() {
+    use quarkdsl::frontend::ast::Type;
+
+    let source = "fn main() -> float { let n = sum([1, 2, 3]); let p = product([1.0, 2.0]); let d = dot([1.0, 2.0], [3.0, 4.0]); return p + d; }";
+    let types = quarkdsl::frontend::infer_types(&quarkdsl::parse(source).unwrap()).unwrap();
+    assert_eq!(types[0].lets[0], ("n".to_string(), Type::Int));
+
+    let code = orchestrator(source);
+    let body = python_function(&code, "main");
+    for call in ["np.sum([1, 2, 3])", "np.prod([1.0, 2.0])", "np.dot([1.0, 2.0], [3.0, 4.0])"] {
+        assert!(body.contains(call), "{}", body);
+    }
+}
+
+#[test]
+fn debug_print_escapes_inlined_arguments() {
+    let code = orchestrator(
+        "fn f(xs: [int; 2]) -> int { return xs[0]; }
+         fn main() -> int { let a = 1; return f([a + 1, 2]); }",
+    );
+    // Each call prints itself and its result; inlined arguments are literal
+    // text of the f-string
+    let body = python_function(&code, "main");
+    assert!(body.contains("    v2 = f([(1 + 1), 2])\n    if DEBUG_MODE:\n        print(f\"  f([(1 + 1), 2]) = {v2}\")\n"), "{}", body);
+}
+
+#[test]
+fn float_remainder_truncates_on_every_target() {
+    // np.fmod here, `%` in WGSL, and Rust's `%` when -O folds it
+    let source = "fn main() -> float { let a = 3.5; let r = a % 2.0; return r; }";
+    let code = orchestrator(source);
+    assert!(python_function(&code, "main").contains("np.fmod(3.5, 2.0)"), "{}", code);
+    let wgsl = compile_to_string(source, Target::Wgsl, false).unwrap();
+    assert!(wgsl.contains("v0 % 2.0"), "{}", wgsl);
+
+    let folded = quarkdsl::compile_to_ir(source, true, &Default::default()).unwrap();
+    let returned = &folded.functions[0].blocks[0].terminator;
+    assert_eq!(returned, &quarkdsl::middle::ir::Terminator::Return(quarkdsl::middle::ir::Value::Float(1.5)));
+}
+
+#[test]
+fn phis_become_assignments_in_each_arm() {
+    let code = orchestrator("fn f(c: bool) -> int { let mut y = 0; if c { y = 1; } else { y = 2; } return y; }");
+    let body = python_function(&code, "f");
+    assert!(body.contains("    if c:\n        v4 = 1\n    else:\n        v4 = 2\n    return v4\n"), "{}", body);
+    assert!(!code.contains("goto"), "{}", code);
+}
+
+#[test]
+fn nested_loops_break_and_continue() {
+    // Each `continue` first assigns the latch's phis, each `break` the exit's;
+    // run as Python this returns 70
+    let code = orchestrator(
+        "fn main() -> int {
+             let mut total = 0;
+             for i in 0..10 {
+                 if i == 3 { continue; }
+                 for j in 0..10 {
+                     if j == 2 { continue; }
+                     if j > i { break; }
+                     total = total + i + j;
+                 }
+                 if i == 5 { break; }
+             }
+             return total;
+         }",
+    );
+    let expected = "    v1 = 0
+    v2 = 0
+    while True:
+        v3 = v1 < 10
+        v18 = v2
+        if not v3:
+            break
+        if (v1 == 3):
+            v16 = v2
+            v1 = (v1 + 1)
+            v2 = v16
+            continue
+        v5 = 0
+        v6 = v2
+        while True:
+            if not (v5 < 10):
+                break
+            if (v5 == 2):
+                v13 = v6
+                v5 = (v5 + 1)
+                v6 = v13
+                continue
+            if (v5 > v1):
+                break
+            v13 = ((v6 + v1) + v5)
+            v5 = (v5 + 1)
+            v6 = v13
+        if (v1 == 5):
+            v18 = v6
+            break
+        v16 = v6
+        v1 = (v1 + 1)
+        v2 = v16
+    return v18
+";
+    assert!(python_function(&code, "main").contains(expected), "{}", code);
+}
+
+#[test]
+fn quantum_backend_picks_the_device() {
+    let code = orchestrator(
+        "@quantum(backend=\"ibm_brisbane\") fn coin() -> int { let q = qalloc(1); h(q[0]); return measure(q[0]); }",
+    );
+    assert!(code.contains("counts = run_quantum_circuit(circuit, device=\"ibm_brisbane\")"), "{}", code);
+    assert!(code.contains("backend = service.backend(device)"), "{}", code);
+
+    let error = quarkdsl::parse("@quantum(backend=\"\") fn coin() -> int { return 0; }").unwrap_err();
+    assert!(error.to_string().contains("'backend' must name a device"), "{}", error);
+}
@@ -0,0 +1,153 @@
+// Parser: annotations, top-level items and the errors for malformed input.
+
+use quarkdsl::frontend::ast::Domain;
+use quarkdsl::parse;
+
+fn parse_error(source: &str) -> String {
+    format!("{:#}", parse(source).unwrap_err())
+}
+
+#[test]
+fn explicit_classical_annotation() {
+    let program = parse("@classical fn f() -> int { return 1; } fn g() -> int { return 2; }").unwrap();
+    assert_eq!(program.functions[0].domain, Domain::Classical);
+    assert_eq!(program.functions[1].domain, Domain::Classical);
+
+    // Typed dumps spell the default domain out
+    let ir = quarkdsl::compile_to_ir("@classical fn f() -> int { return 1; }", false, &Default::default()).unwrap();
+    assert!(quarkdsl::middle::dump_ir_typed(&ir).starts_with("@classical\nfn f() -> int {"));
+}
+
+#[test]
+fn annotation_arguments() {
+    let program = parse("@quantum(shots=2048, seed=7) fn q() -> int { return measure(0); }").unwrap();
+    let expected = [("shots".to_string(), 2048), ("seed".to_string(), 7)].into_iter().collect();
+    assert_eq!(program.functions[0].annotations, expected);
+
+    let program = parse("@gpu(workgroup=64) fn k(x: [float; 4]) -> float { return x[0]; }").unwrap();
+    assert_eq!(program.functions[0].annotations["workgroup"], 64);
+}
+
+#[test]
+fn unknown_annotation_arguments() {
+    let error = parse_error("@quantum(workgroup=64) fn q() -> int { return 0; }");
+    assert!(error.contains("Unknown argument 'workgroup' for @quantum; allowed: shots, seed, backend"), "{}", error);
+
+    let error = parse_error("@classical(shots=1) fn f() -> int { return 0; }");
+    assert!(error.contains("Unknown argument 'shots' for @classical; it takes no arguments"), "{}", error);
+}
+
+#[test]
+fn call_on_non_identifier_is_rejected() {
+    let error = parse_error("fn f(a: int, b: int) -> int { return (a + b)(); }");
+    assert!(error.contains("Callee is not a function name: only `name(args)` calls are supported"), "{}", error);
+    // The caret points at the call's opening parenthesis
+    assert!(error.contains("--> line 1, column 45"), "{}", error);
+}
+
+#[test]
+fn enum_auto_increment_overflow() {
+    let program = parse("enum Qubit { Ancilla = 0, Data } fn f() -> int { return Qubit::Data; }").unwrap();
+    assert_eq!(program.enums[0].members.len(), 2);
+
+    // The last value is fine; counting past it is not
+    parse("enum Big { Last = 9223372036854775807 }").unwrap();
+    let error = parse_error("enum Big { Last = 9223372036854775807, Next }");
+    assert!(
+        error.contains("Enum member Big::Next overflows: the previous member is 9223372036854775807"),
+        "{}",
+        error
+    );
+    // The caret points at the member that has no value
+    assert!(error.contains("--> line 1, column 40"), "{}", error);
+}
+
+#[test]
+fn unexpected_token_is_named() {
+    let error = parse_error("fn f() -> int {\n    let x = 1;\n    return x +\n}\n");
+    assert!(error.contains("Unexpected token '}' in expression"), "{}", error);
+    assert!(error.contains("--> line 4, column 1"), "{}", error);
+}
+
+#[test]
+fn array_repeat_count_bounds() {
+    let error = parse_error("fn f() -> int { let a = [0; 100000000]; return a[0]; }");
+    assert!(
+        error.contains("Array repeat count 100000000 is too large (at most 65536); use zeros or ones for large arrays"),
+        "{}",
+        error
+    );
+    let error = parse_error("fn f() -> int { let a = [0; -1]; return 0; }");
+    assert!(error.contains("Array repeat count must be a non-negative integer constant"), "{}", error);
+
+    parse("fn f() -> int { let a = [0; 65536]; return a[0]; }").unwrap();
+}
+
+#[test]
+fn constant_overflow_is_an_error() {
+    let error = parse_error("const A: int = -(-9223372036854775807 - 1);\nfn main() -> int { return A; }");
+    assert!(error.contains("constant overflows int"), "{}", error);
+    assert!(error.contains("--> line 1, column 16"), "{}", error);
+
+    let error = parse_error("const A: int = 7 / (3 - 3); fn main() -> int { return A; }");
+    assert!(error.contains("division by zero in constant expression"), "{}", error);
+
+    // The same rules apply to float constants, folded during lowering
+    let error = quarkdsl::compile_to_ir("const F: float = 1.0 / 0.0; fn main() -> float { return F; }", false, &Default::default()).unwrap_err();
+    assert!(format!("{:#}", error).contains("division by zero in constant expression"), "{:#}", error);
+
+    let program = parse("const A: int = -(-9223372036854775807); fn main() -> int { return A; }").unwrap();
+    assert_eq!(program.constants.len(), 1);
+}
+
+#[test]
+fn statements_carry_their_span() {
+    use quarkdsl::frontend::ast::StatementKind;
+
+    let source = "fn f(n: int) -> int {\n    let mut s = 0;\n    for i in 0..n { s = s + i; }\n    return s;\n}\n";
+    let program = parse(source).unwrap();
+    let body = &program.functions[0].body;
+    let text: Vec<&str> = body.iter().map(|stmt| &source[stmt.span.clone()]).collect();
+    assert_eq!(text, ["let mut s = 0;", "for i in 0..n { s = s + i; }", "return s;"]);
+
+    let StatementKind::For { body: inner, .. } = &body[1].kind else { panic!("expected a for loop") };
+    assert_eq!(&source[inner[0].span.clone()], "s = s + i;");
+}
+
+#[test]
+fn word_operators_match_the_symbols() {
+    let body = |source: &str| parse(source).unwrap().functions.remove(0).body;
+    assert_eq!(
+        body("fn f(a: bool, b: bool, c: bool) -> bool { let r = a and not b or c; return not (r and a); }"),
+        body("fn f(a: bool, b: bool, c: bool) -> bool { let r = a && !b || c; return !(r && a); }"),
+    );
+
+    // They are keywords, never identifiers
+    let error = parse_error("fn f() -> int { let and = 1; return and; }");
+    assert!(error.contains("Expected identifier"), "{}", error);
+}
+
+#[test]
+fn string_literal_escapes() {
+    use quarkdsl::frontend::ast::{Expression, StatementKind};
+
+    let program = parse(r#"fn f() -> void { print_fmt("\x41={}\u{e9}", 1); print_fmt(r"C:\new {}", 2); }"#).unwrap();
+    let formats: Vec<&Expression> = program.functions[0]
+        .body
+        .iter()
+        .map(|stmt| match &stmt.kind {
+            StatementKind::Expression(Expression::Call { args, .. }) => &args[0],
+            other => panic!("expected a call, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(formats[0], &Expression::StringLiteral("A={}\u{e9}".to_string()));
+    assert_eq!(formats[1], &Expression::StringLiteral(r"C:\new {}".to_string()));
+
+    let error = parse_error(r#"fn f() -> void { print_fmt("\q{}", 1); }"#);
+    assert!(error.contains(r"Invalid escape sequence '\q'"), "{}", error);
+
+    // A missing closing quote is a lexer error, not a confusing parse error
+    let error = parse_error("fn f() -> void {\n    print_fmt(\"abc, 1);\n}\n");
+    assert!(error.contains("unterminated string literal"), "{}", error);
+    assert!(error.contains("--> line 2, column 15"), "{}", error);
+}
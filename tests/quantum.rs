@@ -0,0 +1,250 @@
+// Qiskit backend: gates, measurement and circuit configuration, checked in
+// the standalone circuit file and (where it differs) the orchestrator.
+
+use quarkdsl::{compile_to_string, Target};
+
+fn qiskit(source: &str) -> String {
+    compile_to_string(source, Target::Quantum, true).unwrap()
+}
+
+fn orchestrator(source: &str) -> String {
+    compile_to_string(source, Target::Orchestrator, true).unwrap()
+}
+
+#[test]
+fn u_and_p_gates() {
+    let source = "@quantum fn q() -> int { let q = qalloc(2); u(0.1, 0.2, 0.3, q[0]); p(0.5, q[1]); return measure(q[0]); }";
+    for code in [qiskit(source), orchestrator(source)] {
+        assert!(code.contains("circuit.u(0.1, 0.2, 0.3, qr[0])"), "{}", code);
+        assert!(code.contains("circuit.p(0.5, qr[1])"), "{}", code);
+    }
+
+    let error = |source| format!("{:#}", compile_to_string(source, Target::Quantum, true).unwrap_err());
+    assert!(error("@quantum fn q() -> int { u(0.1, 0.2, 0); return 0; }")
+        .contains("Function u expects 4 arguments, got 3"));
+    assert!(error("@quantum fn q() -> int { let q = qalloc(1); u(0, 0.1, 0.2, q[0]); return 0; }")
+        .contains("call to u: argument 1 expected float, got int"));
+}
+
+#[test]
+fn reset_and_barrier() {
+    // Both survive -O: they have no result but change the circuit
+    let source = "@quantum fn q() -> int { let q = qalloc(1); h(q[0]); reset(q[0]); barrier(); return measure(q[0]); }";
+    for code in [qiskit(source), orchestrator(source)] {
+        assert!(code.contains("circuit.reset(qr[0])"), "{}", code);
+        assert!(code.contains("circuit.barrier()"), "{}", code);
+    }
+}
+
+#[test]
+fn classical_register_sized_to_measured_qubits() {
+    let source = "@quantum fn q() -> int { let q = qalloc(3); h(q[0]); cx(q[0], q[1]); h(q[2]); return measure(q[0]); }";
+    for code in [qiskit(source), orchestrator(source)] {
+        assert!(code.contains("qr = QuantumRegister(3, 'q')"), "{}", code);
+        assert!(code.contains("cr = ClassicalRegister(1, 'c')"), "{}", code);
+        assert!(code.contains("circuit.measure(qr[0], cr[0])"), "{}", code);
+        assert!(!code.contains("circuit.measure(qr, cr)"), "{}", code);
+    }
+
+    // Without an explicit measurement every qubit is measured
+    let code = qiskit("@quantum fn q() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return 0; }");
+    assert!(code.contains("cr = ClassicalRegister(2, 'c')"), "{}", code);
+    assert!(code.contains("circuit.measure(qr, cr)"), "{}", code);
+}
+
+#[test]
+fn seed_annotation_reaches_simulator() {
+    let source = "@quantum(seed=42) fn q() -> int { let q = qalloc(1); h(q[0]); return measure(q[0]); }";
+
+    let code = orchestrator(source);
+    assert!(code.contains("counts = run_quantum_circuit(circuit, seed=42)"), "{}", code);
+    assert!(code.contains("AerSimulator(seed_simulator=seed)"), "{}", code);
+
+    let code = qiskit(source);
+    assert!(code.contains("\"q\": (build_q, SHOTS, 42, None),"), "{}", code);
+
+    // No seed by default
+    let code = orchestrator("@quantum fn q() -> int { let q = qalloc(1); h(q[0]); return measure(q[0]); }");
+    assert!(code.contains("counts = run_quantum_circuit(circuit)\n"), "{}", code);
+}
+
+#[test]
+fn measured_bit_flows_into_classical_code() {
+    let code = orchestrator(
+        "@quantum fn q() -> int { let q = qalloc(2); h(q[1]); let m = measure(q[1]); return m * 3 + 1; }",
+    );
+    let body = &code[code.find("def q():").unwrap()..];
+
+    assert!(!body.contains("measure placeholder"), "{}", body);
+    // Qubit 1 is the only one measured, into classical bit 0
+    let extract = body.find("= (result >> 0) & 1\n").expect(body);
+    let line_start = body[..extract].rfind('\n').unwrap() + 1;
+    let var = body[line_start..extract].trim();
+    assert!(body.contains(&format!("return (({} * 3) + 1)", var)), "{}", body);
+}
+
+#[test]
+fn mid_circuit_measurement_feeds_forward() {
+    let source = "@quantum fn q() -> int { let q = qalloc(2); h(q[0]); let b = measure(q[0]); if b == 1 { x(q[1]); } return measure(q[1]); }";
+    for code in [qiskit(source), orchestrator(source)] {
+        // One classical bit per measured qubit, measured where the call is
+        assert!(code.contains("cr = ClassicalRegister(2, 'c')"), "{}", code);
+        let first = code.find("circuit.measure(qr[0], cr[0])").expect(&code);
+        let condition = code.find("with circuit.if_test((cr[0], 1))").expect(&code);
+        let second = code.find("circuit.measure(qr[1], cr[1])").expect(&code);
+        assert!(first < condition && condition < second, "{}", code);
+    }
+}
+
+#[test]
+fn teleportation_corrections_are_classically_conditioned() {
+    let source = "@quantum fn teleport() -> int {
+                      let q = qalloc(3);
+                      ry(q[0], 0.7);
+                      h(q[1]); cx(q[1], q[2]);
+                      cx(q[0], q[1]); h(q[0]);
+                      let m0 = measure(q[0]);
+                      let m1 = measure(q[1]);
+                      if m1 == 1 { x(q[2]); }
+                      if m0 == 1 { z(q[2]); }
+                      return measure(q[2]);
+                  }";
+    let circuit = qiskit(source);
+    assert!(circuit.contains("with circuit.if_test((cr[1], 1)) as else_:\n        # line 8\n        circuit.x(2)\n"), "{}", circuit);
+    assert!(circuit.contains("with circuit.if_test((cr[0], 1)) as else_:\n        # line 9\n        circuit.z(2)\n"), "{}", circuit);
+
+    let python = orchestrator(source);
+    assert!(python.contains("with circuit.if_test((cr[1], 1)) as else_:\n        # line 8\n        circuit.x(qr[2])\n"), "{}", python);
+    assert!(python.contains("with circuit.if_test((cr[0], 1)) as else_:\n        # line 9\n        circuit.z(qr[2])\n"), "{}", python);
+
+    // An arm without gates gets a `pass` in both backends
+    let source = "@quantum fn q() -> int {
+                      let q = qalloc(2);
+                      h(q[0]);
+                      let b = measure(q[0]);
+                      let mut t = 0;
+                      if b == 1 { t = 2; } else { x(q[1]); }
+                      return measure(q[1]);
+                  }";
+    for code in [qiskit(source), orchestrator(source)] {
+        let arm = &code[code.find("with circuit.if_test((cr[0], 1)) as else_:\n").expect(&code)..];
+        let arm = &arm[..arm.find("    with else_:").unwrap()];
+        assert!(arm.ends_with("        pass\n"), "{}", code);
+    }
+}
+
+#[test]
+fn each_circuit_has_its_own_builder() {
+    let code = qiskit(
+        "@quantum fn bell() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[0]); }
+         @quantum fn flip() -> int { let q = qalloc(1); x(q[0]); return measure(q[0]); }",
+    );
+    assert_eq!(code.matches("def build_bell():\n").count(), 1, "{}", code);
+    assert_eq!(code.matches("def build_flip():\n").count(), 1, "{}", code);
+    assert!(code.contains("    \"bell\": (build_bell, SHOTS, SEED, None),\n"), "{}", code);
+    assert!(code.contains("    \"flip\": (build_flip, SHOTS, SEED, None),\n"), "{}", code);
+
+    // No module-level circuit: each builder makes and returns its own
+    assert!(!code.lines().any(|line| line.starts_with("circuit = ")), "{}", code);
+    let flip = &code[code.find("def build_flip():").unwrap()..];
+    let flip = &flip[..flip.find("    return circuit\n").unwrap()];
+    assert!(flip.contains("    circuit = QuantumCircuit(qr, cr)\n"), "{}", flip);
+    assert!(!flip.contains("cx("), "{}", flip);
+}
+
+#[test]
+fn negative_angles_reach_the_circuit() {
+    let source = "@quantum fn q() -> int { let q = qalloc(2); rz(q[0], -3.14); ry(q[1], -1.57); return measure(q[0]); }";
+    for optimize in [false, true] {
+        let code = compile_to_string(source, Target::Orchestrator, optimize).unwrap();
+        assert!(code.contains("circuit.rz(-3.14, qr[0])\n"), "{}", code);
+        assert!(code.contains("circuit.ry(-1.57, qr[1])\n"), "{}", code);
+    }
+    assert!(qiskit(source).contains("circuit.rz(-3.14, 0)\n"), "{}", qiskit(source));
+}
+
+#[test]
+fn two_measurements_return_a_tuple() {
+    let code = compile_to_string(
+        "@quantum fn two() -> (int, int) { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); let a = measure(q[0]); let b = measure(q[1]); return (a, b); }",
+        Target::Orchestrator,
+        false,
+    )
+    .unwrap();
+    assert!(code.contains("    circuit.measure(qr[0], cr[0])\n"), "{}", code);
+    assert!(code.contains("    circuit.measure(qr[1], cr[1])\n"), "{}", code);
+
+    // Each bit is extracted from the shared result and both are returned
+    let bits: Vec<&str> = ["= (result >> 0) & 1", "= (result >> 1) & 1"]
+        .iter()
+        .map(|extract| code.lines().find(|l| l.ends_with(extract)).expect(&code).trim().split(" = ").next().unwrap())
+        .collect();
+    assert!(code.contains(&format!("    return ({}, {})\n", bits[0], bits[1])), "{}", code);
+}
+
+#[test]
+fn helper_gates_follow_qubit_arguments() {
+    let source = "@quantum fn entangle(a: qubit, b: qubit) -> void { h(a); cx(a, b); }
+                  @quantum fn main() -> int { let q = qalloc(4); entangle(q[0], q[1]); entangle(q[2], q[3]); return measure(q[3]); }";
+    let code = orchestrator(source);
+    let main = &code[code.find("def main(").unwrap()..];
+    let gates: Vec<&str> = main.lines().map(str::trim).filter(|l| l.starts_with("circuit.h(") || l.starts_with("circuit.cx(")).collect();
+    assert_eq!(gates, ["circuit.h(qr[0])", "circuit.cx(qr[0], qr[1])", "circuit.h(qr[2])", "circuit.cx(qr[2], qr[3])"]);
+
+    let code = qiskit(source);
+    assert!(code.contains("    circuit.h(2)\n") && code.contains("    circuit.cx(2, 3)\n"), "{}", code);
+}
+
+#[test]
+fn debug_mode_draws_the_circuit() {
+    let code = orchestrator("@quantum fn bell() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[1]); }");
+    let run = code.find("    counts = run_quantum_circuit(circuit").expect(&code);
+    let draw = code.find("    if DEBUG_MODE:\n        print(circuit.draw())\n").expect(&code);
+    // Drawn after the measurements, before the circuit runs
+    assert!(code.find("circuit.measure(qr[1], cr[0])").unwrap() < draw && draw < run, "{}", code);
+}
+
+#[test]
+fn qubit_indices_from_constants_resolve() {
+    let source = "const Q: int = 2;
+                  @quantum fn f() -> int { let r = qalloc(4); let q = 1; h(r[q]); x(r[Q]); h(r[q + 2]); return measure(r[q]); }";
+    for optimize in [false, true] {
+        let code = compile_to_string(source, Target::Orchestrator, optimize).unwrap();
+        for gate in ["circuit.h(qr[1])\n", "circuit.x(qr[2])\n", "circuit.h(qr[3])\n", "circuit.measure(qr[1], cr[0])\n"] {
+            assert!(code.contains(gate), "{}", code);
+        }
+    }
+}
+
+#[test]
+fn measure_all_returns_every_bit() {
+    let code = orchestrator("@quantum fn all() -> [int] { let q = qalloc(3); h(q[0]); cx(q[0], q[1]); x(q[2]); return measure_all(); }");
+    assert!(code.contains("def extract_bits(counts):\n"), "{}", code);
+
+    let body = &code[code.find("def all(").unwrap()..];
+    assert!(body.contains("    circuit.measure(qr, cr)\n"), "{}", body);
+    let bits = body.lines().find(|l| l.ends_with(" = extract_bits(counts)")).expect(body);
+    let bits = bits.trim().split(" = ").next().unwrap();
+    assert!(body.contains(&format!("    return {}\n", bits)), "{}", body);
+}
+
+#[test]
+fn max_qubits_bounds_each_circuit() {
+    use quarkdsl::backend::quantum::check_max_qubits;
+
+    let source = "@quantum fn five() -> int { let q = qalloc(5); h(q[0]); cx(q[0], q[4]); return measure(q[4]); }";
+    let ir = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+    let error = check_max_qubits(&ir, 4).unwrap_err();
+    assert_eq!(error.to_string(), "@quantum function 'five' needs 5 qubits, but --max-qubits is 4");
+    check_max_qubits(&ir, 8).unwrap();
+}
+
+#[test]
+fn rotation_angle_parameters_are_bound_per_run() {
+    let code = qiskit("@quantum fn ansatz(theta: float) -> int { let q = qalloc(1); h(q[0]); ry(q[0], theta); return measure(q[0]); }");
+    assert!(code.contains("from qiskit.circuit import Parameter"), "{}", code);
+    assert!(code.contains("v0 = Parameter('theta')"), "{}", code);
+    assert!(code.contains("circuit.ry(v0, 0)"), "{}", code);
+    assert!(code.contains("\"ansatz\": (\"theta\",),"), "{}", code);
+    assert!(code.contains("circuit = circuit.assign_parameters("), "{}", code);
+}
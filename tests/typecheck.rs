@@ -0,0 +1,195 @@
+// Type checker: programs it must reject, and the message it gives.
+
+fn check(source: &str) -> anyhow::Result<()> {
+    quarkdsl::typecheck(&quarkdsl::parse(source).unwrap())
+}
+
+fn type_error(source: &str) -> String {
+    format!("{:#}", check(source).unwrap_err())
+}
+
+#[test]
+fn direct_recursion_is_rejected() {
+    let error = type_error("fn f(n: int) -> int { return f(n - 1); }");
+    assert!(error.contains("recursion is not supported: f -> f"), "{}", error);
+}
+
+#[test]
+fn mutual_recursion_reports_the_cycle() {
+    let error = type_error(
+        "fn f(n: int) -> int { return g(n); }
+         fn g(n: int) -> int { return h(n); }
+         fn h(n: int) -> int { return f(n); }",
+    );
+    assert!(error.contains("recursion is not supported: f -> g -> h -> f"), "{}", error);
+
+    // Calling the same helper twice is not a cycle
+    check("fn g(n: int) -> int { return n; } fn f(n: int) -> int { return g(n) + g(n); }").unwrap();
+}
+
+#[test]
+fn array_builtins_have_static_sizes() {
+    use quarkdsl::frontend::ast::Type;
+
+    let source = "fn main() -> float { let z = zeros(4); let o = ones(3); let r = range(5); return z[0] + o[1]; }";
+    let types = quarkdsl::frontend::infer_types(&quarkdsl::parse(source).unwrap()).unwrap();
+    assert_eq!(
+        types[0].lets,
+        vec![
+            ("z".to_string(), Type::Array(Box::new(Type::Float), Some(4))),
+            ("o".to_string(), Type::Array(Box::new(Type::Float), Some(3))),
+            ("r".to_string(), Type::Array(Box::new(Type::Int), Some(5))),
+        ]
+    );
+
+    let python = quarkdsl::compile_to_string(source, quarkdsl::Target::Orchestrator, false).unwrap();
+    for call in [" = np.zeros(4)\n", " = np.ones(3)\n", " = np.arange(5)\n"] {
+        assert!(python.contains(call), "{}", python);
+    }
+    let shader = quarkdsl::compile_to_string(source, quarkdsl::Target::Wgsl, false).unwrap();
+    assert!(shader.contains("for (var i: i32 = 0; i < 4; i++) { v0[i] = 0.0; }"), "{}", shader);
+}
+
+#[test]
+fn assignment_needs_let_mut() {
+    let error = type_error("fn f() -> int { let x = 1; x = 2; return x; }");
+    assert!(error.contains("cannot assign to immutable variable x"), "{}", error);
+    let error = type_error("fn f() -> int { let a = [1, 2]; a[0] = 2; return a[0]; }");
+    assert!(error.contains("cannot assign to immutable variable a"), "{}", error);
+
+    check("fn f() -> int { let mut x = 1; x = 2; let mut a = [1, 2]; a[0] = x; return a[0]; }").unwrap();
+}
+
+#[test]
+fn nested_assignment_peels_one_level_per_index() {
+    check("fn f() -> int { let mut m = [[1, 2], [3, 4]]; m[1][0] = 7; return m[1][0]; }").unwrap();
+
+    let error = type_error("fn f() -> int { let mut m = [[1, 2], [3, 4]]; m[1][0] = [5, 6]; return 0; }");
+    assert!(error.contains("Type mismatch in array assignment: expected int, got [int; 2]"), "{}", error);
+    let error = type_error("fn f() -> int { let mut m = [[1, 2], [3, 4]]; m[0][0][0] = 1; return 0; }");
+    assert!(error.contains("Cannot index non-array type int"), "{}", error);
+}
+
+#[test]
+fn empty_source_and_empty_body() {
+    for target in [quarkdsl::Target::Wgsl, quarkdsl::Target::Quantum, quarkdsl::Target::Orchestrator] {
+        let error = quarkdsl::compile_to_string("", target, false).unwrap_err();
+        assert_eq!(format!("{:#}", error), "no functions to compile");
+    }
+
+    let error = type_error("fn f() -> int { }");
+    assert!(error.contains("Function f returns int but its body is empty"), "{}", error);
+    check("fn f() -> void { }").unwrap();
+}
+
+#[test]
+fn builtin_float_constants() {
+    use quarkdsl::frontend::ast::Type;
+
+    let source = "@quantum fn q(t: float) -> int { let q = qalloc(2); ry(q[0], PI / 2.0); rz(q[1], t * TAU); return measure(q[0]); }
+                  fn c() -> float { let e = E; return PI + e; }";
+    let types = quarkdsl::frontend::infer_types(&quarkdsl::parse(source).unwrap()).unwrap();
+    assert_eq!(types[1].lets, vec![("e".to_string(), Type::Float)]);
+
+    // Folded during lowering, printed back symbolically
+    let python = quarkdsl::compile_to_string(source, quarkdsl::Target::Orchestrator, false).unwrap();
+    assert!(python.contains("circuit.ry((np.pi / 2), qr[0])\n"), "{}", python);
+    assert!(python.contains("circuit.rz((t * (2 * np.pi)), qr[1])\n"), "{}", python);
+    assert!(python.contains("return (np.pi + np.e)\n"), "{}", python);
+}
+
+#[test]
+fn errors_point_at_the_innermost_statement() {
+    use quarkdsl::frontend::SourceError;
+
+    fn location(source: &str) -> (usize, usize, &str) {
+        let error = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap_err();
+        let at = error.chain().find_map(|e| e.downcast_ref::<SourceError>()).expect("no location");
+        (at.line, at.column, &source[at.span.clone()])
+    }
+
+    let source = "fn f(c: bool) -> int {\n    let v = if c { let a = 1; a } else { let b: bool = 2; 3 };\n    return v;\n}\n";
+    assert_eq!(location(source), (2, 42, "let b: bool = 2;"));
+
+    let source = "fn f(n: int) -> int {\n    for i in 0..n {\n        if i > 2 { return true; }\n    }\n    return 0;\n}\n";
+    assert_eq!(location(source), (3, 20, "return true;"));
+
+    // Errors about the function as a whole point at its name
+    assert_eq!(location("fn f() -> int { }"), (1, 4, "f"));
+    assert_eq!(location("fn f(n: int) -> int { return f(n); }"), (1, 4, "f"));
+}
+
+#[test]
+fn qalloc_size_is_bounded() {
+    let error = type_error("@quantum fn q() -> int { let r = qalloc(100000000); return measure(r[0]); }");
+    assert!(error.contains("qalloc(100000000) is too large: a function can allocate at most 4096 qubits"), "{}", error);
+    let error = type_error("@quantum fn q() -> int { let r = qalloc(9223372036854775807); return measure(r[0]); }");
+    assert!(error.contains("is too large"), "{}", error);
+
+    check("@quantum fn q() -> int { let r = qalloc(4096); return measure(r[4095]); }").unwrap();
+}
+
+#[test]
+fn gates_take_qubits_not_indices() {
+    let error = type_error("@quantum fn q() -> int { h(0); cx(0, 1); return measure(1); }");
+    assert!(
+        error.contains("h expects a qubit, got int (argument 1): allocate a register with qalloc and pass an element such as q[0]"),
+        "{}",
+        error
+    );
+    let error = type_error("@quantum fn q() -> int { let r = qalloc(2); cx(r[0], 1.5); return measure(r[0]); }");
+    assert!(error.contains("cx expects a qubit, got float (argument 2)"), "{}", error);
+
+    check("@quantum fn q() -> int { let r = qalloc(3); for i in 0..3 { h(r[i]); } cx(r[0], r[2]); return measure(r[1]); }").unwrap();
+}
+
+#[test]
+fn qubits_support_no_arithmetic() {
+    let error = type_error("@quantum fn f() -> int { let q = qalloc(2); let s = q[0] + q[1]; return measure(q[0]); }");
+    assert!(error.contains("Arithmetic is not defined for qubits"), "{}", error);
+}
+
+#[test]
+fn assert_takes_a_bool() {
+    let error = type_error("fn main() -> int { assert(1); return 0; }");
+    assert!(error.contains("Argument type mismatch in call to assert: argument 1 expected bool, got int"), "{}", error);
+    check("fn main(x: int) -> int { assert(x > 0 && x < 10); return x; }").unwrap();
+}
+
+#[test]
+fn print_fmt_placeholders_match_values() {
+    let error = type_error("fn main() -> int { print_fmt(\"a {} {}\", 1); return 0; }");
+    assert!(error.contains("print_fmt format has 2 placeholders but 1 values were given"), "{}", error);
+    check("fn main() -> int { print_fmt(\"a {} {}\", 1, 2.5); return 0; }").unwrap();
+}
+
+#[test]
+fn infer_types_reports_signatures_and_lets() {
+    use quarkdsl::frontend::ast::{Domain, Type};
+
+    let source = "@gpu fn scale(xs: [float; 4], k: float) -> float { let y = xs[0] * k; let n = len(xs); return y; }";
+    let types = quarkdsl::frontend::infer_types(&quarkdsl::parse(source).unwrap()).unwrap();
+    let scale = &types[0];
+
+    assert_eq!((scale.name.as_str(), &scale.domain, &scale.return_type), ("scale", &Domain::Gpu, &Type::Float));
+    assert_eq!(
+        scale.params,
+        vec![("xs".to_string(), Type::Array(Box::new(Type::Float), Some(4))), ("k".to_string(), Type::Float)]
+    );
+    assert_eq!(scale.lets, vec![("y".to_string(), Type::Float), ("n".to_string(), Type::Int)]);
+
+    // Type errors still fail the whole program
+    assert!(quarkdsl::frontend::infer_types(&quarkdsl::parse("fn f() -> int { return 1.5; }").unwrap()).is_err());
+}
+
+#[test]
+fn extern_calls_are_checked_against_the_signature() {
+    let source = "extern fn helper(x: int) -> int;
+                  fn main() -> int { return helper(2) + 1; }";
+    let python = quarkdsl::compile_to_string(source, quarkdsl::Target::Orchestrator, false).unwrap();
+    assert!(python.contains("v0 = helper(2)"), "{}", python);
+    assert!(!python.contains("def helper"), "{}", python);
+
+    let error = type_error("extern fn helper(x: int) -> int; fn main() -> int { return helper(true); }");
+    assert!(error.contains("call to helper: argument 1 expected int, got bool"), "{}", error);
+}
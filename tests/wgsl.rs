@@ -0,0 +1,212 @@
+// WGSL backend: types, signatures and control flow as they appear in the
+// generated shader.
+
+use quarkdsl::{compile_to_string, Target};
+
+fn wgsl(source: &str) -> String {
+    compile_to_string(source, Target::Wgsl, false).unwrap()
+}
+
+#[test]
+fn sized_types() {
+    let source = "@gpu fn k(a: [float32; 4], b: [float64; 4], n: int32, m: int64) -> float32 { return a[0]; }";
+
+    // WGSL has no 64-bit types: float64/int64 are narrowed
+    let shader = wgsl(source);
+    assert!(
+        shader.contains("fn k(a: array<f32, 4>, b: array<f32, 4>, n: i32, m: i32) -> f32 {"),
+        "{}",
+        shader
+    );
+
+    // The orchestrator keeps the widths through NumPy
+    let python = compile_to_string(source, Target::Orchestrator, false).unwrap();
+    for conversion in [
+        "a = np.asarray(a, dtype=np.float32)",
+        "b = np.asarray(b, dtype=np.float64)",
+        "n = np.int32(n)",
+        "m = np.int64(m)",
+    ] {
+        assert!(python.contains(conversion), "{}", python);
+    }
+}
+
+#[test]
+fn matmul_on_matrices() {
+    let source = "@gpu fn mm(a: matrix<float, 2, 2>, b: matrix<float, 2, 2>) -> matrix<float, 2, 2> { return matmul(a, b); }";
+
+    let shader = wgsl(source);
+    assert!(shader.contains("-> array<array<f32, 2>, 2> {"), "{}", shader);
+    assert!(shader.contains("for (var k: i32 = 0; k < 2; k++) { acc += a[i][k] * b[k][j]; }"), "{}", shader);
+
+    let python = compile_to_string(source, Target::Orchestrator, false).unwrap();
+    assert!(python.contains(" = np.matmul(a, b)\n"), "{}", python);
+
+    let error = compile_to_string(
+        "fn bad(a: matrix<float, 2, 3>, b: matrix<float, 2, 2>) -> float { let c = matmul(a, b); return c[0][0]; }",
+        Target::Wgsl,
+        false,
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains("matmul dimension mismatch: 2x3 times 2x2"), "{:#}", error);
+}
+
+#[test]
+fn phi_becomes_assignments_in_each_arm() {
+    let shader = wgsl("@gpu fn pick(c: bool, x: float) -> float { let mut y = 0.0; if c { y = x; } else { y = x + 1.0; } return y; }");
+    assert!(!shader.contains("phi"), "{}", shader);
+
+    // The merged variable is written at the end of both arms and read after
+    let merged = shader.lines().find_map(|l| l.trim().strip_prefix("return ")).unwrap().trim_end_matches(';');
+    let then_arm = &shader[shader.find("if (c) {").unwrap()..shader.find("} else {").unwrap()];
+    let else_arm = &shader[shader.find("} else {").unwrap()..shader.find("// if_merge_0").unwrap()];
+    for arm in [then_arm, else_arm] {
+        assert!(arm.contains(&format!("    {} = ", merged)), "{}", shader);
+    }
+}
+
+#[test]
+fn void_function_has_no_return_type() {
+    let shader = wgsl("@gpu fn nothing(x: float) -> void { let y = x; }");
+    assert!(shader.contains("fn nothing(x: f32) {\n"), "{}", shader);
+    assert!(shader.contains("  return;\n}"), "{}", shader);
+    assert!(!shader.contains("void"), "{}", shader);
+}
+
+#[test]
+fn structured_if_and_for() {
+    let shader = wgsl("@gpu fn f(c: bool, x: float) -> float { let mut y = x; if c { y = x + 1.0; } return y; }");
+    assert!(shader.contains("  if (c) {\n"), "{}", shader);
+    assert!(!shader.contains("// branch"), "{}", shader);
+
+    let shader = wgsl("@gpu fn loop_sum(n: int) -> int { let mut s = 0; for i in 0..n { s = s + i; } return s; }");
+    assert!(shader.contains("  loop {\n"), "{}", shader);
+    assert!(shader.contains(" < n;\n    if (!("), "{}", shader);
+    assert!(shader.contains(")) { break; }\n"), "{}", shader);
+    assert!(shader.contains("    continuing {\n"), "{}", shader);
+}
+
+#[test]
+fn elementwise_array_arithmetic() {
+    let source = "@gpu fn add(a: [float; 4], b: [float; 4]) -> [float; 4] { let c = a + b; return c; }";
+
+    let types = quarkdsl::frontend::infer_types(&quarkdsl::parse(source).unwrap()).unwrap();
+    let float4 = quarkdsl::frontend::ast::Type::Array(Box::new(quarkdsl::frontend::ast::Type::Float), Some(4));
+    assert_eq!(types[0].lets, vec![("c".to_string(), float4)]);
+
+    let python = compile_to_string(source, Target::Orchestrator, false).unwrap();
+    assert!(python.contains("    return (np.asarray(a) + b)\n"), "{}", python);
+    let shader = wgsl(source);
+    assert!(shader.contains("for (var i: i32 = 0; i < 4; i++) { v2[i] = a[i] + b[i]; }"), "{}", shader);
+
+    // Classical code keeps array + array an error
+    let error = compile_to_string(
+        "fn f(a: [float; 4], b: [float; 4]) -> float { let c = a + b; return c[0]; }",
+        Target::Orchestrator,
+        false,
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains("Elementwise array arithmetic is only allowed in @gpu functions"));
+}
+
+#[test]
+fn unsized_array_parameters_are_storage_bindings() {
+    let shader = wgsl("@gpu fn first(xs: [float], w: [float; 4]) -> float { return xs[0] + w[0]; }");
+    assert!(shader.contains("@group(0) @binding(0) var<storage, read> xs: array<f32>;\n"), "{}", shader);
+    // The sized array stays a value parameter
+    assert!(shader.contains("fn first(w: array<f32, 4>) -> f32 {"), "{}", shader);
+
+    // Written elements make the buffer read_write
+    let shader = wgsl("@gpu fn scale(xs: [float], k: float) -> void { xs[0] = xs[0] * k; }");
+    assert!(shader.contains("@group(0) @binding(0) var<storage, read_write> xs: array<f32>;\n"), "{}", shader);
+}
+
+#[test]
+fn computed_array_literal_is_built_by_stores() {
+    let shader = wgsl("@gpu fn k(x: float, y: float, z: float) -> float { let a = [x, y, z]; let b = [1.0, 2.0]; return a[1] + b[0]; }");
+    assert!(shader.contains("  v3[0] = x;\n  v3[1] = y;\n  v3[2] = z;\n"), "{}", shader);
+    assert!(!shader.contains("array(x"), "{}", shader);
+    // Constant elements keep the constructor
+    assert!(shader.contains(" = array(1.0, 2.0);\n"), "{}", shader);
+}
+
+#[test]
+fn len_of_a_buffer_uses_array_length() {
+    let shader = wgsl("@gpu fn total(xs: [float]) -> int { return len(xs); }");
+    assert!(shader.contains(" = i32(arrayLength(&xs));\n"), "{}", shader);
+
+    // A sized array's length is a constant
+    let shader = wgsl("@gpu fn total(xs: [float; 8]) -> int { return len(xs); }");
+    assert!(!shader.contains("arrayLength"), "{}", shader);
+    assert!(shader.contains("return 8;"), "{}", shader);
+}
+
+#[test]
+fn strict_rejects_host_builtins() {
+    let source = "fn main() -> int { print(1); print_fmt(\"a\\nb {}\", 1); return 0; }";
+    let ir = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+
+    // Left for the reader to replace by default...
+    let shader = quarkdsl::codegen(&ir, Target::Wgsl, None, false).unwrap();
+    assert!(shader.contains("print(1);"), "{}", shader);
+
+    // ...an error naming the call with --strict
+    let error = quarkdsl::codegen(&ir, Target::Wgsl, None, true).unwrap_err();
+    assert_eq!(error.to_string(), "unsupported instruction in WGSL function 'main': %0 = call print(1)");
+
+    // Calls WGSL can express are fine
+    let ir = quarkdsl::compile_to_ir("@gpu fn total(xs: [float]) -> float { return sum(xs) + to_float(len(xs)); }", false, &Default::default()).unwrap();
+    quarkdsl::codegen(&ir, Target::Wgsl, None, true).unwrap();
+}
+
+#[test]
+fn split_output_shares_one_preamble() {
+    let source = "@gpu fn scale(xs: [float]) -> float { return xs[0] * 2.0; }
+                  @gpu fn shift(ys: [float]) -> float { return ys[0] + 1.0; }";
+    let ir = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+    let files = quarkdsl::backend::wgsl::codegen_split(&ir, false).unwrap();
+
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["preamble.wgsl", "scale.wgsl", "shift.wgsl"]);
+
+    // Bindings are numbered across the module and all live in the preamble
+    let preamble = &files[0].1;
+    assert!(preamble.contains("@group(0) @binding(0) var<storage, read> xs: array<f32>;"), "{}", preamble);
+    assert!(preamble.contains("@group(0) @binding(1) var<storage, read> ys: array<f32>;"), "{}", preamble);
+    assert!(!files[1].1.contains("@binding"), "{}", files[1].1);
+    assert!(files[1].1.contains("fn scale() -> f32 {"), "{}", files[1].1);
+}
+
+#[test]
+fn written_buffer_is_read_write() {
+    let source = "@gpu fn scale(xs: [float], k: float) -> void {
+                      for i in 0..len(xs) { xs[i] = xs[i] * k; }
+                  }
+                  @gpu fn first(ys: [float]) -> float { return ys[0]; }";
+    let ir = quarkdsl::compile_to_ir(source, false, &Default::default()).unwrap();
+    let files = quarkdsl::codegen_all(&ir, None, false).unwrap();
+
+    let (_, shader) = files.iter().find(|(name, _)| *name == "out.wgsl").unwrap();
+    assert!(shader.contains("var<storage, read_write> xs: array<f32>;"), "{}", shader);
+    assert!(shader.contains("var<storage, read> ys: array<f32>;"), "{}", shader);
+
+    // The NumPy simulation updates the buffer in place and hands it back
+    let (_, python) = files.iter().find(|(name, _)| *name == "orchestrator.py").unwrap();
+    let store = python.lines().find(|line| line.trim_start().starts_with("xs[")).expect(python);
+    assert!(store.ends_with(" * k)"), "{}", python);
+    assert!(python.contains("    return xs\n"), "{}", python);
+}
+
+#[test]
+fn atomic_updates_make_the_buffer_atomic() {
+    let shader = wgsl(
+        "@gpu fn count(xs: [int], bins: [int]) -> void {
+             for i in 0..len(xs) { atomic_add(bins, xs[i], 1); }
+             bins[0] = bins[1];
+         }",
+    );
+    assert!(shader.contains("var<storage, read_write> bins: array<atomic<i32>>;"), "{}", shader);
+    assert!(shader.contains("atomicAdd(&bins[v5], 1);"), "{}", shader);
+    // Its plain loads and stores become atomic too
+    assert!(shader.contains("atomicLoad(&bins[1])"), "{}", shader);
+}
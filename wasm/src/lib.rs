@@ -0,0 +1,39 @@
+//! Browser entry points for the QuarkDSL compiler (built with wasm-pack)
+//!
+//! Compiles from a source string to a string: no filesystem, and log
+//! lines go to a JavaScript callback instead of stderr.
+
+use clap::ValueEnum;
+use wasm_bindgen::prelude::*;
+
+use quarkdsl::Target;
+
+/// Compile DSL source for one target (`wgsl`, `quantum`, or `orchestrator`),
+/// with optimizations on. Errors come back as the full message chain.
+#[wasm_bindgen]
+pub fn compile(source: &str, target: &str) -> Result<String, JsValue> {
+    compile_native(source, target).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Send INFO/WARNING lines to `callback(line)` instead of the console.
+#[wasm_bindgen(js_name = setLogCallback)]
+pub fn set_log_callback(callback: js_sys::Function) {
+    quarkdsl::log::set_sink(Some(Box::new(move |line: &str| {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_str(line));
+    })));
+}
+
+// The part of `compile` that does not touch JsValue, so native builds can
+// check it against the CLI
+pub fn compile_native(source: &str, target: &str) -> Result<String, String> {
+    let target = match Target::from_str(target, true) {
+        Ok(Target::All) | Err(_) => {
+            return Err(format!(
+                "Unknown target '{}' (expected wgsl, quantum, or orchestrator)",
+                target
+            ))
+        }
+        Ok(target) => target,
+    };
+    quarkdsl::compile_to_string(source, target, true).map_err(|e| format!("{:#}", e))
+}
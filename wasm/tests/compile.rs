@@ -0,0 +1,23 @@
+// The browser entry point gives the same code as the library the CLI uses.
+
+use quarkdsl::{compile_to_string, Target};
+use quarkdsl_wasm::compile_native;
+
+const SOURCE: &str = "@quantum fn bell() -> int { let q = qalloc(2); h(q[0]); cx(q[0], q[1]); return measure(q[1]); }
+fn main() -> int { let a = [1.0, 2.0]; return bell() + 1; }";
+
+#[test]
+fn matches_the_cli_for_each_target() {
+    for (name, target) in [("wgsl", Target::Wgsl), ("quantum", Target::Quantum), ("orchestrator", Target::Orchestrator)] {
+        assert_eq!(compile_native(SOURCE, name).unwrap(), compile_to_string(SOURCE, target, true).unwrap(), "{}", name);
+    }
+}
+
+#[test]
+fn errors_are_message_chains() {
+    let error = compile_native("fn f() -> int { return x; }", "wgsl").unwrap_err();
+    assert_eq!(error, format!("{:#}", compile_to_string("fn f() -> int { return x; }", Target::Wgsl, true).unwrap_err()));
+
+    let error = compile_native(SOURCE, "all").unwrap_err();
+    assert_eq!(error, "Unknown target 'all' (expected wgsl, quantum, or orchestrator)");
+}